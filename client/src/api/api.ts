@@ -10,6 +10,8 @@ interface ApiOptions {
   returnType?: "json" | "text" | "blob" | "response";
   /** Whether to redirect to login page if HTTP 401 Unauthorized is returned */
   redirectOnUnauthorized?: boolean;
+  /** Extra request headers */
+  headers?: Record<string, string>;
 }
 
 /** Function for making API requests */
@@ -29,6 +31,7 @@ export async function api(
     credentials: "include",
     headers: {
       "Content-Type": "application/json",
+      ...options?.headers,
     },
     method,
     mode: "cors",
@@ -10,7 +10,7 @@ export class ContentClient {
   /** Gets content of a file */
   static async getContent(filename: string): Promise<string> {
     filename = encodeURIComponent(filename);
-    return await api(`content/${filename}`, "GET", undefined, {
+    return await api(`content/files/${filename}`, "GET", undefined, {
       returnType: "text",
     });
   }
@@ -18,7 +18,7 @@ export class ContentClient {
   /** Sets content of a file */
   static async setContent(filename: string, content: string): Promise<void> {
     filename = encodeURIComponent(filename);
-    return await api(`content/${filename}`, "PUT", content, {
+    return await api(`content/files/${filename}`, "PUT", content, {
       bodyJson: false,
     });
   }
@@ -0,0 +1,277 @@
+import { api } from "./api";
+import type {
+  ExportedServiceData,
+  ExportedServiceItem,
+} from "@/stores/service";
+
+/** Service stored on the server */
+export interface StoredService extends ExportedServiceData {
+  id: string;
+  /** Date of the service (YYYY-MM-DD) */
+  date?: string | null;
+  /** Incremented every time the service is changed */
+  revision: number;
+  createdAt: string;
+  updatedAt: string;
+}
+
+/** Service in the list of stored services */
+export interface ServiceSummary {
+  id: string;
+  title?: string | null;
+  date?: string | null;
+  revision: number;
+  updatedAt: string;
+}
+
+/** Change to a single part of a service, made while editing it live */
+export type ServiceOperation =
+  | { type: "insert"; index: number; item: ExportedServiceItem }
+  | { type: "move"; item_id: string; index: number }
+  | { type: "update"; item: ExportedServiceItem }
+  | { type: "delete"; item_id: string }
+  | {
+      type: "details";
+      title?: string | null;
+      description?: string | null;
+      languages?: string[];
+    };
+
+/** Change to a stored service */
+export type ServiceUpdate =
+  | { type: "saved"; service: StoredService }
+  | { type: "deleted"; id: string }
+  | {
+      type: "operation";
+      id: string;
+      revision: number;
+      op_id: string;
+      operation: ServiceOperation;
+    };
+
+/** Message received from the service updates websocket */
+export type UpdatesMessage = ServiceUpdate | { type: "resync" };
+
+/** Operation sent while editing a service live */
+export interface LiveRequest {
+  op_id: string;
+  operation: ServiceOperation;
+}
+
+/** Message received while editing a service live */
+export type LiveMessage =
+  | ServiceUpdate
+  | { type: "rejected"; op_id: string }
+  | { type: "error"; error: string };
+
+/** Service sent to the server */
+export interface ServiceRequest extends ExportedServiceData {
+  date?: string | null;
+}
+
+/** Error thrown when a stored service was changed by someone else */
+export class ServiceConflictError extends Error {
+  constructor() {
+    super("The service has been changed by someone else");
+  }
+}
+
+/** Throws an error if a response is not ok */
+async function checkResponse(response: Response): Promise<void> {
+  if (response.status == 412) {
+    throw new ServiceConflictError();
+  }
+  if (!response.ok) {
+    throw new Error(await response.text());
+  }
+}
+
+/** Handles services stored on the server */
+export class ServicesClient {
+  /** Lists stored services between two dates (YYYY-MM-DD, inclusive), newest first */
+  static async listServices(
+    from?: string,
+    to?: string
+  ): Promise<ServiceSummary[]> {
+    const query = new URLSearchParams();
+    if (from) query.set("from", from);
+    if (to) query.set("to", to);
+    return await api(`services?${query}`, "GET");
+  }
+
+  /** Gets a stored service */
+  static async getService(id: string): Promise<StoredService> {
+    id = encodeURIComponent(id);
+    return await api(`services/${id}`, "GET");
+  }
+
+  /** Stores a new service */
+  static async createService(service: ServiceRequest): Promise<StoredService> {
+    return await api("services", "POST", service);
+  }
+
+  /**
+   * Replaces a stored service.
+   * If a revision is given, a ServiceConflictError is thrown if the service has been changed since.
+   */
+  static async updateService(
+    id: string,
+    service: ServiceRequest,
+    revision?: number
+  ): Promise<StoredService> {
+    id = encodeURIComponent(id);
+    const response: Response = await api(`services/${id}`, "PUT", service, {
+      headers: revision != undefined ? { "If-Match": `"${revision}"` } : {},
+      returnType: "response",
+    });
+    await checkResponse(response);
+    return await response.json();
+  }
+
+  /** Converts a Planning Center Services plan export into a service, matching its songs to the song library */
+  static async importPlanningCenter(planExport: any): Promise<PlanImport> {
+    return await api("services/import/planning-center", "POST", planExport);
+  }
+
+  /** Renders a service as a print-ready HTML page, with its songs' lyrics (and chords if requested) */
+  static async printService(
+    service: ExportedServiceData,
+    chords: boolean
+  ): Promise<string> {
+    return await api(`services/print?chords=${chords}`, "POST", service, {
+      returnType: "text",
+    });
+  }
+
+  /**
+   * Deletes a stored service.
+   * If a revision is given, a ServiceConflictError is thrown if the service has been changed since.
+   */
+  static async deleteService(id: string, revision?: number): Promise<void> {
+    id = encodeURIComponent(id);
+    const response: Response = await api(`services/${id}`, "DELETE", undefined, {
+      headers: revision != undefined ? { "If-Match": `"${revision}"` } : {},
+      returnType: "response",
+    });
+    await checkResponse(response);
+  }
+}
+
+/** Song in an imported Planning Center plan */
+export interface ImportedSong {
+  /** Position of the item in the service */
+  item: number;
+  /** Song title in Planning Center */
+  title: string;
+  ccli_number?: string;
+  /** Title of the song in the song library (null if it isn't in the song library) */
+  matched: string | null;
+  matched_by?: "ccli_number" | "title";
+  arrangement?: string;
+  arrangement_matched: boolean;
+}
+
+/** Service converted from a Planning Center plan */
+export interface PlanImport {
+  service: ExportedServiceData;
+  date: string | null;
+  songs: ImportedSong[];
+}
+
+/** Service template stored on the server */
+export interface ServiceTemplate extends ExportedServiceData {
+  id: string;
+  name: string;
+  createdAt: string;
+  updatedAt: string;
+}
+
+/** Service template in the list of templates */
+export interface TemplateSummary {
+  id: string;
+  name: string;
+  updatedAt: string;
+  /** Names of the placeholders (e.g. `speaker` for `{{speaker}}`) in the template */
+  placeholders: string[];
+}
+
+/** Handles service templates stored on the server */
+export class ServiceTemplatesClient {
+  /** Lists templates by name */
+  static async listTemplates(): Promise<TemplateSummary[]> {
+    return await api("services/templates", "GET");
+  }
+
+  /** Stores a new template */
+  static async createTemplate(
+    name: string,
+    service: ExportedServiceData
+  ): Promise<ServiceTemplate> {
+    return await api("services/templates", "POST", { ...service, name });
+  }
+
+  /** Deletes a template */
+  static async deleteTemplate(id: string): Promise<void> {
+    id = encodeURIComponent(id);
+    return await api(`services/templates/${id}`, "DELETE");
+  }
+
+  /**
+   * Stores a new service created from a template, with the placeholders filled in
+   * (`{{date}}` is the service date unless another value is given)
+   */
+  static async instantiateTemplate(
+    id: string,
+    date: string | null,
+    values: Record<string, string>
+  ): Promise<StoredService> {
+    id = encodeURIComponent(id);
+    return await api(`services/templates/${id}/instantiate`, "POST", {
+      date,
+      values,
+    });
+  }
+}
+
+/** Position in the service being shown on the displays */
+export interface LiveCursor {
+  serviceId: string;
+  itemId?: string;
+  itemIndex: number;
+  /** Position of the verse in the item's verses (0 for items other than songs) */
+  verseIndex: number;
+  /** Sub item ID of the verse (or "0" for items other than songs) */
+  subItem: string;
+  verse?: string;
+}
+
+/** Moves the live cursor, which shows verses and items on the displays from the server */
+export class LiveCursorClient {
+  /** Gets the live cursor (null if no service is live) */
+  static async getCursor(): Promise<LiveCursor | null> {
+    return await api("services/cursor", "GET");
+  }
+
+  /** Shows a verse or item of a stored service, making it the live service */
+  static async goto(
+    serviceId: string,
+    item: number,
+    verse: number
+  ): Promise<LiveCursor | null> {
+    return await api("services/cursor/goto", "POST", {
+      serviceId,
+      item,
+      verse,
+    });
+  }
+
+  /** Shows the next enabled verse or item of the live service */
+  static async next(): Promise<LiveCursor | null> {
+    return await api("services/cursor/next", "POST");
+  }
+
+  /** Shows the previous enabled verse or item of the live service */
+  static async previous(): Promise<LiveCursor | null> {
+    return await api("services/cursor/previous", "POST");
+  }
+}
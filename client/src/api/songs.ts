@@ -0,0 +1,29 @@
+import { api } from "./api";
+import type { Song } from "@/stores/song";
+
+/** Handles songs in the song library */
+export class SongsClient {
+  /** Adds a new song */
+  static async createSong(title: string, song: Song): Promise<void> {
+    return await api("songs", "POST", { ...song, title });
+  }
+
+  /** Replaces a song, renaming it if the new title is different */
+  static async updateSong(
+    title: string,
+    newTitle: string,
+    song: Song
+  ): Promise<void> {
+    title = encodeURIComponent(title);
+    return await api(`songs/titles/${title}`, "PUT", {
+      ...song,
+      title: newTitle,
+    });
+  }
+
+  /** Removes a song */
+  static async deleteSong(title: string): Promise<void> {
+    title = encodeURIComponent(title);
+    return await api(`songs/titles/${title}`, "DELETE");
+  }
+}
@@ -18,6 +18,14 @@ export interface DisplayConfig {
   fade_speed?: number;
   font_size?: string;
   background?: string;
+  /** Languages to show song translations in (default is all languages in the state) */
+  languages?: Array<string>;
+  /** How to show song translations */
+  translation_layout?: "side_by_side" | "stacked";
+  /** Most lines on a slide (used by the server to split verses into slides) */
+  max_lines_per_slide?: number;
+  /** Most characters on a slide (used by the server to split verses into slides) */
+  max_characters_per_slide?: number;
 }
 
 /** Config store */
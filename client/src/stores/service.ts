@@ -1,11 +1,31 @@
 import { defineStore } from "pinia";
 import { computed, nextTick, ref, watch } from "vue";
 
+import { API_URI } from "@/api/api";
+import {
+  LiveCursorClient,
+  ServiceConflictError,
+  ServicesClient,
+  ServiceTemplatesClient,
+  type LiveCursor,
+  type LiveMessage,
+  type LiveRequest,
+  type ServiceOperation,
+  type ServiceSummary,
+  type StoredService,
+  type TemplateSummary,
+  type UpdatesMessage,
+} from "@/api/services";
+import { clone } from "@/helpers/clone";
 import { uuid } from "@/helpers/random";
 import { natcasecmp } from "@/helpers/sort";
 
-import { useSongStore } from "./song";
-import { useStateStore, type StateContent } from "./state";
+import { useSongStore, type Song } from "./song";
+import {
+  useStateStore,
+  type SongLanguageText,
+  type StateContent,
+} from "./state";
 import { useConfigStore } from "./config";
 
 /** Service data */
@@ -13,6 +33,8 @@ export interface ServiceData {
   serviceItems: Array<ServiceItem>;
   title?: string;
   description?: string;
+  /** Languages to show songs in (the first is the primary language) */
+  languages?: Array<string>;
 }
 
 /** Service item */
@@ -29,14 +51,19 @@ export interface ServiceItem {
 export interface ServiceSong {
   title: string;
   verses: Array<string>;
+  /** Name of the song arrangement to sing the verses in (used instead of the selected verses) */
+  arrangement?: string;
 }
 
 export interface ExportedServiceData {
   serviceItems: Array<ExportedServiceItem>;
   title?: string;
   description?: string;
+  languages?: Array<string>;
 }
 export interface ExportedServiceItem {
+  /** Item ID (kept so that items can be edited live by several people) */
+  id?: string;
   type: "empty" | "song" | "mainText" | "subText" | "smallText";
   song?: ExportedServiceSong;
   text?: string;
@@ -46,6 +73,7 @@ export interface ExportedServiceItem {
 export interface ExportedServiceSong {
   title: string;
   verses: Array<string>;
+  arrangement?: string;
 }
 
 /** Data for drag and drop */
@@ -76,6 +104,15 @@ export const useServiceStore = defineStore("service", () => {
     () => JSON.stringify(serviceData.value) != savedServiceData.value
   );
 
+  /** Service stored on the server that is being edited (null if it isn't stored on the server) */
+  const serverService = ref<{
+    id: string;
+    revision: number;
+    date: string | null;
+  } | null>(null);
+  /** Whether the stored service was changed by someone else while there were unsaved changes */
+  const serverServiceChanged = ref(false);
+
   /** Selected item index */
   const selectedItemIndex = ref<number | null>(null);
   /** Selected sub item id */
@@ -186,6 +223,68 @@ export const useServiceStore = defineStore("service", () => {
     serviceData.value.serviceItems = [];
   }
 
+  /**
+   * Sub items of a song: the verses of its arrangement in order,
+   * or its verse order if no arrangement is chosen
+   * (or all verses sorted by name if it doesn't have one)
+   */
+  function songSubItems(
+    song: ServiceSong | undefined
+  ): Array<{ id: string; verse: string; arranged: boolean }> {
+    const librarySong = songStore.songs[song?.title ?? ""];
+    const arrangement =
+      song?.arrangement != undefined
+        ? librarySong?.arrangements?.[song.arrangement]
+        : undefined;
+
+    if (arrangement != undefined) {
+      // verses can be repeated in an arrangement, so the sub items are numbered
+      return arrangement.map((verse, index) => ({
+        id: `${index + 1}:${verse}`,
+        verse,
+        arranged: true,
+      }));
+    }
+
+    const verseOrder = (librarySong?.verseOrder ?? []).filter(
+      (verse) => librarySong?.verses[verse] != undefined
+    );
+    if (verseOrder.length > 0) {
+      // verses can be repeated in the verse order too, but only the chosen verses are shown
+      return verseOrder.map((verse, index) => ({
+        id: `${index + 1}:${verse}`,
+        verse,
+        arranged: false,
+      }));
+    }
+
+    return Object.keys(librarySong?.verses ?? {})
+      .sort((a, b) => natcasecmp([a, b]))
+      .map((verse) => ({ id: verse, verse, arranged: false }));
+  }
+
+  /**
+   * Gets a verse in each of the service languages that the song has.
+   * Songs without a language are assumed to be in the primary language.
+   */
+  function songLanguageTexts(
+    song: Song,
+    verseName: string
+  ): Array<SongLanguageText> {
+    const languages = serviceData.value.languages ?? [];
+    const songLanguage = song.language ?? languages[0];
+
+    return languages
+      .map((language) => ({
+        language,
+        text:
+          language == songLanguage
+            ? song.verses[verseName]
+            : song.translations?.[language]?.[verseName],
+      }))
+      .filter((x): x is SongLanguageText => x.text != undefined);
+  }
+
   function getState(): StateContent {
     switch (selectedItemType.value) {
       case "empty": {
@@ -197,15 +296,24 @@ export const useServiceStore = defineStore("service", () => {
             ? songStore.songs[selectedItem.value.song.title]
             : null;
         const songVerses = song?.verses;
-        const verseContent =
-          (songVerses ?? {})[selectedSubItemId.value ?? ""] ?? undefined;
+        const verseName = songSubItems(selectedItem.value?.song).find(
+          (subItem) => subItem.id == selectedSubItemId.value
+        )?.verse;
+        const verseContent = (songVerses ?? {})[verseName ?? ""] ?? undefined;
+        const songLanguages =
+          song != null && verseContent != undefined
+            ? songLanguageTexts(song, verseName ?? "")
+            : [];
         return {
           background: true,
-          song: verseContent,
+          song: songLanguages[0]?.text ?? verseContent,
+          songLanguages: songLanguages.length > 0 ? songLanguages : undefined,
           songTitle:
             (selectedItem.value?.text ?? "") != ""
               ? selectedItem.value?.text
               : selectedItem.value?.song?.title ?? "",
+          songId: selectedItem.value?.song?.title,
+          serviceTitle: serviceData.value.title,
         };
       }
       case "mainText": {
@@ -242,9 +350,46 @@ export const useServiceStore = defineStore("service", () => {
     }
     selectedSubItemId.value = subItemId;
 
+    // services edited live are shown by the server, so that it knows where the service is
+    if (await showOnServer()) return;
+
     stateStore.setState(getState());
   }
 
+  /** Shows the selected item with the live cursor, returning whether it could be shown */
+  async function showOnServer(): Promise<boolean> {
+    const item = selectedItem.value;
+    if (
+      liveServiceId == null ||
+      !liveConnected.value ||
+      pendingOperations.length > 0 ||
+      item == null ||
+      selectedItemIndex.value == null
+    ) {
+      return false;
+    }
+
+    const verse =
+      item.type == "song"
+        ? songSubItems(item.song).findIndex(
+            (subItem) => subItem.id == selectedSubItemId.value
+          )
+        : 0;
+    if (verse < 0) return false;
+
+    try {
+      liveCursor.value = await LiveCursorClient.goto(
+        liveServiceId,
+        selectedItemIndex.value,
+        verse
+      );
+      return true;
+    } catch (e) {
+      console.error(e);
+      return false;
+    }
+  }
+
   function showEmptyScreen() {
     stateStore.setState({ background: false });
   }
@@ -258,15 +403,13 @@ export const useServiceStore = defineStore("service", () => {
     return serviceData.value.serviceItems
       .map((item, index) => {
         if (item.type == "song") {
-          const songVerseTitlesSorted = Object.keys(
-            songStore.songs[item.song?.title ?? ""].verses ?? {}
-          ).sort((a, b) => natcasecmp([a, b]));
-          return songVerseTitlesSorted.map((verseTitle) => {
+          return songSubItems(item.song).map((subItem) => {
             const enabled =
-              (item.song?.verses.length == 0 ||
-                item.song?.verses.includes(verseTitle)) ??
+              (subItem.arranged ||
+                item.song?.verses.length == 0 ||
+                item.song?.verses.includes(subItem.verse)) ??
               false;
-            return { item: index, subitem: verseTitle, enabled };
+            return { item: index, subitem: subItem.id, enabled };
           });
         } else {
           return { item: index, subitem: "0", enabled: item.enabled };
@@ -386,8 +529,11 @@ export const useServiceStore = defineStore("service", () => {
           const convertedServiceData = convertImportToInternal(fileContent);
 
           // set serviceData
+          stopLiveEditing();
           serviceData.value = convertedServiceData;
           savedServiceData.value = JSON.stringify(serviceData.value);
+          serverService.value = null;
+          serverServiceChanged.value = false;
         } catch (e) {
           console.error(e);
           alert(
@@ -404,6 +550,67 @@ export const useServiceStore = defineStore("service", () => {
     }
   }
 
+  /**
+   * Imports a plan from a Planning Center Services export file, returning the plan date.
+   * Songs that aren't in the song library are listed (and named "Not in the song library" in the service).
+   */
+  function importPlanningCenterPlan(): Promise<string | null> {
+    return new Promise((resolve) => {
+      // create file dialog
+      const importFileInput = document.createElement("input");
+      importFileInput.setAttribute("type", "file");
+      importFileInput.setAttribute("accept", ".json,application/json");
+
+      // handle file selection
+      importFileInput.addEventListener("change", async () => {
+        try {
+          const rawFileContent =
+            (await importFileInput.files?.[0].text()) ?? "{}";
+          const planImport = await ServicesClient.importPlanningCenter(
+            JSON.parse(rawFileContent)
+          );
+
+          // unselect everything
+          selectedItemIndex.value = null;
+          selectedSubItemId.value = null;
+
+          stopLiveEditing();
+          serviceData.value = convertImportToInternal(planImport.service);
+          // the imported service hasn't been saved yet
+          savedServiceData.value = "";
+          serverService.value = null;
+          serverServiceChanged.value = false;
+
+          const unmatched = planImport.songs.filter(
+            (song) => song.matched == null
+          );
+          if (unmatched.length > 0) {
+            alert(
+              "These songs aren't in the song library:\n" +
+                unmatched
+                  .map(
+                    (song) =>
+                      song.title +
+                      (song.ccli_number ? ` (CCLI ${song.ccli_number})` : "")
+                  )
+                  .join("\n")
+            );
+          }
+          resolve(planImport.date);
+        } catch (e) {
+          console.error(e);
+          alert(
+            "An error occurred importing the plan. (Is it a Planning Center Services export?)"
+          );
+          resolve(null);
+        }
+      });
+
+      // open file dialog
+      importFileInput.click();
+    });
+  }
+
   /** Export service to file */
   async function exportService() {
     try {
@@ -449,30 +656,609 @@ export const useServiceStore = defineStore("service", () => {
     }
   }
 
+  /** Lists services stored on the server between two dates (YYYY-MM-DD, inclusive) */
+  async function listServerServices(
+    from?: string,
+    to?: string
+  ): Promise<ServiceSummary[]> {
+    return await ServicesClient.listServices(from, to);
+  }
+
+  /** Sets the service being edited to a service stored on the server */
+  function setServerService(service: StoredService) {
+    selectedItemIndex.value = null;
+    selectedSubItemId.value = null;
+
+    serviceData.value = convertImportToInternal(service);
+    savedServiceData.value = JSON.stringify(serviceData.value);
+    serverService.value = {
+      id: service.id,
+      revision: service.revision,
+      date: service.date ?? null,
+    };
+    serverServiceChanged.value = false;
+  }
+
+  /** Opens a service stored on the server (and starts editing it live) */
+  async function openServerService(id: string) {
+    try {
+      setServerService(await ServicesClient.getService(id));
+      startLiveEditing(id);
+      // carries on from where the service was if it is live
+      followCursor();
+    } catch (e) {
+      console.error(e);
+      alert("An error occurred opening the service.");
+    }
+  }
+
+  /**
+   * Saves the service to the server (as a new service if it isn't stored on the server yet).
+   * If someone else has changed the service since it was opened, asks before overwriting their changes.
+   */
+  async function saveServerService(date: string | null) {
+    const request = { ...convertInternalToExport(serviceData.value), date };
+    try {
+      let service: StoredService;
+      if (serverService.value == null) {
+        service = await ServicesClient.createService(request);
+      } else {
+        try {
+          service = await ServicesClient.updateService(
+            serverService.value.id,
+            request,
+            serverService.value.revision
+          );
+        } catch (e) {
+          if (
+            !(e instanceof ServiceConflictError) ||
+            !confirm(
+              "The service has been changed by someone else. Overwrite their changes?"
+            )
+          ) {
+            throw e;
+          }
+          service = await ServicesClient.updateService(
+            serverService.value.id,
+            request
+          );
+        }
+      }
+
+      savedServiceData.value = JSON.stringify(serviceData.value);
+      serverService.value = {
+        id: service.id,
+        revision: service.revision,
+        date: service.date ?? null,
+      };
+      serverServiceChanged.value = false;
+      if (liveServiceId != service.id) startLiveEditing(service.id);
+    } catch (e) {
+      console.error(e);
+      if (!(e instanceof ServiceConflictError)) {
+        alert("An error occurred saving the service.");
+      }
+    }
+  }
+
+  /** Deletes a service stored on the server */
+  async function deleteServerService(id: string) {
+    try {
+      await ServicesClient.deleteService(id);
+    } catch (e) {
+      console.error(e);
+      alert("An error occurred deleting the service.");
+    }
+  }
+
+  /** Opens the service as a print-ready page (order of service and lyrics) in a new window */
+  async function printService(chords: boolean) {
+    try {
+      const html = await ServicesClient.printService(
+        convertInternalToExport(serviceData.value),
+        chords
+      );
+      const objectURL = URL.createObjectURL(
+        new Blob([html], { type: "text/html" })
+      );
+      window.open(objectURL, "_blank");
+
+      // schedule object url revocation
+      window.setTimeout(() => URL.revokeObjectURL(objectURL), 1000 * 60 * 60);
+    } catch (e) {
+      console.error(e);
+      alert("An error occurred printing the service.");
+    }
+  }
+
+  /** Lists service templates stored on the server */
+  async function listTemplates(): Promise<TemplateSummary[]> {
+    return await ServiceTemplatesClient.listTemplates();
+  }
+
+  /** Saves the service as a template on the server */
+  async function saveAsTemplate(name: string) {
+    try {
+      await ServiceTemplatesClient.createTemplate(
+        name,
+        convertInternalToExport(serviceData.value)
+      );
+    } catch (e) {
+      console.error(e);
+      alert("An error occurred saving the template.");
+    }
+  }
+
+  /** Deletes a service template stored on the server */
+  async function deleteTemplate(id: string) {
+    try {
+      await ServiceTemplatesClient.deleteTemplate(id);
+    } catch (e) {
+      console.error(e);
+      alert("An error occurred deleting the template.");
+    }
+  }
+
+  /** Creates a service on the server from a template and opens it */
+  async function newServiceFromTemplate(
+    id: string,
+    date: string | null,
+    values: Record<string, string>
+  ) {
+    try {
+      const service = await ServiceTemplatesClient.instantiateTemplate(
+        id,
+        date,
+        values
+      );
+      setServerService(service);
+      startLiveEditing(service.id);
+    } catch (e) {
+      console.error(e);
+      alert("An error occurred creating the service from the template.");
+    }
+  }
+
+  const SERVICE_UPDATES_PATH = "api/services/updates";
+  const SERVICE_UPDATES_RECONNECT_DELAY = 5000;
+
+  /** Applies a change to a stored service made by anyone (including this client) */
+  function applyServiceUpdate(update: UpdatesMessage) {
+    const current = serverService.value;
+    // changes to a service being edited live are received from the live websocket
+    if (current == null || current.id == liveServiceId) return;
+
+    switch (update.type) {
+      case "saved":
+        if (
+          update.service.id != current.id ||
+          update.service.revision <= current.revision
+        ) {
+          return;
+        }
+        // local changes are never overwritten
+        if (unsavedChanges.value) {
+          serverServiceChanged.value = true;
+        } else {
+          setServerService(update.service);
+        }
+        break;
+      case "deleted":
+        if (update.id == current.id) {
+          // the service can be saved again as a new service
+          serverService.value = null;
+          serverServiceChanged.value = false;
+          savedServiceData.value = "";
+        }
+        break;
+      case "resync":
+        // some updates were missed, so the open service is checked for changes
+        resyncServerService(current.id);
+        break;
+    }
+  }
+
+  /** Gets a stored service again after missing updates, applying it like an update */
+  async function resyncServerService(id: string) {
+    try {
+      const service = await ServicesClient.getService(id);
+      applyServiceUpdate({ type: "saved", service });
+    } catch (e) {
+      console.error(e);
+    }
+  }
+
+  /** Receives changes to stored services, reconnecting if disconnected */
+  function subscribeToServiceUpdates() {
+    const wsUri = new URL(`${API_URI}${SERVICE_UPDATES_PATH}`);
+    wsUri.protocol = wsUri.protocol === "https:" ? "wss:" : "ws:";
+
+    const ws = new WebSocket(wsUri);
+    ws.addEventListener("message", (evt: MessageEvent<any>) => {
+      try {
+        applyServiceUpdate(JSON.parse(evt.data));
+      } catch (e) {
+        console.error(e);
+      }
+    });
+    ws.addEventListener("close", () => {
+      window.setTimeout(
+        subscribeToServiceUpdates,
+        SERVICE_UPDATES_RECONNECT_DELAY
+      );
+    });
+  }
+  subscribeToServiceUpdates();
+
+  // live editing: every change to the service is sent to the server as an operation on a single item,
+  // and the server applies everyone's operations one at a time and sends them to everyone editing the service
+
+  const LIVE_PATH = "api/services/{id}/live";
+  const LIVE_RECONNECT_DELAY = 1000;
+
+  let liveSocket: WebSocket | null = null;
+  /** ID of the service being edited live */
+  let liveServiceId: string | null = null;
+  /** Whether the live websocket is connected */
+  const liveConnected = ref(false);
+  /** Service as the server has it, with every operation applied so far */
+  let liveServerData: ServiceData | null = null;
+  /** Service as it was when local changes were last sent */
+  let liveSentData: ExportedServiceData | null = null;
+  /** Operations sent to the server that haven't been applied yet */
+  let pendingOperations: LiveRequest[] = [];
+  /** Whether the whole service is expected next (after connecting) */
+  let awaitingSnapshot = false;
+
+  // live cursor: the position in the service being shown, kept on the server
+
+  const CURSOR_PATH = "api/services/cursor/ws";
+  const CURSOR_RECONNECT_DELAY = 5000;
+
+  /** Position in the service being shown on the displays (null if no service is live) */
+  const liveCursor = ref<LiveCursor | null>(null);
+
+  /** Receives the live cursor whenever it moves, reconnecting if disconnected */
+  function subscribeToCursor() {
+    const wsUri = new URL(`${API_URI}${CURSOR_PATH}`);
+    wsUri.protocol = wsUri.protocol === "https:" ? "wss:" : "ws:";
+
+    const ws = new WebSocket(wsUri);
+    ws.addEventListener("message", (evt: MessageEvent<any>) => {
+      try {
+        const message = JSON.parse(evt.data);
+        if (message.cursor !== undefined) {
+          liveCursor.value = message.cursor;
+          followCursor();
+        }
+      } catch (e) {
+        console.error(e);
+      }
+    });
+    ws.addEventListener("close", () => {
+      window.setTimeout(subscribeToCursor, CURSOR_RECONNECT_DELAY);
+    });
+  }
+  subscribeToCursor();
+
+  /** Selects the verse or item the live cursor is on, if it is in the open service */
+  function followCursor() {
+    const cursor = liveCursor.value;
+    if (cursor == null || cursor.serviceId != serverService.value?.id) return;
+
+    const itemIndex = serviceData.value.serviceItems.findIndex(
+      (item) => item.id == cursor.itemId
+    );
+    const index = itemIndex >= 0 ? itemIndex : cursor.itemIndex;
+    if (index >= serviceData.value.serviceItems.length) return;
+
+    if (selectedItemIndex.value != index) {
+      selectedItemIndex.value = index;
+      // the sub item is unselected when a different index is selected
+      nextTick(() => (selectedSubItemId.value = cursor.subItem));
+    } else {
+      selectedSubItemId.value = cursor.subItem;
+    }
+  }
+
+  /** Starts editing a stored service live */
+  function startLiveEditing(id: string) {
+    stopLiveEditing();
+    liveServiceId = id;
+    connectLive(id);
+  }
+
+  /** Stops editing a stored service live */
+  function stopLiveEditing() {
+    liveServiceId = null;
+    liveConnected.value = false;
+    liveServerData = null;
+    liveSentData = null;
+    pendingOperations = [];
+    const socket = liveSocket;
+    liveSocket = null;
+    socket?.close();
+  }
+
+  function connectLive(id: string) {
+    const wsUri = new URL(
+      `${API_URI}${LIVE_PATH.replace("{id}", encodeURIComponent(id))}`
+    );
+    wsUri.protocol = wsUri.protocol === "https:" ? "wss:" : "ws:";
+
+    const socket = new WebSocket(wsUri);
+    liveSocket = socket;
+    awaitingSnapshot = true;
+    socket.addEventListener("open", () => {
+      if (liveSocket == socket) liveConnected.value = true;
+    });
+    socket.addEventListener("message", (evt: MessageEvent<any>) => {
+      if (liveSocket != socket) return;
+      try {
+        handleLiveMessage(JSON.parse(evt.data));
+      } catch (e) {
+        console.error(e);
+      }
+    });
+    socket.addEventListener("close", () => {
+      if (liveSocket != socket) return;
+      liveConnected.value = false;
+      liveSocket = null;
+      window.setTimeout(() => {
+        if (liveServiceId == id && liveSocket == null) connectLive(id);
+      }, LIVE_RECONNECT_DELAY);
+    });
+  }
+
+  function handleLiveMessage(message: LiveMessage) {
+    // local changes are sent first, so that they are based on the service before the message
+    sendLocalChanges();
+
+    switch (message.type) {
+      case "saved":
+        liveServerData = convertImportToInternal(message.service);
+        serverService.value = {
+          id: message.service.id,
+          revision: message.service.revision,
+          date: message.service.date ?? null,
+        };
+        if (awaitingSnapshot) {
+          // local changes that weren't applied before (re)connecting are sent again
+          awaitingSnapshot = false;
+          pendingOperations = [];
+          liveSentData = convertInternalToExport(liveServerData);
+          sendLocalChanges();
+        }
+        updateLocalService();
+        break;
+
+      case "deleted":
+        // the service can be saved again as a new service
+        stopLiveEditing();
+        serverService.value = null;
+        savedServiceData.value = "";
+        break;
+
+      case "operation": {
+        if (liveServerData == null) return;
+        applyOperation(liveServerData, message.operation);
+        if (serverService.value != null)
+          serverService.value.revision = message.revision;
+
+        const pendingIndex = pendingOperations.findIndex(
+          (request) => request.op_id == message.op_id
+        );
+        if (pendingIndex >= 0) {
+          pendingOperations.splice(pendingIndex, 1);
+        } else {
+          keepSelection(() =>
+            applyOperation(serviceData.value, message.operation)
+          );
+        }
+        updateLocalService();
+        break;
+      }
+
+      case "rejected":
+        pendingOperations = pendingOperations.filter(
+          (request) => request.op_id != message.op_id
+        );
+        updateLocalService();
+        break;
+
+      case "error":
+        console.error(message.error);
+        break;
+    }
+  }
+
+  /**
+   * Once every sent operation has been applied, replaces the local service with the server's
+   * (the server may have applied the operations in a different order)
+   */
+  function updateLocalService() {
+    if (liveServerData != null && pendingOperations.length == 0) {
+      const serverData = liveServerData;
+      if (
+        JSON.stringify(convertInternalToExport(serverData)) !=
+        JSON.stringify(convertInternalToExport(serviceData.value))
+      ) {
+        keepSelection(() => (serviceData.value = clone(serverData)));
+      }
+      savedServiceData.value = JSON.stringify(serviceData.value);
+    }
+    liveSentData = convertInternalToExport(serviceData.value);
+  }
+
+  /** Sends the changes made since changes were last sent */
+  function sendLocalChanges() {
+    if (liveSocket == null || !liveConnected.value || liveSentData == null)
+      return;
+
+    const current = convertInternalToExport(serviceData.value);
+    for (const operation of diffOperations(liveSentData, current)) {
+      const request: LiveRequest = { op_id: uuid(), operation };
+      pendingOperations.push(request);
+      liveSocket.send(JSON.stringify(request));
+    }
+    liveSentData = current;
+  }
+  watch(serviceData, sendLocalChanges, { deep: true });
+
+  /** Keeps the same item selected while items are added, moved and removed */
+  function keepSelection(change: () => void) {
+    const selectedId = selectedItem.value?.id;
+    const subItemId = selectedSubItemId.value;
+    change();
+
+    if (selectedId == undefined) return;
+    const index = serviceData.value.serviceItems.findIndex(
+      (item) => item.id == selectedId
+    );
+    if (index == selectedItemIndex.value) return;
+    selectedItemIndex.value = index >= 0 ? index : null;
+    // the sub item is unselected when a different index is selected
+    if (index >= 0) nextTick(() => (selectedSubItemId.value = subItemId));
+  }
+
+  /** Applies an operation to a service the same way as the server, returning whether it could be applied */
+  function applyOperation(
+    data: ServiceData,
+    operation: ServiceOperation
+  ): boolean {
+    const items = data.serviceItems;
+    const indexOf = (id: string | undefined) =>
+      items.findIndex((item) => item.id == id);
+
+    switch (operation.type) {
+      case "insert":
+        if (operation.item.id == undefined || indexOf(operation.item.id) >= 0)
+          return false;
+        items.splice(
+          Math.min(operation.index, items.length),
+          0,
+          convertItemToInternal(operation.item)
+        );
+        return true;
+      case "move": {
+        const index = indexOf(operation.item_id);
+        if (index < 0) return false;
+        const [item] = items.splice(index, 1);
+        items.splice(Math.min(operation.index, items.length), 0, item);
+        return true;
+      }
+      case "update": {
+        const index = indexOf(operation.item.id);
+        if (index < 0) return false;
+        items[index] = convertItemToInternal(operation.item);
+        return true;
+      }
+      case "delete": {
+        const index = indexOf(operation.item_id);
+        if (index < 0) return false;
+        items.splice(index, 1);
+        return true;
+      }
+      case "details":
+        if (operation.title != undefined) data.title = operation.title;
+        else delete data.title;
+        if (operation.description != undefined)
+          data.description = operation.description;
+        else delete data.description;
+        if (operation.languages != undefined && operation.languages.length > 0)
+          data.languages = operation.languages;
+        else delete data.languages;
+        return true;
+    }
+  }
+
+  /** Gets the operations that change one service into another */
+  function diffOperations(
+    from: ExportedServiceData,
+    to: ExportedServiceData
+  ): ServiceOperation[] {
+    const operations: ServiceOperation[] = [];
+
+    // removed items
+    const toIds = new Set(to.serviceItems.map((item) => item.id));
+    for (const item of from.serviceItems) {
+      if (!toIds.has(item.id))
+        operations.push({ type: "delete", item_id: item.id! });
+    }
+
+    // added and moved items
+    const ids = from.serviceItems
+      .map((item) => item.id)
+      .filter((id) => toIds.has(id));
+    to.serviceItems.forEach((item, index) => {
+      const currentIndex = ids.indexOf(item.id);
+      if (currentIndex < 0) {
+        operations.push({ type: "insert", index, item });
+        ids.splice(index, 0, item.id);
+      } else if (currentIndex != index) {
+        operations.push({ type: "move", item_id: item.id!, index });
+        ids.splice(currentIndex, 1);
+        ids.splice(index, 0, item.id);
+      }
+    });
+
+    // changed items
+    const fromItems = new Map(
+      from.serviceItems.map((item) => [item.id, JSON.stringify(item)])
+    );
+    for (const item of to.serviceItems) {
+      const fromItem = fromItems.get(item.id);
+      if (fromItem != undefined && fromItem != JSON.stringify(item))
+        operations.push({ type: "update", item });
+    }
+
+    if (
+      from.title != to.title ||
+      from.description != to.description ||
+      JSON.stringify(from.languages ?? []) != JSON.stringify(to.languages ?? [])
+    ) {
+      operations.push({
+        type: "details",
+        title: to.title ?? null,
+        description: to.description ?? null,
+        languages: to.languages ?? [],
+      });
+    }
+
+    return operations;
+  }
+
   function convertInternalToExport(
     serviceData: ServiceData
   ): ExportedServiceData {
     let ret: ExportedServiceData = {
-      serviceItems: serviceData.serviceItems.map((item) => {
-        let ret: ExportedServiceItem = {
-          type: item.type,
-        };
-        if (item.song != undefined) {
-          let song: ExportedServiceSong = {
-            title: item.song.title,
-            verses: item.song.verses.map((verse) => verse),
-          };
-          ret.song = song;
-        }
-        if (item.text != undefined) ret.text = item.text;
-        if (item.comment != undefined) ret.comment = item.comment;
-        if (!item.enabled) ret.enabled = false;
-        return ret;
-      }),
+      serviceItems: serviceData.serviceItems.map(convertItemToExport),
     };
     if (serviceData.title != undefined) ret.title = serviceData.title;
     if (serviceData.description != undefined)
       ret.description = serviceData.description;
+    if (serviceData.languages != undefined)
+      ret.languages = serviceData.languages.map((language) => language);
+    return ret;
+  }
+
+  function convertItemToExport(item: ServiceItem): ExportedServiceItem {
+    let ret: ExportedServiceItem = {
+      id: item.id,
+      type: item.type,
+    };
+    if (item.song != undefined) {
+      let song: ExportedServiceSong = {
+        title: item.song.title,
+        verses: item.song.verses.map((verse) => verse),
+      };
+      if (item.song.arrangement != undefined)
+        song.arrangement = item.song.arrangement;
+      ret.song = song;
+    }
+    if (item.text != undefined) ret.text = item.text;
+    if (item.comment != undefined) ret.comment = item.comment;
+    if (!item.enabled) ret.enabled = false;
     return ret;
   }
 
@@ -480,33 +1266,43 @@ export const useServiceStore = defineStore("service", () => {
     serviceData: ExportedServiceData
   ): ServiceData {
     let ret: ServiceData = {
-      serviceItems: serviceData.serviceItems.map((item) => {
-        let ret: ServiceItem = {
-          id: uuid(),
-          type: item.type,
-          enabled: item.enabled ?? true,
-        };
-        if (item.song != undefined) {
-          let song: ServiceSong = {
-            title: item.song.title,
-            verses: item.song.verses.map((verse) => verse),
-          };
-          ret.song = song;
-        }
-        if (item.text != undefined) ret.text = item.text;
-        if (item.comment != undefined) ret.comment = item.comment;
-        return ret;
-      }),
+      serviceItems: serviceData.serviceItems.map(convertItemToInternal),
     };
     if (serviceData.title != undefined) ret.title = serviceData.title;
     if (serviceData.description != undefined)
       ret.description = serviceData.description;
+    if (serviceData.languages != undefined)
+      ret.languages = serviceData.languages.map((language) => language);
+    return ret;
+  }
+
+  function convertItemToInternal(item: ExportedServiceItem): ServiceItem {
+    let ret: ServiceItem = {
+      id: item.id ?? uuid(),
+      type: item.type,
+      enabled: item.enabled ?? true,
+    };
+    if (item.song != undefined) {
+      let song: ServiceSong = {
+        title: item.song.title,
+        verses: item.song.verses.map((verse) => verse),
+      };
+      if (item.song.arrangement != undefined)
+        song.arrangement = item.song.arrangement;
+      ret.song = song;
+    }
+    if (item.text != undefined) ret.text = item.text;
+    if (item.comment != undefined) ret.comment = item.comment;
     return ret;
   }
 
   return {
     unsavedChanges,
     serviceData,
+    serverService,
+    serverServiceChanged,
+    liveConnected,
+    liveCursor,
     selectedItemIndex,
     selectedSubItemId,
     selectedItem,
@@ -518,6 +1314,7 @@ export const useServiceStore = defineStore("service", () => {
     removeItem,
     moveItem,
     clearService,
+    songSubItems,
     selectAndShowItem,
     showEmptyScreen,
     showBlackScreen,
@@ -527,6 +1324,16 @@ export const useServiceStore = defineStore("service", () => {
     goToLastSubItem,
     showCurrentItem,
     importService,
+    importPlanningCenterPlan,
     exportService,
+    listServerServices,
+    openServerService,
+    saveServerService,
+    deleteServerService,
+    printService,
+    listTemplates,
+    saveAsTemplate,
+    deleteTemplate,
+    newServiceFromTemplate,
   };
 });
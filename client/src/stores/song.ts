@@ -2,6 +2,7 @@ import { defineStore } from "pinia";
 import { computed, ref } from "vue";
 
 import { ContentClient } from "@/api/content";
+import { SongsClient } from "@/api/songs";
 import { natcasecmp } from "@/helpers/sort";
 
 const SONG_FILE = "songs.json";
@@ -9,6 +10,36 @@ const SONG_FILE = "songs.json";
 export interface Song {
   verses: Record<string, string>,
   attribution?: string,
+  alternateTitles?: Array<string>,
+  authors?: Array<string>,
+  copyright?: string,
+  ccliNumber?: string,
+  key?: string,
+  /** Tempo in beats per minute */
+  tempo?: number,
+  themes?: Array<string>,
+  /** Default order to sing the verses in (verse names) */
+  verseOrder?: Array<string>,
+  /** Named arrangements (verse names to sing in order) */
+  arrangements?: Record<string, Array<string>>,
+  notes?: string,
+  language?: string,
+  translations?: Record<string, Record<string, string>>,
+  songbooks?: Array<SongbookEntry>,
+  chords?: Record<string, Array<ChordAnnotation>>,
+}
+
+/** Song book a song appears in */
+export interface SongbookEntry {
+  name: string,
+  entry?: string,
+}
+
+/** Chord shown above a position in a verse */
+export interface ChordAnnotation {
+  line: number,
+  position: number,
+  chord: string,
 }
 
 /** Song store */
@@ -48,25 +79,38 @@ export const useSongStore = defineStore("song", () => {
     songLoadingPromise = null;
   }
 
-  async function saveSongs() {
+  /** Saves a single song, renaming it if an existing song's title changed */
+  async function saveSong(
+    existingTitle: string | undefined,
+    title: string,
+    song: Song
+  ): Promise<boolean> {
     try {
-      const songsJson = JSON.stringify(
-        Object.fromEntries(
-          Object.entries(songs.value)
-            .sort((a, b) => natcasecmp([a[0], b[0]]))
-            .map(([title, song]) => [
-              title,
-              Object.fromEntries(
-                Object.entries(song).sort((a, b) => natcasecmp([a[0], b[0]]))
-              ),
-            ])
-        )
-      );
+      if (existingTitle != undefined) {
+        await SongsClient.updateSong(existingTitle, title, song);
+        delete songs.value[existingTitle];
+      } else {
+        await SongsClient.createSong(title, song);
+      }
+      songs.value[title] = song;
+      return true;
+    } catch (e) {
+      console.error(e);
+      alert(`An error occurred saving the song. (Is the server running?)\n${e}`);
+      return false;
+    }
+  }
 
-      await ContentClient.setContent(SONG_FILE, songsJson);
+  /** Removes a song */
+  async function deleteSong(title: string): Promise<boolean> {
+    try {
+      await SongsClient.deleteSong(title);
+      delete songs.value[title];
+      return true;
     } catch (e) {
       console.error(e);
-      alert("An error occurred saving songs. (Is the server running?)");
+      alert("An error occurred deleting the song. (Is the server running?)");
+      return false;
     }
   }
 
@@ -74,6 +118,7 @@ export const useSongStore = defineStore("song", () => {
     songs,
     songTitlesSorted,
     loadSongs,
-    saveSongs,
+    saveSong,
+    deleteSong,
   };
 });
@@ -17,11 +17,22 @@ export interface StateContent {
   background: boolean;
   song?: string;
   songTitle?: string;
+  /** Song library title of the song (songTitle can be changed per service item) */
+  songId?: string;
+  serviceTitle?: string;
+  /** Song verse in each language shown (the first is the primary language, also in song) */
+  songLanguages?: Array<SongLanguageText>;
   mainText?: string;
   subText?: string;
   smallText?: string;
 }
 
+/** Song verse text in a language */
+export interface SongLanguageText {
+  language: string;
+  text: string;
+}
+
 /** State store */
 export const useStateStore = defineStore("state", () => {
   // Taken from my Rust-Vue state system with authentication removed
@@ -5,14 +5,20 @@ use std::sync::Arc;
 
 use axum::Router;
 
-use crate::{app::AppServices, client_options, content, state};
+use crate::{
+    app::AppServices, client_options, config::file::AppConfig, content, reports, services, songs,
+    state,
+};
 
 /// API routes
-pub fn route() -> Router<Arc<AppServices>> {
+pub fn route(config: &AppConfig) -> Router<Arc<AppServices>> {
     Router::new()
         .nest("/server-info", server_info::route())
         .nest("/shutdown", shutdown::route())
-        .nest("/content", content::api::route())
+        .nest("/content", content::api::route(config))
+        .nest("/songs", songs::api::route(config))
+        .nest("/reports", reports::api::route())
+        .nest("/services", services::api::route())
         .nest("/state", state::api::route())
         .nest("/config", client_options::api::route())
 }
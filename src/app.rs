@@ -16,19 +16,40 @@ use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
 use tower_http::{
     catch_panic::CatchPanicLayer,
-    compression::CompressionLayer,
+    compression::{
+        predicate::{NotForContentType, Predicate},
+        CompressionLayer, DefaultPredicate,
+    },
     cors::CorsLayer,
     services::{ServeDir, ServeFile},
     set_header::SetResponseHeaderLayer,
 };
 
-use crate::{api, config::file::AppConfig, state::service::StateService};
+use crate::{
+    api,
+    config::file::AppConfig,
+    content::{api::DETECTED_ENCODING_HEADER, search, service::ContentService, trash},
+    database::Database,
+    reports::usage::{self, SongUsageService},
+    services::{
+        cursor::LiveCursorService, service::ServicePlanService, templates::ServiceTemplateService,
+    },
+    songs::service::SongService,
+    state::service::StateService,
+};
 
 /// Services that are passed to each endpoint as the state
 pub struct AppServices {
     pub config: AppConfig,
     pub shutdown_token: CancellationToken,
+    pub database: Database,
     pub state_service: StateService,
+    pub content_service: ContentService,
+    pub song_service: SongService,
+    pub song_usage_service: SongUsageService,
+    pub service_plan_service: ServicePlanService,
+    pub service_template_service: ServiceTemplateService,
+    pub live_cursor_service: LiveCursorService,
 }
 
 /// All the requirements to serve the app
@@ -44,13 +65,31 @@ impl App {
     pub async fn build(config: &AppConfig) -> Result<Self, &'static str> {
         let shutdown_token = CancellationToken::new();
 
+        let database = Database::new(config);
+
         // build state object
         let state = Arc::new(AppServices {
             config: config.clone(),
             shutdown_token: shutdown_token.clone(),
             state_service: StateService::new(),
+            content_service: ContentService::new(config, &database),
+            song_service: SongService::new(config),
+            song_usage_service: SongUsageService::new(database.clone()),
+            service_plan_service: ServicePlanService::new(database.clone()),
+            service_template_service: ServiceTemplateService::new(database.clone()),
+            live_cursor_service: LiveCursorService::new(),
+            database,
         });
 
+        // periodically clean up the content trash
+        tokio::spawn(trash::purge_task(state.clone()));
+
+        // keep the content search index up to date
+        tokio::spawn(search::index_task(state.clone()));
+
+        // log the songs shown on the displays for CCLI reporting
+        tokio::spawn(usage::log_task(state.clone()));
+
         // create TCP listener
         let host_address = SocketAddr::from((
             state
@@ -141,7 +180,7 @@ impl App {
             // API routes
             .nest(
                 "/api",
-                api::route().layer(SetResponseHeaderLayer::if_not_present(
+                api::route(&state.config).layer(SetResponseHeaderLayer::if_not_present(
                     header::CACHE_CONTROL,
                     HeaderValue::from_static(
                         "no-store, no-cache, max-age=0, must-revalidate, proxy-revalidate",
@@ -156,6 +195,7 @@ impl App {
                             .allow_origin(cors_origins)
                             .allow_credentials(true)
                             .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+                            .expose_headers([DETECTED_ENCODING_HEADER])
                             .allow_methods([
                                 Method::GET,
                                 Method::POST,
@@ -166,8 +206,14 @@ impl App {
                     )
                     // Handle panics by sending HTTP 500
                     .layer(CatchPanicLayer::new())
-                    // Compress responses using default settings
-                    .layer(CompressionLayer::new()),
+                    // Compress responses using default settings (except for media, which is already compressed and may be range requested)
+                    .layer(
+                        CompressionLayer::new().compress_when(
+                            DefaultPredicate::new()
+                                .and(NotForContentType::const_new("video/"))
+                                .and(NotForContentType::const_new("audio/")),
+                        ),
+                    ),
             )
             .with_state(state.clone());
 
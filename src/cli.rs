@@ -0,0 +1,241 @@
+use std::{fs, path::Path, process::ExitCode};
+
+use crate::{
+    config::file::AppConfig,
+    content::service::ContentService,
+    database::Database,
+    songs::{
+        chordpro,
+        import::{self, ConflictStrategy, ImportParser, ImportReport, ImportedSong},
+        openlp, openlyrics,
+        service::SongService,
+        songselect,
+    },
+};
+
+/// Command line usage
+const USAGE: &str = "Usage:
+    garnetdg_streamcontentsystemtest
+        Runs the server
+    garnetdg_streamcontentsystemtest import-openlyrics [--conflict=skip|replace|rename] [--dry-run] <file, zip or directory>...
+        Imports OpenLyrics XML songs into the song library
+    garnetdg_streamcontentsystemtest import-chordpro [--conflict=skip|replace|rename] [--dry-run] <file, zip or directory>...
+        Imports ChordPro songs into the song library
+    garnetdg_streamcontentsystemtest import-songselect [--conflict=skip|replace|rename] [--dry-run] <file, zip or directory>...
+        Imports CCLI SongSelect songs (.usr or plain text) into the song library
+    garnetdg_streamcontentsystemtest import-openlp [--conflict=skip|replace|rename] [--dry-run] <songs.sqlite>
+        Imports songs from an OpenLP song database into the song library
+    garnetdg_streamcontentsystemtest migrate-songs [--dry-run]
+        Moves metadata in older songs (e.g. authors in the attribution) into the current song fields";
+
+/// Command line subcommands
+const SUBCOMMANDS: &[&str] = &[
+    "import-openlyrics",
+    "import-chordpro",
+    "import-songselect",
+    "import-openlp",
+    "migrate-songs",
+];
+
+/// Checks if a command line argument is a subcommand (anything else runs the server)
+pub fn is_subcommand(arg: &str) -> bool {
+    SUBCOMMANDS.contains(&arg)
+}
+
+/// Runs a command line subcommand instead of the server
+pub fn run(config: &AppConfig, args: &[String]) -> ExitCode {
+    attach_console();
+
+    match args.first().map(String::as_str) {
+        Some("import-openlyrics") => import_openlyrics(config, &args[1..]),
+        Some("import-chordpro") => import_chordpro(config, &args[1..]),
+        Some("import-songselect") => import_songselect(config, &args[1..]),
+        Some("import-openlp") => import_openlp(config, &args[1..]),
+        Some("migrate-songs") => migrate_songs(config, &args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Attaches to the console of the command prompt the program was run from so that output can be seen.
+/// The program is built as a GUI program on Windows (so that the server doesn't open a console window),
+/// so it doesn't get a console of its own.
+/// Output is written after the command prompt has returned, since the command prompt doesn't wait for GUI programs.
+#[cfg(windows)]
+fn attach_console() {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+
+    // failing is fine (e.g. if the program wasn't run from a command prompt)
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+#[cfg(not(windows))]
+fn attach_console() {}
+
+/// Import options parsed from the command line
+struct ImportArgs<'a> {
+    strategy: ConflictStrategy,
+    dry_run: bool,
+    paths: Vec<&'a Path>,
+}
+
+/// Parses the options and paths of an import subcommand
+fn parse_import_args(args: &[String]) -> Option<ImportArgs<'_>> {
+    let mut import_args = ImportArgs {
+        strategy: ConflictStrategy::default(),
+        dry_run: false,
+        paths: Vec::new(),
+    };
+    for arg in args {
+        if let Some(value) = arg.strip_prefix("--conflict=") {
+            import_args.strategy = serde_json::from_value(serde_json::Value::from(value)).ok()?;
+        } else if arg == "--dry-run" {
+            import_args.dry_run = true;
+        } else {
+            import_args.paths.push(Path::new(arg));
+        }
+    }
+    if import_args.paths.is_empty() {
+        return None;
+    }
+    Some(import_args)
+}
+
+/// Imports OpenLyrics files into the song library and prints the import report
+fn import_openlyrics(config: &AppConfig, args: &[String]) -> ExitCode {
+    import_with(config, args, openlyrics::import)
+}
+
+/// Imports ChordPro files into the song library and prints the import report
+fn import_chordpro(config: &AppConfig, args: &[String]) -> ExitCode {
+    import_with(config, args, chordpro::import)
+}
+
+/// Imports CCLI SongSelect files into the song library and prints the import report
+fn import_songselect(config: &AppConfig, args: &[String]) -> ExitCode {
+    import_with(config, args, songselect::import)
+}
+
+/// Imports the songs from OpenLP song databases into the song library and prints the import report
+fn import_openlp(config: &AppConfig, args: &[String]) -> ExitCode {
+    import_with(config, args, openlp::import)
+}
+
+/// Reads the files to import with the parser of the import format
+/// and merges the songs into the song library
+fn import_with(config: &AppConfig, args: &[String], parser: ImportParser) -> ExitCode {
+    let Some(import_args) = parse_import_args(args) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::FAILURE;
+    };
+
+    let mut report = ImportReport::default();
+    let mut files = Vec::new();
+    for path in &import_args.paths {
+        read_import_path(path, config, &mut files, &mut report);
+    }
+    let songs = parser(files, &mut report);
+
+    import_songs(config, songs, &import_args, report)
+}
+
+/// Merges imported songs into the song library and prints the import report
+fn import_songs(
+    config: &AppConfig,
+    songs: Vec<ImportedSong>,
+    import_args: &ImportArgs,
+    report: ImportReport,
+) -> ExitCode {
+    let database = Database::new(config);
+    let content_service = ContentService::new(config, &database);
+    let song_service = SongService::new(config);
+
+    match song_service.import(
+        &content_service,
+        songs,
+        import_args.strategy,
+        report,
+        import_args.dry_run,
+        None,
+    ) {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to import songs: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Migrates older songs in the song library and prints the migration report
+fn migrate_songs(config: &AppConfig, args: &[String]) -> ExitCode {
+    let dry_run = match args {
+        [] => false,
+        [arg] if arg == "--dry-run" => true,
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let database = Database::new(config);
+    let content_service = ContentService::new(config, &database);
+    let song_service = SongService::new(config);
+
+    match song_service.migrate(&content_service, dry_run, None) {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to migrate songs: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads the files to import from a path, expanding directories and zip files
+fn read_import_path(
+    path: &Path,
+    config: &AppConfig,
+    files: &mut Vec<(String, Vec<u8>)>,
+    report: &mut ImportReport,
+) {
+    let source = path.to_string_lossy();
+
+    if path.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else {
+            report.error(&source, "Failed to read directory");
+            return;
+        };
+        let mut entries: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            read_import_path(&entry, config, files, report);
+        }
+        return;
+    }
+
+    match fs::read(path) {
+        Ok(bytes) => files.extend(import::extract_files(
+            &source,
+            bytes,
+            config.content_max_file_size,
+            report,
+        )),
+        Err(err) => report.error(&source, format!("Failed to read file: {}", err)),
+    }
+}
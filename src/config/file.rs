@@ -1,9 +1,11 @@
-use std::io::ErrorKind;
+use std::{collections::HashMap, io::ErrorKind};
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tokio::fs;
 
+use crate::songs::lint::SongLintConfig;
+
 /// App config read from file
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AppConfig {
@@ -27,6 +29,30 @@ pub struct AppConfig {
     #[serde(default = "default_content_directory")]
     pub content_directory: String,
 
+    /// Default maximum size of a content file in bytes
+    #[serde(default = "default_content_max_file_size")]
+    pub content_max_file_size: u64,
+
+    /// Maximum content file sizes in bytes by file extension
+    #[serde(default = "default_content_file_size_limits")]
+    pub content_file_size_limits: HashMap<String, u64>,
+
+    /// Whether to convert text content files to UTF-8 when they are written
+    #[serde(default = "default_content_normalize_encoding")]
+    pub content_normalize_encoding: bool,
+
+    /// Directory that deleted and overwritten content files are moved to
+    #[serde(default = "default_content_trash_directory")]
+    pub content_trash_directory: String,
+
+    /// Number of days to keep content files in the trash (0 to keep forever)
+    #[serde(default = "default_content_trash_retention_days")]
+    pub content_trash_retention_days: u64,
+
+    /// SQLite database file
+    #[serde(default = "default_database_file")]
+    pub database_file: String,
+
     /// Client static file root
     #[serde(default = "default_static_file_root")]
     pub static_file_root: String,
@@ -43,6 +69,10 @@ pub struct AppConfig {
     #[serde(default = "default_open_browser_on_start")]
     pub open_browser_on_start: bool,
 
+    /// Song library lint rules
+    #[serde(default = "default_song_lint")]
+    pub song_lint: SongLintConfig,
+
     /// Options passed to the client
     #[serde(default = "default_client_options")]
     pub client_options: Value,
@@ -87,6 +117,24 @@ fn default_client_proxy_url() -> Option<String> {
 fn default_content_directory() -> String {
     String::from("./content")
 }
+fn default_content_max_file_size() -> u64 {
+    256 * 1024 * 1024
+}
+fn default_content_file_size_limits() -> HashMap<String, u64> {
+    HashMap::new()
+}
+fn default_content_normalize_encoding() -> bool {
+    false
+}
+fn default_content_trash_directory() -> String {
+    String::from("./trash")
+}
+fn default_content_trash_retention_days() -> u64 {
+    30
+}
+fn default_database_file() -> String {
+    String::from("./database.sqlite3")
+}
 fn default_static_file_root() -> String {
     String::from("./client/dist/")
 }
@@ -99,6 +147,9 @@ fn default_http_caching_max_age() -> u64 {
 fn default_open_browser_on_start() -> bool {
     true
 }
+fn default_song_lint() -> SongLintConfig {
+    SongLintConfig::default()
+}
 fn default_client_options() -> Value {
     json!({})
 }
@@ -1,26 +1,76 @@
-use std::{collections::HashMap, fs, path, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::Write,
+    net::SocketAddr,
+    sync::Arc,
+};
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{ConnectInfo, DefaultBodyLimit, Multipart, Path, Query, Request, State},
+    http::{header, HeaderName, StatusCode},
     response::IntoResponse,
-    routing::{get, put},
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use serde::{Deserialize, Serialize};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use crate::{
+    app::AppServices,
+    config::file::AppConfig,
+    helpers::errors::GenericError,
+    songs::{service::SONGS_FILE, validation::validate_library_file},
+};
 
-use crate::app::AppServices;
+use super::{
+    encoding,
+    service::{is_text_file, sanitize_filename},
+};
+
+/// Header containing the encoding that a text content file was converted from
+pub const DETECTED_ENCODING_HEADER: HeaderName = HeaderName::from_static("x-detected-encoding");
 
 /// Content routes
-pub fn route() -> Router<Arc<AppServices>> {
+/// (content files are under `/files` so that their paths can't clash with the other routes)
+pub fn route(config: &AppConfig) -> Router<Arc<AppServices>> {
+    // the largest file that can be saved, so that bigger request bodies are rejected before they're buffered
+    let max_file_size = config
+        .content_file_size_limits
+        .values()
+        .copied()
+        .chain([config.content_max_file_size])
+        .max()
+        .unwrap_or(config.content_max_file_size);
+    let max_file_size = usize::try_from(max_file_size).unwrap_or(usize::MAX);
+
     Router::new()
         .route("/", get(list_content))
-        .route("/:filename", get(get_content))
-        .route("/:filename", put(set_content))
+        .route("/list", get(list_directory))
+        .route("/mkdir", post(create_directory))
+        .route("/rename", post(rename))
+        .route("/move", post(move_entry))
+        .route(
+            "/upload",
+            post(upload_content).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/search", get(search_content))
+        .route("/trash", get(list_trash))
+        .route("/trash/:id", delete(purge_trash_entry))
+        .route("/trash/:id/restore", post(restore_trash_entry))
+        .route("/files/*path", get(get_content))
+        .route(
+            "/files/*path",
+            put(set_content).layer(DefaultBodyLimit::max(max_file_size)),
+        )
+        .route("/files/*path", delete(delete_content))
 }
 
 /// Lists all the content files and their contents
 pub async fn list_content(State(state): State<Arc<AppServices>>) -> impl IntoResponse {
-    let Ok(dir) = fs::read_dir(state.config.content_directory.clone()) else {
+    let Ok(dir) = fs::read_dir(state.content_service.root.clone()) else {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     };
 
@@ -36,11 +86,11 @@ pub async fn list_content(State(state): State<Arc<AppServices>>) -> impl IntoRes
 
         let sanitized_filename = sanitize_filename(&filename);
         let path = file.path();
-        if !path.is_file() {
+        if !path.is_file() || !is_text_file(&path) {
             continue;
         }
 
-        let Ok(contents) = fs::read_to_string(path) else {
+        let Ok((contents, _)) = state.content_service.read_text(&path) else {
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         };
 
@@ -50,50 +100,362 @@ pub async fn list_content(State(state): State<Arc<AppServices>>) -> impl IntoRes
     Json(files).into_response()
 }
 
-/// Gets the value of a single content file
-pub async fn get_content(
+/// Query for endpoints that take a content path
+#[derive(Serialize, Deserialize)]
+pub struct PathQuery {
+    #[serde(default)]
+    pub path: String,
+}
+
+/// Lists the files and directories in a content directory
+pub async fn list_directory(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<PathQuery>,
+) -> impl IntoResponse {
+    match state.content_service.list_directory(&query.path) {
+        Ok(entries) => Json(entries).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Creates a content directory
+pub async fn create_directory(
+    State(state): State<Arc<AppServices>>,
+    Json(request): Json<PathQuery>,
+) -> impl IntoResponse {
+    match state.content_service.create_directory(&request.path) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Rename request
+#[derive(Serialize, Deserialize)]
+pub struct RenameRequest {
+    /// Path of the file or directory to rename
+    pub path: String,
+    /// New file or directory name
+    pub name: String,
+}
+
+/// Renames a content file or directory
+pub async fn rename(
+    State(state): State<Arc<AppServices>>,
+    Json(request): Json<RenameRequest>,
+) -> impl IntoResponse {
+    match state.content_service.rename(&request.path, &request.name) {
+        Ok(path) => Json(PathQuery { path }).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Move request
+#[derive(Serialize, Deserialize)]
+pub struct MoveRequest {
+    /// Path of the file or directory to move
+    pub path: String,
+    /// Directory to move into
+    pub destination: String,
+}
+
+/// Moves a content file or directory into another directory
+pub async fn move_entry(
     State(state): State<Arc<AppServices>>,
-    Path(filename): Path<String>,
+    Json(request): Json<MoveRequest>,
 ) -> impl IntoResponse {
-    let filename = sanitize_filename(&filename);
+    match state
+        .content_service
+        .move_entry(&request.path, &request.destination)
+    {
+        Ok(path) => Json(PathQuery { path }).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Upload query
+#[derive(Serialize, Deserialize)]
+pub struct UploadQuery {
+    /// Directory to upload into
+    #[serde(default)]
+    pub path: String,
+    /// Whether to replace existing files
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Uploads one or more files into a content directory from a multipart form.
+/// Returns the paths of the uploaded files.
+pub async fn upload_content(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(query): Query<UploadQuery>,
+    mut multipart: Multipart,
+) -> impl IntoResponse {
+    let mut uploaded = Vec::new();
+
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        };
+
+        let Some(filename) = field.file_name().map(String::from) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
 
-    let mut path = path::PathBuf::from(&state.config.content_directory.clone());
-    path.push(&filename);
+        let path =
+            match state
+                .content_service
+                .resolve_upload_path(&query.path, &filename, query.overwrite)
+            {
+                Ok(path) => path,
+                Err(err) => return err.to_status_code().into_response(),
+            };
+        let size_limit = state.content_service.file_size_limit(&path);
+
+        // write to a temporary file first so that a failed upload doesn't clobber an existing file
+        let temp_path = path.with_file_name(format!(".{}.upload", filename));
+        let result = async {
+            let mut file = File::create(&temp_path)?;
+            let mut size = 0;
+            while let Some(chunk) = field.chunk().await.map_err(|_| GenericError::BAD_REQUEST)? {
+                size += chunk.len() as u64;
+                if size > size_limit {
+                    return Err(GenericError::PAYLOAD_TOO_LARGE);
+                }
+                file.write_all(&chunk)?;
+            }
+            file.sync_all()?;
+            drop(file);
+
+            // convert text files to UTF-8 if enabled
+            if is_text_file(&path) {
+                let contents = fs::read(&temp_path)?;
+                if let Some(normalized) = state.content_service.normalize_text(&path, &contents) {
+                    fs::write(&temp_path, normalized)?;
+                }
+            }
+
+            state
+                .content_service
+                .trash_before_overwrite(&path, Some(remote_address.ip().to_string()))?;
+            fs::rename(&temp_path, &path)?;
+            Ok(())
+        }
+        .await;
+
+        if let Err(err) = result {
+            let _ = fs::remove_file(&temp_path);
+            return err.to_status_code().into_response();
+        }
+
+        uploaded.push(state.content_service.relative_path(&path));
+    }
+
+    Json(uploaded).into_response()
+}
+
+/// Gets a single content file.
+/// Text files are converted to UTF-8, with the detected encoding returned in the `X-Detected-Encoding` header.
+/// Other files support range requests and have the content type set based on the file extension.
+pub async fn get_content(
+    State(state): State<Arc<AppServices>>,
+    Path(path): Path<String>,
+    request: Request,
+) -> impl IntoResponse {
+    let path = match state.content_service.resolve_path(&path) {
+        Ok(path) => path,
+        Err(err) => return err.to_status_code().into_response(),
+    };
 
     if !path.is_file() {
         return StatusCode::NOT_FOUND.into_response();
     }
 
-    let Ok(contents) = fs::read_to_string(path) else {
-        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-    };
+    if is_text_file(&path) {
+        let Ok(bytes) = fs::read(&path) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        let decoded = encoding::decode(&bytes);
 
-    contents.into_response()
+        let mime = mime_guess::from_path(&path).first_or_text_plain();
+        return (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    format!("{}; charset=utf-8", mime.essence_str()),
+                ),
+                (
+                    DETECTED_ENCODING_HEADER,
+                    String::from(decoded.encoding.name()),
+                ),
+            ],
+            decoded.text.into_owned(),
+        )
+            .into_response();
+    }
+
+    match ServeFile::new(path).oneshot(request).await {
+        Ok(response) => response.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
 }
 
 /// Sets the value of a single content file
 pub async fn set_content(
     State(state): State<Arc<AppServices>>,
-    Path(filename): Path<String>,
-    text: String,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(path): Path<String>,
+    body: Bytes,
 ) -> impl IntoResponse {
-    let filename = sanitize_filename(&filename);
-
-    let mut path = path::PathBuf::from(&state.config.content_directory.clone());
-    path.push(&filename);
+    let path = match state.content_service.resolve_path(&path) {
+        Ok(path) => path,
+        Err(err) => return err.to_status_code().into_response(),
+    };
 
     if !path.is_file() {
         return StatusCode::NOT_FOUND.into_response();
     }
 
-    if fs::write(path, text).is_err() {
+    if body.len() as u64 > state.content_service.file_size_limit(&path) {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    }
+
+    let contents = match state.content_service.normalize_text(&path, &body) {
+        Some(normalized) => Bytes::from(normalized),
+        None => body,
+    };
+
+    // the song library is checked so that invalid songs aren't saved,
+    // and saved through the song service so that it doesn't overwrite concurrent song changes
+    if path
+        == state
+            .content_service
+            .resolve_path(SONGS_FILE)
+            .unwrap_or_default()
+    {
+        let library = match validate_library_file(&contents) {
+            Ok(library) => library,
+            Err(problems) => return (StatusCode::BAD_REQUEST, Json(problems)).into_response(),
+        };
+        return match state.song_service.replace_library(
+            &state.content_service,
+            library,
+            Some(remote_address.ip().to_string()),
+        ) {
+            Ok(issues) if issues.is_empty() => StatusCode::NO_CONTENT.into_response(),
+            Ok(issues) => (StatusCode::BAD_REQUEST, Json(issues)).into_response(),
+            Err(err) => err.to_status_code().into_response(),
+        };
+    }
+
+    if state
+        .content_service
+        .trash_before_overwrite(&path, Some(remote_address.ip().to_string()))
+        .is_err()
+    {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    if fs::write(path, contents).is_err() {
         return StatusCode::INTERNAL_SERVER_ERROR.into_response();
     }
 
     StatusCode::NO_CONTENT.into_response()
 }
 
-/// Sanitizes the filenames passed into the client endpoints.
-pub fn sanitize_filename(filename: &str) -> String {
-    sanitize_filename::sanitize(filename)
+/// Search query
+#[derive(Serialize, Deserialize)]
+pub struct SearchQuery {
+    /// Text to search for
+    pub q: String,
+    /// Maximum number of results
+    #[serde(default = "default_search_limit")]
+    pub limit: u32,
+}
+fn default_search_limit() -> u32 {
+    50
+}
+
+/// Searches the text in the content files
+pub async fn search_content(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<SearchQuery>,
+) -> impl IntoResponse {
+    let result = tokio::task::spawn_blocking(move || {
+        state.content_service.search.search(&query.q, query.limit)
+    })
+    .await;
+
+    match result {
+        Ok(Ok(results)) => Json(results).into_response(),
+        Ok(Err(err)) => err.to_status_code().into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Moves a content file or directory to the trash
+pub async fn delete_content(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(path): Path<String>,
+) -> impl IntoResponse {
+    match state
+        .content_service
+        .delete(&path, Some(remote_address.ip().to_string()))
+    {
+        Ok(entry) => Json(entry).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Lists the content files and directories in the trash
+pub async fn list_trash(State(state): State<Arc<AppServices>>) -> impl IntoResponse {
+    match state.content_service.trash.list() {
+        Ok(entries) => Json(entries).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Restore request
+#[derive(Serialize, Deserialize, Default)]
+pub struct RestoreRequest {
+    /// Path to restore to instead of the original path
+    pub path: Option<String>,
+}
+
+/// Restores a content file or directory from the trash.
+/// Returns the path it was restored to.
+pub async fn restore_trash_entry(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+    request: Option<Json<RestoreRequest>>,
+) -> impl IntoResponse {
+    let Json(request) = request.unwrap_or_default();
+
+    match state
+        .content_service
+        .restore_from_trash(&id, request.path.as_deref())
+    {
+        Ok(path) => Json(PathQuery { path }).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Permanently deletes a trash entry
+pub async fn purge_trash_entry(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let result = state
+        .content_service
+        .trash
+        .get(&id)
+        .and_then(|entry| state.content_service.trash.remove(&entry));
+
+    match result {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
 }
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+/// Text decoded from a content file
+pub struct DecodedText<'a> {
+    /// Text converted to UTF-8
+    pub text: Cow<'a, str>,
+    /// Encoding that was detected
+    pub encoding: &'static Encoding,
+    /// Whether the original bytes had a byte order mark
+    pub had_bom: bool,
+}
+
+impl DecodedText<'_> {
+    /// Whether the original bytes were already plain UTF-8 without a byte order mark
+    pub fn is_plain_utf8(&self) -> bool {
+        self.encoding == UTF_8 && !self.had_bom
+    }
+}
+
+/// Detects the encoding of text and converts it to UTF-8.
+///
+/// Byte order marks are checked first, then UTF-16 without a byte order mark
+/// (guessed from the position of null bytes), then UTF-8 validity,
+/// falling back to Windows-1252 (which older Windows song tools commonly use).
+pub fn decode(bytes: &[u8]) -> DecodedText<'_> {
+    let (encoding, bom_length) = match Encoding::for_bom(bytes) {
+        Some((encoding, bom_length)) => (encoding, bom_length),
+        None => (detect_without_bom(bytes), 0),
+    };
+
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+
+    DecodedText {
+        text,
+        encoding,
+        had_bom: bom_length > 0,
+    }
+}
+
+/// Guesses the encoding of text that doesn't have a byte order mark
+fn detect_without_bom(bytes: &[u8]) -> &'static Encoding {
+    // UTF-16 text that is mostly ASCII has a null byte in every code unit
+    if bytes.len() >= 2 && bytes.len().is_multiple_of(2) {
+        let code_units = bytes.len() / 2;
+        let even_nulls = bytes.iter().step_by(2).filter(|byte| **byte == 0).count();
+        let odd_nulls = bytes
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count();
+
+        if odd_nulls * 10 >= code_units * 3 && even_nulls * 10 < code_units {
+            return UTF_16LE;
+        }
+        if even_nulls * 10 >= code_units * 3 && odd_nulls * 10 < code_units {
+            return UTF_16BE;
+        }
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    WINDOWS_1252
+}
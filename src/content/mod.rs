@@ -1 +1,6 @@
 pub mod api;
+pub mod encoding;
+pub mod models;
+pub mod search;
+pub mod service;
+pub mod trash;
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Entry in a content directory listing
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DirectoryEntry {
+    /// File or directory name
+    pub name: String,
+    /// Path relative to the content directory
+    pub path: String,
+    /// Whether the entry is a directory
+    pub is_directory: bool,
+    /// File size in bytes (not set for directories)
+    pub size: Option<u64>,
+    /// Last modified time as a Unix timestamp
+    pub modified: Option<u64>,
+}
+
+/// Content file or directory that is in the trash
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Trash entry ID
+    pub id: Uuid,
+    /// Path relative to the content directory that the entry was removed from
+    pub original_path: String,
+    /// When the entry was put in the trash
+    pub deleted_at: DateTime<Utc>,
+    /// Remote address of the client that removed the entry
+    pub deleted_by: Option<String>,
+    /// Why the entry was put in the trash
+    pub reason: TrashReason,
+    /// Whether the entry is a directory
+    pub is_directory: bool,
+    /// File size in bytes (not set for directories)
+    pub size: Option<u64>,
+}
+
+/// Reason for a content file being put in the trash
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashReason {
+    /// File or directory was deleted
+    Deleted,
+    /// Previous version of a file that was overwritten
+    Overwritten,
+}
+
+/// Content search match
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    /// Path of the matching file relative to the content directory
+    pub file: String,
+    /// JSON path of the matching key or value (not set for files that aren't JSON)
+    pub json_path: Option<String>,
+    /// Snippet of the matching text (plain text, not HTML)
+    pub snippet: String,
+    /// Ranges of the snippet that matched the query
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Range of a search snippet that matched the query,
+/// in UTF-16 code units (the same as JavaScript string indices)
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub start: usize,
+    pub end: usize,
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use rusqlite::params;
+use serde_json::Value;
+use tokio::time::interval;
+
+use crate::{app::AppServices, database::Database, helpers::errors::GenericError};
+
+use super::{
+    encoding,
+    models::{SearchMatch, SearchResult},
+    service::is_text_file,
+};
+
+/// How often the search index is brought up to date with the content directory
+const INDEX_INTERVAL: Duration = Duration::from_secs(30);
+/// Marker inserted before a match in a snippet (private use character, removed before the snippet is returned)
+const HIGHLIGHT_START: char = '\u{E000}';
+/// Marker inserted after a match in a snippet (private use character, removed before the snippet is returned)
+const HIGHLIGHT_END: char = '\u{E001}';
+/// Approximate number of tokens in a snippet
+const SNIPPET_TOKENS: i64 = 16;
+
+/// Full-text search index over the content files
+pub struct ContentSearch {
+    /// Content directory root
+    root: PathBuf,
+    database: Database,
+}
+
+impl ContentSearch {
+    pub fn new(root: PathBuf, database: Database) -> Self {
+        Self { root, database }
+    }
+
+    /// Searches the content files.
+    /// The index is kept up to date by [`index_task`], so recent changes may not show up right away.
+    pub fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchResult>, GenericError> {
+        let Some(match_query) = build_match_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let conn = self.database.get()?;
+        let mut statement = conn
+            .prepare_cached(
+                "
+                SELECT path, json_path, snippet(content_search, 2, ?2, ?3, '…', ?4)
+                FROM content_search
+                WHERE content_search MATCH ?1
+                ORDER BY rank
+                LIMIT ?5
+                ",
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let results = statement
+            .query_map(
+                params![
+                    match_query,
+                    HIGHLIGHT_START.to_string(),
+                    HIGHLIGHT_END.to_string(),
+                    SNIPPET_TOKENS,
+                    limit
+                ],
+                |row| {
+                    let (snippet, matches) = split_highlights(&row.get::<_, String>(2)?);
+                    Ok(SearchResult {
+                        file: row.get(0)?,
+                        json_path: row.get(1)?,
+                        snippet,
+                        matches,
+                    })
+                },
+            )
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        Ok(results)
+    }
+
+    /// Re-indexes content files that have been added, changed or removed since they were last indexed
+    pub fn refresh(&self) -> Result<(), GenericError> {
+        let mut files = HashMap::new();
+        if self.root.is_dir() {
+            self.find_files(&self.root, &mut files)?;
+        }
+
+        let mut conn = self.database.get()?;
+        let transaction = conn
+            .transaction()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let indexed: HashMap<String, (i64, i64)> = transaction
+            .prepare("SELECT path, modified, size FROM content_search_files")
+            .and_then(|mut statement| {
+                statement
+                    .query_map([], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+                    .collect()
+            })
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let result: Result<(), rusqlite::Error> = (|| {
+            // remove files that no longer exist
+            for path in indexed.keys().filter(|path| !files.contains_key(*path)) {
+                transaction.execute("DELETE FROM content_search WHERE path = ?1", [path])?;
+                transaction.execute("DELETE FROM content_search_files WHERE path = ?1", [path])?;
+            }
+
+            // index new and changed files
+            for (path, (full_path, modified, size)) in &files {
+                if indexed.get(path) == Some(&(*modified, *size)) {
+                    continue;
+                }
+
+                transaction.execute("DELETE FROM content_search WHERE path = ?1", [path])?;
+
+                // files that can't be read are left out of the index so that they're retried next time
+                let Ok(bytes) = fs::read(full_path) else {
+                    transaction
+                        .execute("DELETE FROM content_search_files WHERE path = ?1", [path])?;
+                    continue;
+                };
+                let text = encoding::decode(&bytes).text;
+
+                let mut statement = transaction.prepare_cached(
+                    "INSERT INTO content_search (path, json_path, text) VALUES (?1, ?2, ?3)",
+                )?;
+                match serde_json::from_str::<Value>(&text) {
+                    Ok(value) => {
+                        let mut entries = Vec::new();
+                        collect_json_text(&value, String::from("$"), &mut entries);
+                        for (json_path, text) in entries {
+                            statement.execute(params![path, json_path, text])?;
+                        }
+                    }
+                    Err(_) => {
+                        statement.execute(params![path, Option::<String>::None, text])?;
+                    }
+                }
+
+                transaction.execute(
+                    "INSERT OR REPLACE INTO content_search_files (path, modified, size) VALUES (?1, ?2, ?3)",
+                    params![path, modified, size],
+                )?;
+            }
+
+            Ok(())
+        })();
+
+        result
+            .and_then(|()| transaction.commit())
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Recursively finds the text files in a directory along with their modification times and sizes
+    fn find_files(
+        &self,
+        directory: &Path,
+        files: &mut HashMap<String, (PathBuf, i64, i64)>,
+    ) -> Result<(), GenericError> {
+        for entry in fs::read_dir(directory)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            // skip hidden files (includes in-progress uploads)
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                self.find_files(&path, files)?;
+            } else if metadata.is_file() && is_text_file(&path) {
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map(|modified| modified.as_nanos() as i64)
+                    .unwrap_or_default();
+
+                let relative_path = path
+                    .strip_prefix(&self.root)
+                    .unwrap_or(&path)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+
+                files.insert(relative_path, (path, modified, metadata.len() as i64));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Periodically brings the search index up to date with the content directory until the server shuts down
+pub async fn index_task(services: Arc<AppServices>) {
+    let mut interval = interval(INDEX_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let services = services.clone();
+                let _ = tokio::task::spawn_blocking(move || services.content_service.search.refresh()).await;
+            }
+            _ = services.shutdown_token.cancelled() => return,
+        }
+    }
+}
+
+/// Removes the highlight markers from a snippet, returning the plain text and the ranges that were highlighted
+fn split_highlights(snippet: &str) -> (String, Vec<SearchMatch>) {
+    let mut text = String::with_capacity(snippet.len());
+    let mut matches = Vec::new();
+    let mut position = 0;
+    let mut start = None;
+    for character in snippet.chars() {
+        match character {
+            HIGHLIGHT_START => start = Some(position),
+            HIGHLIGHT_END => {
+                if let Some(start) = start.take() {
+                    matches.push(SearchMatch {
+                        start,
+                        end: position,
+                    });
+                }
+            }
+            _ => {
+                text.push(character);
+                position += character.len_utf16();
+            }
+        }
+    }
+
+    (text, matches)
+}
+
+/// Converts a user search query into an FTS5 match query.
+/// Each word must match, with the last word matched as a prefix so results show up while typing.
+fn build_match_query(query: &str) -> Option<String> {
+    let words: Vec<_> = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+
+    let (last, rest) = words.split_last()?;
+    let mut match_query = rest.join(" ");
+    if !match_query.is_empty() {
+        match_query.push(' ');
+    }
+    match_query.push_str(last);
+    match_query.push('*');
+
+    Some(match_query)
+}
+
+/// Collects the object keys and string values of a JSON document along with their JSON paths
+fn collect_json_text(value: &Value, path: String, entries: &mut Vec<(String, String)>) {
+    match value {
+        Value::String(text) => entries.push((path, text.clone())),
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_json_text(item, format!("{}[{}]", path, index), entries);
+            }
+        }
+        Value::Object(members) => {
+            for (key, member) in members {
+                let member_path = format!(
+                    "{}[{}]",
+                    path,
+                    serde_json::to_string(key).unwrap_or_default()
+                );
+                // keys are indexed as well since they are used for things like song titles
+                entries.push((member_path.clone(), key.clone()));
+                collect_json_text(member, member_path, entries);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,335 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use encoding_rs::Encoding;
+
+use crate::{config::file::AppConfig, database::Database, helpers::errors::GenericError};
+
+use super::{
+    encoding,
+    models::{DirectoryEntry, TrashEntry},
+    search::ContentSearch,
+    trash::ContentTrash,
+};
+
+/// Content service
+pub struct ContentService {
+    /// Content directory root
+    pub root: PathBuf,
+    /// Default maximum file size
+    max_file_size: u64,
+    /// Maximum file sizes by lowercase file extension
+    file_size_limits: HashMap<String, u64>,
+    /// Whether to convert text files to UTF-8 on write
+    normalize_encoding: bool,
+    /// Trash for deleted and overwritten files
+    pub trash: ContentTrash,
+    /// Full-text search index
+    pub search: ContentSearch,
+}
+
+impl ContentService {
+    pub fn new(config: &AppConfig, database: &Database) -> Self {
+        Self {
+            root: PathBuf::from(&config.content_directory),
+            max_file_size: config.content_max_file_size,
+            file_size_limits: config
+                .content_file_size_limits
+                .iter()
+                .map(|(extension, limit)| {
+                    (extension.trim_start_matches('.').to_lowercase(), *limit)
+                })
+                .collect(),
+            normalize_encoding: config.content_normalize_encoding,
+            trash: ContentTrash::new(config),
+            search: ContentSearch::new(PathBuf::from(&config.content_directory), database.clone()),
+        }
+    }
+
+    /// Resolves a path relative to the content directory.
+    /// Rejects any path that could escape the content directory.
+    pub fn resolve_path(&self, path: &str) -> Result<PathBuf, GenericError> {
+        let mut resolved = self.root.clone();
+        for component in split_path(path)? {
+            resolved.push(component);
+        }
+
+        // make sure that the deepest existing ancestor is inside of the content directory
+        // (catches symlinks that point outside of it)
+        let canonical_root = fs::canonicalize(&self.root)?;
+        let existing_ancestor = resolved
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .ok_or(GenericError::NOT_FOUND)?;
+        let canonical_ancestor = fs::canonicalize(existing_ancestor)?;
+        if !canonical_ancestor.starts_with(&canonical_root) {
+            return Err(GenericError::FORBIDDEN);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Converts an absolute path inside the content directory back to a relative content path
+    pub fn relative_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Lists the entries of a content directory
+    pub fn list_directory(&self, path: &str) -> Result<Vec<DirectoryEntry>, GenericError> {
+        let directory = self.resolve_path(path)?;
+        if !directory.is_dir() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&directory)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let metadata = entry.metadata()?;
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_secs());
+
+            entries.push(DirectoryEntry {
+                path: self.relative_path(&entry.path()),
+                name,
+                is_directory: metadata.is_dir(),
+                size: if metadata.is_file() {
+                    Some(metadata.len())
+                } else {
+                    None
+                },
+                modified,
+            });
+        }
+
+        // directories first, then by name
+        entries.sort_by(|a, b| {
+            b.is_directory
+                .cmp(&a.is_directory)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+
+        Ok(entries)
+    }
+
+    /// Creates a directory (and any missing parents)
+    pub fn create_directory(&self, path: &str) -> Result<(), GenericError> {
+        let directory = self.resolve_path(path)?;
+        if directory.is_file() {
+            return Err(GenericError::CONFLICT);
+        }
+
+        fs::create_dir_all(directory)?;
+
+        Ok(())
+    }
+
+    /// Renames a file or directory, keeping it in the same directory
+    pub fn rename(&self, path: &str, new_name: &str) -> Result<String, GenericError> {
+        let source = self.resolve_existing_entry(path)?;
+
+        let [new_name] = split_path(new_name)?[..] else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+        let destination = source
+            .parent()
+            .ok_or(GenericError::BAD_REQUEST)?
+            .join(new_name);
+
+        self.move_path(&source, &destination)
+    }
+
+    /// Moves a file or directory into another directory
+    pub fn move_entry(&self, path: &str, destination: &str) -> Result<String, GenericError> {
+        let source = self.resolve_existing_entry(path)?;
+
+        let destination_directory = self.resolve_path(destination)?;
+        if !destination_directory.is_dir() {
+            return Err(GenericError::NOT_FOUND);
+        }
+        if destination_directory.starts_with(&source) {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        let destination = destination_directory.join(source.file_name().unwrap());
+
+        self.move_path(&source, &destination)
+    }
+
+    /// Moves a file or directory to the trash
+    pub fn delete(
+        &self,
+        path: &str,
+        deleted_by: Option<String>,
+    ) -> Result<TrashEntry, GenericError> {
+        let resolved = self.resolve_existing_entry(path)?;
+
+        self.trash
+            .move_to_trash(&resolved, self.relative_path(&resolved), deleted_by)
+    }
+
+    /// Copies the current version of a file to the trash if it is about to be overwritten
+    pub fn trash_before_overwrite(
+        &self,
+        path: &Path,
+        deleted_by: Option<String>,
+    ) -> Result<(), GenericError> {
+        if path.is_file() {
+            self.trash
+                .copy_to_trash(path, self.relative_path(path), deleted_by)?;
+        }
+
+        Ok(())
+    }
+
+    /// Restores a file or directory from the trash to its original path (or another path if specified).
+    /// Returns the restored path.
+    pub fn restore_from_trash(&self, id: &str, path: Option<&str>) -> Result<String, GenericError> {
+        let entry = self.trash.get(id)?;
+
+        let destination = self.resolve_path(path.unwrap_or(&entry.original_path))?;
+        if destination == self.root {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        self.trash.restore(&entry, &destination)?;
+
+        Ok(self.relative_path(&destination))
+    }
+
+    /// Resolves the destination of an uploaded file inside of a content directory
+    pub fn resolve_upload_path(
+        &self,
+        directory: &str,
+        filename: &str,
+        overwrite: bool,
+    ) -> Result<PathBuf, GenericError> {
+        let directory = self.resolve_path(directory)?;
+        if !directory.is_dir() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        let [filename] = split_path(filename)?[..] else {
+            return Err(GenericError::BAD_REQUEST);
+        };
+        let path = directory.join(filename);
+
+        if path.is_dir() || (path.exists() && !overwrite) {
+            return Err(GenericError::CONFLICT);
+        }
+
+        Ok(path)
+    }
+
+    /// Gets the maximum allowed size of a content file based on its extension
+    pub fn file_size_limit(&self, path: &Path) -> u64 {
+        path.extension()
+            .and_then(|extension| {
+                self.file_size_limits
+                    .get(&extension.to_string_lossy().to_lowercase())
+            })
+            .copied()
+            .unwrap_or(self.max_file_size)
+    }
+
+    /// Reads a text file, converting it to UTF-8 from whatever encoding it was detected as
+    pub fn read_text(&self, path: &Path) -> Result<(String, &'static Encoding), GenericError> {
+        let bytes = fs::read(path)?;
+        let decoded = encoding::decode(&bytes);
+
+        Ok((decoded.text.into_owned(), decoded.encoding))
+    }
+
+    /// Converts the contents of a text file to UTF-8 before it is written if encoding normalization is enabled.
+    /// Returns `None` if the contents should be written unchanged.
+    pub fn normalize_text(&self, path: &Path, bytes: &[u8]) -> Option<Vec<u8>> {
+        if !self.normalize_encoding || !is_text_file(path) {
+            return None;
+        }
+
+        let decoded = encoding::decode(bytes);
+        if decoded.is_plain_utf8() {
+            return None;
+        }
+
+        Some(decoded.text.into_owned().into_bytes())
+    }
+
+    /// Resolves a path to an existing file or directory that is not the content root
+    fn resolve_existing_entry(&self, path: &str) -> Result<PathBuf, GenericError> {
+        if split_path(path)?.is_empty() {
+            return Err(GenericError::BAD_REQUEST);
+        }
+
+        let resolved = self.resolve_path(path)?;
+        if !resolved.exists() {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Moves a path without overwriting an existing entry, returning the new relative path
+    fn move_path(&self, source: &Path, destination: &Path) -> Result<String, GenericError> {
+        if destination.exists() {
+            return Err(GenericError::CONFLICT);
+        }
+
+        fs::rename(source, destination)?;
+
+        Ok(self.relative_path(destination))
+    }
+}
+
+/// Whether a content file should be treated as text based on its extension.
+/// Files without an extension are assumed to be text.
+pub fn is_text_file(path: &Path) -> bool {
+    if path.extension().is_none() {
+        return true;
+    }
+
+    mime_guess::from_path(path).iter().any(|mime| {
+        mime.type_() == mime_guess::mime::TEXT
+            || mime.subtype() == mime_guess::mime::JSON
+            || mime.subtype() == mime_guess::mime::XML
+            || mime.suffix() == Some(mime_guess::mime::JSON)
+            || mime.suffix() == Some(mime_guess::mime::XML)
+    })
+}
+
+/// Splits a relative content path into its components, validating each one.
+/// Components that would be changed by sanitization (including "." and "..") are rejected.
+pub fn split_path(path: &str) -> Result<Vec<&str>, GenericError> {
+    path.split('/')
+        .filter(|component| !component.is_empty())
+        .map(|component| {
+            if sanitize_filename(component) == component && component != "." && component != ".." {
+                Ok(component)
+            } else {
+                Err(GenericError::BAD_REQUEST)
+            }
+        })
+        .collect()
+}
+
+/// Sanitizes a single path component passed into the content endpoints.
+pub fn sanitize_filename(filename: &str) -> String {
+    sanitize_filename::sanitize(filename)
+}
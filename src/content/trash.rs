@@ -0,0 +1,233 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use tokio::time::interval;
+use uuid::Uuid;
+
+use crate::{app::AppServices, config::file::AppConfig, helpers::errors::GenericError};
+
+use super::models::{TrashEntry, TrashReason};
+
+/// How often to check for expired trash entries
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Holds deleted and overwritten content files until they are restored or expire
+pub struct ContentTrash {
+    /// Trash directory
+    root: PathBuf,
+    /// Number of days to keep trashed files (0 keeps them forever)
+    retention_days: u64,
+}
+
+impl ContentTrash {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            root: PathBuf::from(&config.content_trash_directory),
+            retention_days: config.content_trash_retention_days,
+        }
+    }
+
+    /// Moves a file or directory into the trash
+    pub fn move_to_trash(
+        &self,
+        path: &Path,
+        original_path: String,
+        deleted_by: Option<String>,
+    ) -> Result<TrashEntry, GenericError> {
+        let (entry, data_path) =
+            self.new_entry(path, original_path, deleted_by, TrashReason::Deleted)?;
+
+        // renaming fails if the trash is on a different filesystem, so fall back to copying
+        if fs::rename(path, &data_path).is_err() {
+            copy_recursive(path, &data_path)?;
+            if path.is_dir() {
+                fs::remove_dir_all(path)?;
+            } else {
+                fs::remove_file(path)?;
+            }
+        }
+
+        self.write_entry(&entry)?;
+
+        Ok(entry)
+    }
+
+    /// Copies a file into the trash before it gets overwritten
+    pub fn copy_to_trash(
+        &self,
+        path: &Path,
+        original_path: String,
+        deleted_by: Option<String>,
+    ) -> Result<TrashEntry, GenericError> {
+        let (entry, data_path) =
+            self.new_entry(path, original_path, deleted_by, TrashReason::Overwritten)?;
+
+        fs::copy(path, data_path)?;
+        self.write_entry(&entry)?;
+
+        Ok(entry)
+    }
+
+    /// Lists the trash entries, newest first
+    pub fn list(&self) -> Result<Vec<TrashEntry>, GenericError> {
+        if !self.root.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut entries = Vec::new();
+        for file in fs::read_dir(&self.root)? {
+            let path = file?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                if let Ok(entry) = self.read_entry_file(&path) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+
+        Ok(entries)
+    }
+
+    /// Gets a single trash entry
+    pub fn get(&self, id: &str) -> Result<TrashEntry, GenericError> {
+        let id = Uuid::parse_str(id).map_err(|_| GenericError::NOT_FOUND)?;
+        self.read_entry_file(&self.metadata_path(&id))
+    }
+
+    /// Moves a trashed file back to a path in the content directory.
+    /// The trash entry is removed once it has been restored.
+    pub fn restore(&self, entry: &TrashEntry, destination: &Path) -> Result<(), GenericError> {
+        if destination.exists() {
+            return Err(GenericError::CONFLICT);
+        }
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let data_path = self.data_path(&entry.id);
+        if fs::rename(&data_path, destination).is_err() {
+            copy_recursive(&data_path, destination)?;
+        }
+
+        self.remove(entry)
+    }
+
+    /// Permanently removes a trash entry
+    pub fn remove(&self, entry: &TrashEntry) -> Result<(), GenericError> {
+        let data_path = self.data_path(&entry.id);
+        if data_path.is_dir() {
+            fs::remove_dir_all(data_path)?;
+        } else if data_path.exists() {
+            fs::remove_file(data_path)?;
+        }
+
+        fs::remove_file(self.metadata_path(&entry.id))?;
+
+        Ok(())
+    }
+
+    /// Permanently removes trash entries older than the retention period
+    /// (entries that can't be removed are skipped and retried on the next purge)
+    pub fn purge_expired(&self) -> Result<(), GenericError> {
+        if self.retention_days == 0 {
+            return Ok(());
+        }
+
+        let cutoff = Utc::now() - chrono::Duration::days(self.retention_days as i64);
+        for entry in self.list()? {
+            if entry.deleted_at < cutoff {
+                if let Err(err) = self.remove(&entry) {
+                    eprintln!("Failed to purge trash entry {}: {}", entry.id, err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates the metadata for a new trash entry and returns where its data should be stored
+    fn new_entry(
+        &self,
+        path: &Path,
+        original_path: String,
+        deleted_by: Option<String>,
+        reason: TrashReason,
+    ) -> Result<(TrashEntry, PathBuf), GenericError> {
+        fs::create_dir_all(&self.root)?;
+
+        let metadata = fs::metadata(path)?;
+        let entry = TrashEntry {
+            id: Uuid::new_v4(),
+            original_path,
+            deleted_at: Utc::now(),
+            deleted_by,
+            reason,
+            is_directory: metadata.is_dir(),
+            size: if metadata.is_file() {
+                Some(metadata.len())
+            } else {
+                None
+            },
+        };
+        let data_path = self.data_path(&entry.id);
+
+        Ok((entry, data_path))
+    }
+
+    fn write_entry(&self, entry: &TrashEntry) -> Result<(), GenericError> {
+        let json =
+            serde_json::to_string_pretty(entry).map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        fs::write(self.metadata_path(&entry.id), json)?;
+        Ok(())
+    }
+
+    fn read_entry_file(&self, path: &Path) -> Result<TrashEntry, GenericError> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    fn data_path(&self, id: &Uuid) -> PathBuf {
+        self.root.join(id.to_string())
+    }
+
+    fn metadata_path(&self, id: &Uuid) -> PathBuf {
+        self.root.join(format!("{}.json", id))
+    }
+}
+
+/// Copies a file or directory tree
+fn copy_recursive(source: &Path, destination: &Path) -> Result<(), GenericError> {
+    if source.is_dir() {
+        fs::create_dir_all(destination)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, destination)?;
+    }
+
+    Ok(())
+}
+
+/// Periodically purges expired trash entries until the server shuts down
+pub async fn purge_task(services: Arc<AppServices>) {
+    let mut interval = interval(PURGE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let _ = services.content_service.trash.purge_expired();
+            }
+            _ = services.shutdown_token.cancelled() => return,
+        }
+    }
+}
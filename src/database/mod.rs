@@ -0,0 +1,136 @@
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::Connection;
+
+use crate::{config::file::AppConfig, helpers::errors::GenericError};
+
+/// Database schema migrations, applied in order.
+/// The index of the last applied migration is stored in the `user_version` pragma.
+const MIGRATIONS: &[&str] = &[
+    // content search index
+    "
+    CREATE TABLE content_search_files (
+        path TEXT PRIMARY KEY NOT NULL,
+        modified INTEGER NOT NULL,
+        size INTEGER NOT NULL
+    );
+    CREATE VIRTUAL TABLE content_search USING fts5(
+        path UNINDEXED,
+        json_path UNINDEXED,
+        text,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    ",
+    // song usage log
+    "
+    CREATE TABLE song_usage (
+        id INTEGER PRIMARY KEY,
+        title TEXT NOT NULL,
+        ccli_number TEXT,
+        authors TEXT,
+        copyright TEXT,
+        service TEXT,
+        service_date TEXT NOT NULL,
+        shown_at TEXT NOT NULL
+    );
+    CREATE INDEX song_usage_service_date ON song_usage (service_date);
+    ",
+    // services (orders of worship)
+    "
+    CREATE TABLE services (
+        id TEXT PRIMARY KEY NOT NULL,
+        title TEXT,
+        service_date TEXT,
+        data TEXT NOT NULL,
+        revision INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        modified_by TEXT
+    );
+    CREATE INDEX services_service_date ON services (service_date);
+    ",
+    // service templates
+    "
+    CREATE TABLE service_templates (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        data TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    ",
+];
+
+/// Database connection pool
+#[derive(Clone)]
+pub struct Database {
+    pool: Pool<SqliteConnectionManager>,
+}
+
+impl Database {
+    /// Opens the database and applies any pending migrations
+    pub fn new(config: &AppConfig) -> Self {
+        let manager = SqliteConnectionManager::file(&config.database_file).with_init(|conn| {
+            conn.execute_batch(
+                "
+                PRAGMA journal_mode = WAL;
+                PRAGMA synchronous = NORMAL;
+                PRAGMA foreign_keys = ON;
+                PRAGMA busy_timeout = 5000;
+                ",
+            )
+        });
+        let pool = Pool::new(manager).expect("Failed to open database");
+
+        let database = Self { pool };
+        database
+            .migrate()
+            .expect("Failed to apply database migrations");
+
+        database
+    }
+
+    /// Opens an empty in-memory database with all migrations applied (used in tests)
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        // every connection to an in-memory database is a separate database
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory())
+            .expect("Failed to open database");
+
+        let database = Self { pool };
+        database
+            .migrate()
+            .expect("Failed to apply database migrations");
+
+        database
+    }
+
+    /// Gets a connection from the pool
+    pub fn get(&self) -> Result<PooledConnection<SqliteConnectionManager>, GenericError> {
+        self.pool
+            .get()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Applies migrations that haven't been applied yet
+    fn migrate(&self) -> Result<(), rusqlite::Error> {
+        let mut conn = self.pool.get().expect("Failed to get database connection");
+        let version = current_version(&conn)?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = conn.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", index + 1)?;
+            transaction.commit()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets the number of applied migrations
+fn current_version(conn: &Connection) -> Result<usize, rusqlite::Error> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
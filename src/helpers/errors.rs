@@ -1,4 +1,4 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, io};
 
 use axum::http::StatusCode;
 
@@ -12,6 +12,8 @@ impl GenericError {
     pub const FORBIDDEN: GenericError = GenericError(403);
     pub const NOT_FOUND: GenericError = GenericError(404);
     pub const CONFLICT: GenericError = GenericError(409);
+    pub const PRECONDITION_FAILED: GenericError = GenericError(412);
+    pub const PAYLOAD_TOO_LARGE: GenericError = GenericError(413);
     pub const INTERNAL_SERVER_ERROR: GenericError = GenericError(500);
 
     pub fn to_status_code(&self) -> StatusCode {
@@ -31,9 +33,22 @@ impl Display for GenericError {
                 403 => "Forbidden",
                 404 => "Not Found",
                 409 => "Conflict",
+                412 => "Precondition Failed",
+                413 => "Payload Too Large",
                 500 => "Internal Server Error",
                 _ => "Unknown Error",
             }
         )
     }
 }
+
+impl From<io::Error> for GenericError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => Self::NOT_FOUND,
+            io::ErrorKind::AlreadyExists => Self::CONFLICT,
+            io::ErrorKind::PermissionDenied => Self::FORBIDDEN,
+            _ => Self::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::cmp::Ordering;
+
+/// Natural case-insensitive string comparison (matches the sorting used by the client).
+/// Runs of digits are compared numerically, everything else is compared case-insensitively,
+/// with case only used to break ties.
+pub fn natcasecmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+
+    loop {
+        match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_chunk), Some(b_chunk)) => {
+                let a_number = a_chunk.parse::<u128>();
+                let b_number = b_chunk.parse::<u128>();
+                let ordering = match (a_number, b_number) {
+                    (Ok(a_number), Ok(b_number)) => a_number.cmp(&b_number),
+                    _ => a_chunk.to_lowercase().cmp(&b_chunk.to_lowercase()),
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Splits a string into runs of digits and non-digits
+fn chunks(value: &str) -> impl Iterator<Item = &str> {
+    let mut rest = value;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let is_digit = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (chunk, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(chunk)
+    })
+}
@@ -2,13 +2,18 @@
 
 pub mod api;
 pub mod app;
+pub mod cli;
 pub mod client_options;
 pub mod config;
 pub mod content;
+pub mod database;
 pub mod helpers;
+pub mod reports;
+pub mod services;
+pub mod songs;
 pub mod state;
 
-use std::time::Duration;
+use std::{net::SocketAddr, process::ExitCode, time::Duration};
 
 use app::App;
 use config::file::AppConfig;
@@ -18,11 +23,17 @@ use tokio_util::sync::CancellationToken;
 const CONFIG_FILE: &str = "./config.json";
 
 #[tokio::main]
-pub async fn main() {
+pub async fn main() -> ExitCode {
     // load config
     //TODO: allow reloading config without restarting server
     let config = AppConfig::load(CONFIG_FILE).await;
 
+    // run a command line subcommand instead of the server if one was given
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| cli::is_subcommand(arg)) {
+        return cli::run(&config, &args);
+    }
+
     let app = App::build(&config).await;
 
     let browser_port = config.port;
@@ -41,16 +52,22 @@ pub async fn main() {
         });
 
         // serve app
-        axum::serve(app.listener, app.router)
-            .with_graceful_shutdown(shutdown_signal(app.shutdown_token))
-            .await
-            .expect("Error occurred in web server task");
+        axum::serve(
+            app.listener,
+            app.router
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown_signal(app.shutdown_token))
+        .await
+        .expect("Error occurred in web server task");
 
         let _ = browser_task.await;
     } else if config.open_browser_on_start {
         // else open the browser (assumes that the only failure was that the port was in use)
         let _ = open_browser(browser_port);
     }
+
+    ExitCode::SUCCESS
 }
 
 /// Opens the web browser on the local system
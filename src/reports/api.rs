@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::app::AppServices;
+
+use super::models::SongUsageReport;
+
+/// Report routes
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new().route("/song-usage", get(song_usage))
+}
+
+/// Report format
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+/// Song usage report query
+#[derive(Serialize, Deserialize)]
+pub struct SongUsageQuery {
+    /// First day of the reporting period (inclusive)
+    pub from: Option<NaiveDate>,
+    /// Last day of the reporting period (inclusive)
+    pub to: Option<NaiveDate>,
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+/// Gets the songs shown between two dates, with how many services each song was used in
+pub async fn song_usage(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<SongUsageQuery>,
+) -> impl IntoResponse {
+    let report = match state.song_usage_service.report(query.from, query.to) {
+        Ok(report) => report,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    match query.format {
+        ReportFormat::Json => Json(report).into_response(),
+        ReportFormat::Csv => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"song-usage.csv\"",
+                ),
+            ],
+            to_csv(&report),
+        )
+            .into_response(),
+    }
+}
+
+/// Writes a song usage report as CSV, with the columns used when reporting to CCLI
+fn to_csv(report: &SongUsageReport) -> String {
+    let mut csv = String::from(
+        "Title,CCLI Song Number,Authors,Copyright,Times Used,First Used,Last Used\r\n",
+    );
+    for song in &report.songs {
+        let fields = [
+            song.title.clone(),
+            song.ccli_number.clone().unwrap_or_default(),
+            song.authors.join(" | "),
+            song.copyright.clone().unwrap_or_default(),
+            song.times_used.to_string(),
+            song.first_used.to_string(),
+            song.last_used.to_string(),
+        ];
+        let fields: Vec<_> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Quotes a CSV field if needed
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        String::from(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reports::models::SongUsageSummary;
+
+    #[test]
+    fn writes_csv_reports() {
+        let date = |day| NaiveDate::from_ymd_opt(2026, 10, day).unwrap();
+        let report = SongUsageReport {
+            from: None,
+            to: None,
+            songs: vec![
+                SongUsageSummary {
+                    title: String::from("Amazing Grace"),
+                    ccli_number: Some(String::from("22025")),
+                    authors: vec![String::from("John Newton"), String::from("Edwin Excell")],
+                    copyright: Some(String::from("Public Domain")),
+                    times_used: 3,
+                    first_used: date(4),
+                    last_used: date(11),
+                },
+                SongUsageSummary {
+                    title: String::from("Holy, Holy, \"Holy\""),
+                    ccli_number: None,
+                    authors: Vec::new(),
+                    copyright: None,
+                    times_used: 1,
+                    first_used: date(18),
+                    last_used: date(18),
+                },
+            ],
+        };
+
+        assert_eq!(
+            to_csv(&report),
+            "Title,CCLI Song Number,Authors,Copyright,Times Used,First Used,Last Used\r\n\
+             Amazing Grace,22025,John Newton | Edwin Excell,Public Domain,3,2026-10-04,2026-10-11\r\n\
+             \"Holy, Holy, \"\"Holy\"\"\",,,,1,2026-10-18,2026-10-18\r\n"
+        );
+    }
+}
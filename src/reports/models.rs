@@ -0,0 +1,49 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Song shown on the displays
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SongUsageEvent {
+    /// Song title
+    pub title: String,
+    /// CCLI song number (if the song is in the song library)
+    pub ccli_number: Option<String>,
+    /// Song authors
+    pub authors: Vec<String>,
+    /// Song copyright notice
+    pub copyright: Option<String>,
+    /// Title of the service the song was shown in
+    pub service: Option<String>,
+    /// When the song was shown
+    pub shown_at: DateTime<Utc>,
+}
+
+/// Song usage over a reporting period, in the layout used for CCLI reporting
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SongUsageReport {
+    /// First day of the reporting period
+    pub from: Option<NaiveDate>,
+    /// Last day of the reporting period
+    pub to: Option<NaiveDate>,
+    /// Songs used in the period, most used first
+    pub songs: Vec<SongUsageSummary>,
+}
+
+/// How often a song was used in a reporting period
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SongUsageSummary {
+    /// Song title
+    pub title: String,
+    /// CCLI song number
+    pub ccli_number: Option<String>,
+    /// Song authors
+    pub authors: Vec<String>,
+    /// Song copyright notice
+    pub copyright: Option<String>,
+    /// Number of services the song was shown in (each song is counted once per service)
+    pub times_used: u32,
+    /// Day the song was first used in the period
+    pub first_used: NaiveDate,
+    /// Day the song was last used in the period
+    pub last_used: NaiveDate,
+}
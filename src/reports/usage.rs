@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use rusqlite::params;
+use serde_json::Value;
+
+use crate::{app::AppServices, database::Database, helpers::errors::GenericError};
+
+use super::models::{SongUsageEvent, SongUsageReport, SongUsageSummary};
+
+/// Separator used to store song authors in a single column
+const AUTHOR_SEPARATOR: &str = "\n";
+
+/// Song usage log, used for CCLI reporting
+pub struct SongUsageService {
+    database: Database,
+}
+
+impl SongUsageService {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    /// Records a song being shown.
+    /// The service date is the local date the song was shown on.
+    pub fn log(&self, event: &SongUsageEvent) -> Result<(), GenericError> {
+        let conn = self.database.get()?;
+        conn.execute(
+            "
+            INSERT INTO song_usage (title, ccli_number, authors, copyright, service, service_date, shown_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ",
+            params![
+                event.title,
+                event.ccli_number,
+                event.authors.join(AUTHOR_SEPARATOR),
+                event.copyright,
+                event.service,
+                event.shown_at.with_timezone(&Local).date_naive(),
+                event.shown_at,
+            ],
+        )
+        .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        Ok(())
+    }
+
+    /// Summarizes song usage between two dates (inclusive).
+    /// A song is counted once per service (each service title on each day),
+    /// no matter how many times it was shown in that service.
+    pub fn report(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<SongUsageReport, GenericError> {
+        let conn = self.database.get()?;
+        let mut statement = conn
+            .prepare_cached(
+                "
+                SELECT
+                    title,
+                    ccli_number,
+                    MAX(authors),
+                    MAX(copyright),
+                    COUNT(DISTINCT service_date || char(0) || IFNULL(service, '')),
+                    MIN(service_date),
+                    MAX(service_date)
+                FROM song_usage
+                WHERE (?1 IS NULL OR service_date >= ?1) AND (?2 IS NULL OR service_date <= ?2)
+                GROUP BY title, IFNULL(ccli_number, '')
+                ORDER BY COUNT(DISTINCT service_date || char(0) || IFNULL(service, '')) DESC, title
+                ",
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let songs = statement
+            .query_map(params![from, to], |row| {
+                Ok(SongUsageSummary {
+                    title: row.get(0)?,
+                    ccli_number: row.get(1)?,
+                    authors: row
+                        .get::<_, Option<String>>(2)?
+                        .unwrap_or_default()
+                        .split(AUTHOR_SEPARATOR)
+                        .filter(|author| !author.is_empty())
+                        .map(String::from)
+                        .collect(),
+                    copyright: row.get(3)?,
+                    times_used: row.get(4)?,
+                    first_used: row.get(5)?,
+                    last_used: row.get(6)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        Ok(SongUsageReport { from, to, songs })
+    }
+}
+
+/// Gets the song library title of the song in the state, if a song is being shown
+fn shown_song(content: &Value) -> Option<String> {
+    content.get("song").and_then(Value::as_str)?;
+
+    // the song ID is the song library title, the song title may be changed for the service
+    ["songId", "songTitle"]
+        .iter()
+        .filter_map(|key| content.get(key).and_then(Value::as_str))
+        .map(str::trim)
+        .find(|title| !title.is_empty())
+        .map(String::from)
+}
+
+/// Song shown on the displays, with the service and day it was shown in
+#[derive(PartialEq)]
+struct SongUse {
+    title: String,
+    service: Option<String>,
+    date: NaiveDate,
+}
+
+/// Gets the use of the song in the state, if a song is being shown
+fn song_use(content: &Value, shown_at: DateTime<Utc>) -> Option<SongUse> {
+    Some(SongUse {
+        title: shown_song(content)?,
+        service: content
+            .get("serviceTitle")
+            .and_then(Value::as_str)
+            .map(|service| String::from(service.trim()))
+            .filter(|service| !service.is_empty()),
+        date: shown_at.with_timezone(&Local).date_naive(),
+    })
+}
+
+/// Logs a usage event whenever a different song to the last one is shown on the displays.
+/// The same song counts as a new use in a different service or on a different day.
+pub async fn log_task(services: Arc<AppServices>) {
+    let mut watch_recv = services.state_service.watch_recv.clone();
+    let mut last_use = None;
+
+    loop {
+        tokio::select! {
+            changed = watch_recv.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+            _ = services.shutdown_token.cancelled() => return,
+        }
+
+        let content = watch_recv.borrow_and_update().content.clone();
+        let shown_at = Utc::now();
+        // switching verses or to other content and back doesn't count as a new use
+        let Some(song_use) = song_use(&content, shown_at) else {
+            continue;
+        };
+        if last_use.as_ref() == Some(&song_use) {
+            continue;
+        }
+
+        let library_song = services
+            .song_service
+            .get(&services.content_service, &song_use.title)
+            .ok();
+        let event = SongUsageEvent {
+            title: song_use.title.clone(),
+            ccli_number: library_song
+                .as_ref()
+                .and_then(|song| song.ccli_number.clone()),
+            authors: library_song
+                .as_ref()
+                .map(|song| song.authors.clone())
+                .unwrap_or_default(),
+            copyright: library_song.and_then(|song| song.copyright),
+            service: song_use.service.clone(),
+            shown_at,
+        };
+        let _ = services.song_usage_service.log(&event);
+        last_use = Some(song_use);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+
+    use super::*;
+
+    /// Usage event for a song shown in a service at a local date and time
+    fn event(title: &str, service: Option<&str>, shown_at: &str) -> SongUsageEvent {
+        let shown_at = chrono::NaiveDateTime::parse_from_str(shown_at, "%Y-%m-%d %H:%M").unwrap();
+        SongUsageEvent {
+            title: String::from(title),
+            ccli_number: Some(String::from("22025")),
+            authors: vec![String::from("John Newton"), String::from("Edwin Excell")],
+            copyright: Some(String::from("Public Domain")),
+            service: service.map(String::from),
+            shown_at: Local
+                .from_local_datetime(&shown_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn finds_the_shown_song() {
+        let content =
+            json!({ "song": "Amazing grace", "songId": "Amazing Grace", "songTitle": "Grace" });
+        assert_eq!(shown_song(&content).as_deref(), Some("Amazing Grace"));
+
+        let content = json!({ "song": "Amazing grace", "songId": " ", "songTitle": "Grace" });
+        assert_eq!(shown_song(&content).as_deref(), Some("Grace"));
+
+        // other content, or a song without a title
+        assert_eq!(shown_song(&json!({ "songTitle": "Grace" })), None);
+        assert_eq!(shown_song(&json!({ "song": "Amazing grace" })), None);
+    }
+
+    #[test]
+    fn counts_the_same_song_again_in_another_service_or_day() {
+        let now = Utc::now();
+        let content =
+            json!({ "song": "Verse", "songId": "Amazing Grace", "serviceTitle": "Morning" });
+        let first = song_use(&content, now).unwrap();
+
+        // another verse of the same song in the same service
+        let other_verse =
+            json!({ "song": "Chorus", "songId": "Amazing Grace", "serviceTitle": " Morning " });
+        assert!(song_use(&other_verse, now).unwrap() == first);
+
+        let other_service =
+            json!({ "song": "Verse", "songId": "Amazing Grace", "serviceTitle": "Evening" });
+        assert!(song_use(&other_service, now).unwrap() != first);
+
+        let next_week = now + Duration::days(7);
+        assert!(song_use(&content, next_week).unwrap() != first);
+    }
+
+    #[test]
+    fn reports_songs_once_per_service() {
+        let service = SongUsageService::new(Database::in_memory());
+        for event in [
+            event("Amazing Grace", Some("Morning"), "2026-10-04 10:00"),
+            event("Amazing Grace", Some("Morning"), "2026-10-04 10:30"),
+            event("Amazing Grace", Some("Evening"), "2026-10-04 18:00"),
+            event("Amazing Grace", None, "2026-10-11 10:00"),
+            event("Be Thou My Vision", Some("Morning"), "2026-10-11 10:00"),
+            event("Be Thou My Vision", Some("Morning"), "2026-11-01 10:00"),
+        ] {
+            service.log(&event).unwrap();
+        }
+
+        let report = service.report(None, None).unwrap();
+        let counts: Vec<_> = report
+            .songs
+            .iter()
+            .map(|song| (song.title.as_str(), song.times_used))
+            .collect();
+        assert_eq!(counts, [("Amazing Grace", 3), ("Be Thou My Vision", 2)]);
+
+        let song = &report.songs[0];
+        assert_eq!(song.authors, ["John Newton", "Edwin Excell"]);
+        assert_eq!(song.first_used.to_string(), "2026-10-04");
+        assert_eq!(song.last_used.to_string(), "2026-10-11");
+
+        // only uses in the reporting period are counted
+        let from = NaiveDate::from_ymd_opt(2026, 10, 5);
+        let to = NaiveDate::from_ymd_opt(2026, 10, 31);
+        let report = service.report(from, to).unwrap();
+        let counts: Vec<_> = report
+            .songs
+            .iter()
+            .map(|song| (song.title.as_str(), song.times_used))
+            .collect();
+        assert_eq!(counts, [("Amazing Grace", 1), ("Be Thou My Vision", 1)]);
+    }
+}
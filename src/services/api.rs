@@ -0,0 +1,731 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        ConnectInfo, Path, Query, State, WebSocketUpgrade,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use chrono::NaiveDate;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+
+use crate::helpers::errors::GenericError;
+
+use crate::app::AppServices;
+
+use super::{
+    models::{
+        CursorCommand, CursorGoto, InstantiateRequest, LiveCursor, ServiceData, ServiceOperation,
+        ServiceRequest, ServiceUpdate, StoredService, TemplateRequest,
+    },
+    planning_center,
+    print::{self, PrintFormat, PrintOptions},
+    templates::{fill_placeholders, DATE_PLACEHOLDER},
+};
+
+/// Service routes
+pub fn route() -> Router<Arc<AppServices>> {
+    Router::new()
+        .route("/", get(list_services))
+        .route("/", post(create_service))
+        .route("/updates", get(updates_handler))
+        .route("/cursor", get(get_cursor))
+        .route("/cursor", delete(stop_cursor))
+        .route("/cursor/next", post(next_cursor))
+        .route("/cursor/previous", post(previous_cursor))
+        .route("/cursor/show", post(show_cursor))
+        .route("/cursor/goto", post(goto_cursor))
+        .route("/cursor/ws", get(cursor_handler))
+        .route("/import/planning-center", post(import_planning_center))
+        .route("/print", get(print_file))
+        .route("/print", post(print_service))
+        .route("/templates", get(list_templates))
+        .route("/templates", post(create_template))
+        .route("/templates/:id", get(get_template))
+        .route("/templates/:id", put(update_template))
+        .route("/templates/:id", delete(delete_template))
+        .route("/templates/:id/instantiate", post(instantiate_template))
+        .route("/:id", get(get_service))
+        .route("/:id", put(update_service))
+        .route("/:id", delete(delete_service))
+        .route("/:id/live", get(live_handler))
+        .route("/:id/print", get(print_stored_service))
+}
+
+/// Entity tag of a stored service (its revision)
+fn etag(service: &StoredService) -> String {
+    format!("\"{}\"", service.revision)
+}
+
+/// Gets the expected revision from the `If-Match` header.
+/// A header that isn't a revision never matches.
+fn if_match(headers: &HeaderMap) -> Result<Option<u64>, StatusCode> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| value.trim().trim_matches('"').parse().ok())
+        .map(Some)
+        .ok_or(StatusCode::PRECONDITION_FAILED)
+}
+
+/// Service list query
+#[derive(Serialize, Deserialize)]
+pub struct ListQuery {
+    /// First service date to list (inclusive)
+    pub from: Option<NaiveDate>,
+    /// Last service date to list (inclusive)
+    pub to: Option<NaiveDate>,
+}
+
+/// Lists stored services by date, newest first
+pub async fn list_services(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<ListQuery>,
+) -> impl IntoResponse {
+    match state.service_plan_service.list(query.from, query.to) {
+        Ok(services) => Json(services).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Gets a stored service, with its revision in the `ETag` header
+pub async fn get_service(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.service_plan_service.get(&id) {
+        Ok(service) => ([(header::ETAG, etag(&service))], Json(service)).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Stores a new service
+pub async fn create_service(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(request): Json<ServiceRequest>,
+) -> impl IntoResponse {
+    match state.service_plan_service.create(
+        request.date,
+        request.data,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(service) => (
+            StatusCode::CREATED,
+            [(header::ETAG, etag(&service))],
+            Json(service),
+        )
+            .into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Replaces a stored service.
+/// If the `If-Match` header is set, the service is only updated if it hasn't been changed by someone else.
+pub async fn update_service(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<ServiceRequest>,
+) -> impl IntoResponse {
+    let expected_revision = match if_match(&headers) {
+        Ok(revision) => revision,
+        Err(status) => return status.into_response(),
+    };
+
+    match state.service_plan_service.update(
+        &id,
+        request.date,
+        request.data,
+        expected_revision,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(service) => ([(header::ETAG, etag(&service))], Json(service)).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Deletes a stored service.
+/// If the `If-Match` header is set, the service is only deleted if it hasn't been changed by someone else.
+pub async fn delete_service(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let expected_revision = match if_match(&headers) {
+        Ok(revision) => revision,
+        Err(status) => return status.into_response(),
+    };
+
+    match state.service_plan_service.delete(&id, expected_revision) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Converts a Planning Center Services plan export into a service,
+/// matching its songs to the song library (songs that can't be matched are listed and flagged in the service)
+pub async fn import_planning_center(
+    State(state): State<Arc<AppServices>>,
+    Json(export): Json<serde_json::Value>,
+) -> Response {
+    let library = match state.song_service.load(&state.content_service) {
+        Ok(library) => library,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    match planning_center::convert(&export, &library) {
+        Some(plan_import) => Json(plan_import).into_response(),
+        None => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// Print query
+#[derive(Serialize, Deserialize)]
+pub struct PrintQuery {
+    /// Service file in the content directory (when printing a file)
+    pub file: Option<String>,
+    #[serde(default)]
+    pub format: PrintFormat,
+    /// Whether to print chords above the lyrics
+    #[serde(default)]
+    pub chords: bool,
+    /// Whether to print the lyrics of the songs
+    pub lyrics: Option<bool>,
+}
+
+impl PrintQuery {
+    fn options(&self) -> PrintOptions {
+        PrintOptions {
+            format: self.format,
+            chords: self.chords,
+            lyrics: self.lyrics.unwrap_or(true),
+        }
+    }
+}
+
+/// Renders a service for printing, with its songs from the song library
+fn print_response(
+    state: &AppServices,
+    data: &ServiceData,
+    date: Option<NaiveDate>,
+    options: PrintOptions,
+) -> Response {
+    let library = match state.song_service.load(&state.content_service) {
+        Ok(library) => library,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    let content_type = match options.format {
+        PrintFormat::Html => "text/html; charset=utf-8",
+        PrintFormat::Text => "text/plain; charset=utf-8",
+    };
+    (
+        [(header::CONTENT_TYPE, content_type)],
+        print::render(data, date, &library, options),
+    )
+        .into_response()
+}
+
+/// Renders a service (in the format the client exports services in) for printing
+pub async fn print_service(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<PrintQuery>,
+    Json(data): Json<ServiceData>,
+) -> Response {
+    print_response(&state, &data, None, query.options())
+}
+
+/// Renders a service file in the content directory for printing
+pub async fn print_file(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<PrintQuery>,
+) -> Response {
+    let Some(file) = &query.file else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let path = match state.content_service.resolve_path(file) {
+        Ok(path) => path,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    if !path.is_file() {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let text = match state.content_service.read_text(&path) {
+        Ok((text, _)) => text,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    let Ok(data) = serde_json::from_str::<ServiceData>(&text) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    print_response(&state, &data, None, query.options())
+}
+
+/// Renders a stored service for printing
+pub async fn print_stored_service(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+    Query(query): Query<PrintQuery>,
+) -> Response {
+    match state.service_plan_service.get(&id) {
+        Ok(service) => print_response(&state, &service.data, service.date, query.options()),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Lists service templates by name, with their placeholders
+pub async fn list_templates(State(state): State<Arc<AppServices>>) -> impl IntoResponse {
+    match state.service_template_service.list() {
+        Ok(templates) => Json(templates).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Gets a service template
+pub async fn get_template(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.service_template_service.get(&id) {
+        Ok(template) => Json(template).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Stores a new service template
+pub async fn create_template(
+    State(state): State<Arc<AppServices>>,
+    Json(request): Json<TemplateRequest>,
+) -> impl IntoResponse {
+    match state
+        .service_template_service
+        .create(request.name, request.data)
+    {
+        Ok(template) => (StatusCode::CREATED, Json(template)).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Replaces a service template
+pub async fn update_template(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+    Json(request): Json<TemplateRequest>,
+) -> impl IntoResponse {
+    match state
+        .service_template_service
+        .update(&id, request.name, request.data)
+    {
+        Ok(template) => Json(template).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Deletes a service template
+pub async fn delete_template(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.service_template_service.delete(&id) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Stores a new service created from a template, with the placeholders filled in
+/// (`{{date}}` is the service date unless another value is given)
+pub async fn instantiate_template(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    Json(request): Json<InstantiateRequest>,
+) -> impl IntoResponse {
+    let template = match state.service_template_service.get(&id) {
+        Ok(template) => template,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    let mut values = request.values;
+    if let Some(date) = request.date {
+        values
+            .entry(String::from(DATE_PLACEHOLDER))
+            .or_insert_with(|| date.to_string());
+    }
+    let data = fill_placeholders(template.data, &values);
+
+    match state.service_plan_service.create(
+        request.date,
+        data,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(service) => (
+            StatusCode::CREATED,
+            [(header::ETAG, etag(&service))],
+            Json(service),
+        )
+            .into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Gets the position in the live service (`null` if no service is live)
+pub async fn get_cursor(State(state): State<Arc<AppServices>>) -> impl IntoResponse {
+    Json(state.live_cursor_service.get())
+}
+
+/// Applies a live cursor command, responding with the new position
+fn apply_cursor_command(state: &AppServices, command: CursorCommand) -> Response {
+    match state.live_cursor_service.apply(state, command) {
+        Ok(cursor) => Json(cursor).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Shows the next enabled verse or item of the live service
+pub async fn next_cursor(State(state): State<Arc<AppServices>>) -> Response {
+    apply_cursor_command(&state, CursorCommand::Next)
+}
+
+/// Shows the previous enabled verse or item of the live service
+pub async fn previous_cursor(State(state): State<Arc<AppServices>>) -> Response {
+    apply_cursor_command(&state, CursorCommand::Previous)
+}
+
+/// Shows the current verse or item of the live service again
+pub async fn show_cursor(State(state): State<Arc<AppServices>>) -> Response {
+    apply_cursor_command(&state, CursorCommand::Show)
+}
+
+/// Shows a verse or item, making its service the live service
+pub async fn goto_cursor(
+    State(state): State<Arc<AppServices>>,
+    Json(goto): Json<CursorGoto>,
+) -> Response {
+    apply_cursor_command(&state, CursorCommand::Goto(goto))
+}
+
+/// Stops tracking the live service
+pub async fn stop_cursor(State(state): State<Arc<AppServices>>) -> Response {
+    apply_cursor_command(&state, CursorCommand::Stop)
+}
+
+/// Live cursor messages sent to the client
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CursorResponse {
+    Cursor { cursor: Option<LiveCursor> },
+    Error { error: String },
+}
+
+/// Upgrades to a websocket for following and moving the live cursor
+pub async fn cursor_handler(
+    State(state): State<Arc<AppServices>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(|socket| cursor_websocket_handler(socket, state))
+}
+
+/// Sends the live cursor to the client whenever it moves, and applies commands from the client
+/// (commands that fail are answered with an error)
+pub async fn cursor_websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
+    let (mut ws_send, mut ws_recv) = socket.split();
+
+    // send a message to this queue to send it to the client
+    let (queue_send, mut queue_recv) = mpsc::channel::<String>(16);
+
+    // sends messages to the client from the message queue
+    let mut send_task = tokio::spawn(async move {
+        while let Some(message) = queue_recv.recv().await {
+            if ws_send.send(Message::Text(message)).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // sends the cursor, then every change to it
+    let w_state = state.clone();
+    let w_queue_send = queue_send.clone();
+    let mut watch_task = tokio::spawn(async move {
+        let mut watch_recv = w_state.live_cursor_service.watch_recv.clone();
+        loop {
+            let cursor = watch_recv.borrow_and_update().clone();
+            let response_json = serde_json::to_string(&CursorResponse::Cursor { cursor }).unwrap();
+            if w_queue_send.send(response_json).await.is_err() {
+                return;
+            }
+            if watch_recv.changed().await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // applies commands from the client
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_recv.next().await {
+            let msg = match msg {
+                Message::Text(msg) => msg,
+                Message::Close(_) => return,
+                _ => continue,
+            };
+
+            let result = serde_json::from_str::<CursorCommand>(&msg)
+                .map_err(|_| GenericError::BAD_REQUEST)
+                .and_then(|command| state.live_cursor_service.apply(&state, command));
+            if let Err(err) = result {
+                let response = CursorResponse::Error {
+                    error: err.to_string(),
+                };
+                let response_json = serde_json::to_string(&response).unwrap();
+                if queue_send.send(response_json).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => {
+            recv_task.abort();
+            watch_task.abort();
+        }
+        _ = (&mut recv_task) => {
+            send_task.abort();
+            watch_task.abort();
+        }
+        _ = (&mut watch_task) => {
+            send_task.abort();
+            recv_task.abort();
+        }
+    }
+}
+
+/// Upgrades to a websocket that receives changes to stored services
+pub async fn updates_handler(
+    State(state): State<Arc<AppServices>>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(|socket| updates_websocket_handler(socket, state))
+}
+
+/// Sends every change to stored services to the client until it disconnects
+pub async fn updates_websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
+    let (mut ws_send, mut ws_recv) = socket.split();
+    let mut updates = state.service_plan_service.updates.subscribe();
+
+    // sends updates to the client
+    let mut send_task = tokio::spawn(async move {
+        loop {
+            let update_json = match updates.recv().await {
+                Ok(update) => serde_json::to_string(&update).unwrap(),
+                // some updates were missed, so the client needs to reload its services
+                Err(RecvError::Lagged(_)) => {
+                    serde_json::to_string(&UpdatesResponse::Resync).unwrap()
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if ws_send.send(Message::Text(update_json)).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // waits for the client to disconnect (messages from the client are ignored)
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_recv.next().await {
+            if let Message::Close(_) = msg {
+                return;
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => recv_task.abort(),
+        _ = (&mut recv_task) => send_task.abort(),
+    }
+}
+
+/// Messages only sent to a single client receiving service updates
+/// (service updates are sent to everyone)
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpdatesResponse {
+    /// Some updates were missed, so the services the client has open should be reloaded
+    Resync,
+}
+
+/// Operation sent by a client editing a service live
+#[derive(Serialize, Deserialize)]
+pub struct LiveRequest {
+    /// ID chosen by the client, sent back with the applied operation (or the rejection)
+    pub op_id: String,
+    pub operation: ServiceOperation,
+}
+
+/// Messages only sent to the client editing a service live that sent an operation
+/// (applied operations are sent to everyone as service updates)
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveResponse {
+    /// Operation couldn't be applied, e.g. because someone else deleted the item
+    Rejected { op_id: String },
+    /// Message couldn't be read as an operation (and didn't have an operation ID)
+    Error { error: String },
+}
+
+/// Upgrades to a websocket for editing a stored service live
+pub async fn live_handler(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if let Err(err) = state.service_plan_service.get(&id) {
+        return err.to_status_code().into_response();
+    }
+    ws.on_upgrade(move |socket| {
+        live_websocket_handler(socket, state, id, remote_address.ip().to_string())
+    })
+}
+
+/// Edits a stored service live.
+/// The client is sent the service, then every change to it (including its own operations, in the order they were applied).
+/// Operations from the client are applied one at a time and rejected if they can't be applied.
+pub async fn live_websocket_handler(
+    socket: WebSocket,
+    state: Arc<AppServices>,
+    id: String,
+    modified_by: String,
+) {
+    let (mut ws_send, mut ws_recv) = socket.split();
+
+    // send a message to this queue to send it to the client
+    let (queue_send, mut queue_recv) = mpsc::channel::<String>(16);
+
+    // sends messages to the client from the message queue
+    let mut send_task = tokio::spawn(async move {
+        while let Some(message) = queue_recv.recv().await {
+            if ws_send.send(Message::Text(message)).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // sends the service, then changes to it
+    let u_state = state.clone();
+    let u_id = id.clone();
+    let u_queue_send = queue_send.clone();
+    let mut update_task = tokio::spawn(async move {
+        // subscribe before getting the service so that no changes are missed
+        let mut updates = u_state.service_plan_service.updates.subscribe();
+        let mut resync = true;
+        loop {
+            if resync {
+                // send the whole service (the client replaces its copy with it)
+                let update = match u_state.service_plan_service.get(&u_id) {
+                    Ok(service) => ServiceUpdate::Saved { service },
+                    Err(_) => ServiceUpdate::Deleted { id: u_id.clone() },
+                };
+                let update_json = serde_json::to_string(&update).unwrap();
+                if u_queue_send.send(update_json).await.is_err() {
+                    return;
+                }
+                resync = false;
+            }
+
+            let update = match updates.recv().await {
+                Ok(update) => update,
+                // some changes were missed, so the client needs the whole service again
+                Err(RecvError::Lagged(_)) => {
+                    resync = true;
+                    continue;
+                }
+                Err(RecvError::Closed) => return,
+            };
+            let update_id = match &update {
+                ServiceUpdate::Saved { service } => &service.id,
+                ServiceUpdate::Deleted { id } | ServiceUpdate::Operation { id, .. } => id,
+            };
+            if *update_id != u_id {
+                continue;
+            }
+            let update_json = serde_json::to_string(&update).unwrap();
+            if u_queue_send.send(update_json).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    // applies operations from the client
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = ws_recv.next().await {
+            let msg = match msg {
+                Message::Text(msg) => msg,
+                Message::Close(_) => return,
+                _ => continue,
+            };
+            let request = match serde_json::from_str::<LiveRequest>(&msg) {
+                Ok(request) => request,
+                Err(err) => {
+                    // the operation is rejected if its ID can be read, so the client stops waiting for it
+                    let op_id = serde_json::from_str::<Value>(&msg).ok().and_then(|value| {
+                        value.get("op_id").and_then(Value::as_str).map(String::from)
+                    });
+                    let response = match op_id {
+                        Some(op_id) => LiveResponse::Rejected { op_id },
+                        None => LiveResponse::Error {
+                            error: err.to_string(),
+                        },
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    if queue_send.send(response_json).await.is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+
+            let applied = state.service_plan_service.apply_operation(
+                &id,
+                request.op_id.clone(),
+                request.operation,
+                Some(modified_by.clone()),
+            );
+            if !matches!(applied, Ok(Some(_))) {
+                let response = LiveResponse::Rejected {
+                    op_id: request.op_id,
+                };
+                let response_json = serde_json::to_string(&response).unwrap();
+                if queue_send.send(response_json).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    tokio::select! {
+        _ = (&mut send_task) => {
+            recv_task.abort();
+            update_task.abort();
+        }
+        _ = (&mut recv_task) => {
+            send_task.abort();
+            update_task.abort();
+        }
+        _ = (&mut update_task) => {
+            send_task.abort();
+            recv_task.abort();
+        }
+    }
+}
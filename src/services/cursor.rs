@@ -0,0 +1,406 @@
+use std::sync::Mutex;
+
+use rand::distributions::{Alphanumeric, DistString};
+use serde_json::{json, Map, Value};
+use tokio::sync::watch;
+
+use crate::{
+    app::AppServices,
+    helpers::{errors::GenericError, sort::natcasecmp},
+    songs::models::SongLibrary,
+    state::models::CurrentState,
+};
+
+use super::models::{CursorCommand, LiveCursor, ServiceData, ServiceItem, ServiceItemType};
+
+/// Length of the state IDs generated for the displays (the same as the client's)
+const STATE_ID_LENGTH: usize = 16;
+
+/// Tracks where the running service is, so that it can be advanced from anywhere
+/// (and isn't lost if the operator's computer crashes)
+pub struct LiveCursorService {
+    /// Held while the cursor is moved, so that commands are applied one at a time
+    lock: Mutex<()>,
+    pub watch_send: watch::Sender<Option<LiveCursor>>,
+    pub watch_recv: watch::Receiver<Option<LiveCursor>>,
+}
+
+/// Verse (or item other than a song) that can be shown, in service order
+struct Position {
+    item_index: usize,
+    verse_index: usize,
+    sub_item: String,
+    verse: Option<String>,
+    /// Whether the position is shown when moving to the next or previous position
+    enabled: bool,
+}
+
+/// Where a command moves the cursor
+enum Target {
+    At { item: usize, verse: usize },
+    Current,
+    Step(isize),
+}
+
+impl LiveCursorService {
+    pub fn new() -> Self {
+        let (watch_send, watch_recv) = watch::channel(None);
+        Self {
+            lock: Mutex::new(()),
+            watch_send,
+            watch_recv,
+        }
+    }
+
+    /// Gets the live cursor (`None` if no service is live)
+    pub fn get(&self) -> Option<LiveCursor> {
+        self.watch_recv.borrow().clone()
+    }
+
+    /// Moves the live cursor and shows the verse or item it is on on the displays.
+    /// Moving past the first or last enabled verse leaves the cursor where it is.
+    pub fn apply(
+        &self,
+        services: &AppServices,
+        command: CursorCommand,
+    ) -> Result<Option<LiveCursor>, GenericError> {
+        let _lock = self
+            .lock
+            .lock()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        let current = self.get();
+        let current_service_id = current.as_ref().map(|cursor| cursor.service_id.clone());
+
+        let (service_id, target) = match command {
+            CursorCommand::Stop => {
+                self.watch_send.send_replace(None);
+                return Ok(None);
+            }
+            CursorCommand::Goto(goto) => (
+                goto.service_id.or(current_service_id),
+                Target::At {
+                    item: goto.item,
+                    verse: goto.verse,
+                },
+            ),
+            CursorCommand::Next => (current_service_id, Target::Step(1)),
+            CursorCommand::Previous => (current_service_id, Target::Step(-1)),
+            CursorCommand::Show => (current_service_id, Target::Current),
+        };
+        let service_id = service_id.ok_or(GenericError::CONFLICT)?;
+
+        let service = services.service_plan_service.get(&service_id)?;
+        let library = services.song_service.load(&services.content_service)?;
+        let positions = positions(&service.data, &library);
+
+        // the current item is found by its ID, in case items were moved since
+        // (the position is lost if the item was deleted)
+        let current_index = current
+            .as_ref()
+            .filter(|cursor| cursor.service_id == service_id)
+            .and_then(|cursor| {
+                let item_index = match &cursor.item_id {
+                    Some(item_id) => service
+                        .data
+                        .service_items
+                        .iter()
+                        .position(|item| item.id.as_ref() == Some(item_id))?,
+                    None => cursor.item_index,
+                };
+                positions.iter().position(|position| {
+                    position.item_index == item_index && position.verse_index == cursor.verse_index
+                })
+            });
+
+        let index = match target {
+            Target::At { item, verse } => positions
+                .iter()
+                .position(|position| position.item_index == item && position.verse_index == verse)
+                .ok_or(GenericError::NOT_FOUND)?,
+            Target::Current => current_index.ok_or(GenericError::NOT_FOUND)?,
+            Target::Step(step) => {
+                let next = match current_index {
+                    Some(index) if step > 0 => positions
+                        .iter()
+                        .enumerate()
+                        .skip(index + 1)
+                        .find(|(_, position)| position.enabled),
+                    Some(index) => positions
+                        .iter()
+                        .enumerate()
+                        .take(index)
+                        .rev()
+                        .find(|(_, position)| position.enabled),
+                    // starts from the start or end if the cursor isn't on a verse of the service anymore
+                    None if step > 0 => positions
+                        .iter()
+                        .enumerate()
+                        .find(|(_, position)| position.enabled),
+                    None => positions
+                        .iter()
+                        .enumerate()
+                        .rev()
+                        .find(|(_, position)| position.enabled),
+                };
+                match next {
+                    Some((index, _)) => index,
+                    None => return Ok(current),
+                }
+            }
+        };
+
+        let position = &positions[index];
+        let item = &service.data.service_items[position.item_index];
+        let cursor = LiveCursor {
+            service_id,
+            item_id: item.id.clone(),
+            item_index: position.item_index,
+            verse_index: position.verse_index,
+            sub_item: position.sub_item.clone(),
+            verse: position.verse.clone(),
+        };
+
+        let mut state = CurrentState {
+            id: Alphanumeric.sample_string(&mut rand::thread_rng(), STATE_ID_LENGTH),
+            content: state_content(&service.data, item, position.verse.as_deref(), &library),
+        };
+        state.strip_for_displays();
+        services.state_service.watch_send.send_replace(state);
+
+        self.watch_send.send_replace(Some(cursor.clone()));
+        Ok(Some(cursor))
+    }
+}
+
+impl Default for LiveCursorService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Gets every verse (or item other than a song) in a service, in the order the client steps through them:
+/// the verses of a song's arrangement in order, or the song's verse order if no arrangement is chosen
+/// (or all verses sorted by name if it doesn't have one, only the chosen verses are enabled)
+fn positions(data: &ServiceData, library: &SongLibrary) -> Vec<Position> {
+    let mut positions = Vec::new();
+    for (item_index, item) in data.service_items.iter().enumerate() {
+        if item.item_type != ServiceItemType::Song {
+            positions.push(Position {
+                item_index,
+                verse_index: 0,
+                sub_item: String::from("0"),
+                verse: None,
+                enabled: item.enabled.unwrap_or(true),
+            });
+            continue;
+        }
+        let Some((song, library_song)) = item
+            .song
+            .as_ref()
+            .and_then(|song| Some((song, library.get(&song.title)?)))
+        else {
+            continue;
+        };
+
+        let arrangement = song
+            .arrangement
+            .as_ref()
+            .and_then(|name| library_song.arrangements.get(name));
+        // verses can be repeated in an arrangement or verse order, so their sub items are numbered
+        let (verses, numbered): (Vec<_>, _) = match arrangement {
+            Some(arrangement) => (arrangement.iter().collect(), true),
+            None if !library_song.verse_order.is_empty() => (
+                library_song
+                    .verse_order
+                    .iter()
+                    .filter(|verse| library_song.verses.contains_key(*verse))
+                    .collect(),
+                true,
+            ),
+            None => {
+                let mut verses: Vec<_> = library_song.verses.keys().collect();
+                verses.sort_by(|a, b| natcasecmp(a, b));
+                (verses, false)
+            }
+        };
+
+        positions.extend(
+            verses
+                .into_iter()
+                .enumerate()
+                .map(|(index, verse)| Position {
+                    item_index,
+                    verse_index: index,
+                    sub_item: match numbered {
+                        true => format!("{}:{}", index + 1, verse),
+                        false => verse.clone(),
+                    },
+                    verse: Some(verse.clone()),
+                    // all verses in an arrangement are shown
+                    enabled: arrangement.is_some()
+                        || song.verses.is_empty()
+                        || song.verses.contains(verse),
+                }),
+        );
+    }
+    positions
+}
+
+/// Gets the display state for an item (the same as the client shows)
+fn state_content(
+    data: &ServiceData,
+    item: &ServiceItem,
+    verse: Option<&str>,
+    library: &SongLibrary,
+) -> Value {
+    let text_content = |key: &str| {
+        let mut content = Map::new();
+        content.insert(String::from("background"), Value::Bool(false));
+        if let Some(text) = &item.text {
+            content.insert(String::from(key), Value::String(text.clone()));
+        }
+        Value::Object(content)
+    };
+
+    match item.item_type {
+        ServiceItemType::Song => {
+            let song_title = item.song.as_ref().map(|song| &song.title);
+            let library_song = song_title.and_then(|title| library.get(title));
+            let verse_text = library_song
+                .zip(verse)
+                .and_then(|(library_song, verse)| library_song.verses.get(verse));
+
+            // songs without a language are assumed to be in the primary language
+            let mut song_languages = Vec::new();
+            if let (Some(library_song), Some(verse), Some(_)) = (library_song, verse, verse_text) {
+                let song_language = library_song.language.as_ref().or(data.languages.first());
+                for language in &data.languages {
+                    let text = if Some(language) == song_language {
+                        library_song.verses.get(verse)
+                    } else {
+                        library_song
+                            .translations
+                            .get(language)
+                            .and_then(|verses| verses.get(verse))
+                    };
+                    if let Some(text) = text {
+                        song_languages.push((language, text));
+                    }
+                }
+            }
+
+            let mut content = Map::new();
+            content.insert(String::from("background"), Value::Bool(true));
+            if let Some(text) = song_languages.first().map(|(_, text)| *text).or(verse_text) {
+                content.insert(String::from("song"), Value::String(text.clone()));
+            }
+            if !song_languages.is_empty() {
+                let song_languages = song_languages
+                    .into_iter()
+                    .map(|(language, text)| json!({ "language": language, "text": text }))
+                    .collect();
+                content.insert(String::from("songLanguages"), Value::Array(song_languages));
+            }
+            let shown_title = item
+                .text
+                .as_ref()
+                .filter(|text| !text.is_empty())
+                .or(song_title)
+                .cloned()
+                .unwrap_or_default();
+            content.insert(String::from("songTitle"), Value::String(shown_title));
+            if let Some(song_title) = song_title {
+                content.insert(String::from("songId"), Value::String(song_title.clone()));
+            }
+            if let Some(title) = &data.title {
+                content.insert(String::from("serviceTitle"), Value::String(title.clone()));
+            }
+            Value::Object(content)
+        }
+        ServiceItemType::MainText => text_content("mainText"),
+        ServiceItemType::SubText => text_content("subText"),
+        ServiceItemType::SmallText => text_content("smallText"),
+        ServiceItemType::Empty => json!({ "background": false }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Service with a song with an arrangement, a song in its verse order,
+    /// a song without a verse order and a text item
+    fn service() -> (ServiceData, SongLibrary) {
+        let library: SongLibrary = serde_json::from_value(json!({
+            "Amazing Grace": {
+                "verses": {"1": "Amazing grace", "2": "Twas grace", "c1": "My chains are gone"},
+                "verseOrder": ["1", "c1", "2", "c1", "missing"],
+                "arrangements": {"Short": ["1", "c1", "1"]},
+            },
+            "Be Thou My Vision": {
+                "verses": {"10": "Tenth", "2": "Second", "1": "First"},
+            },
+        }))
+        .unwrap();
+        let data: ServiceData = serde_json::from_value(json!({
+            "serviceItems": [
+                {"type": "song", "song": {"title": "Amazing Grace", "verses": [], "arrangement": "Short"}},
+                {"type": "song", "song": {"title": "Amazing Grace", "verses": ["1", "c1"]}},
+                {"type": "song", "song": {"title": "Be Thou My Vision", "verses": []}},
+                {"type": "song", "song": {"title": "Not In The Library", "verses": []}},
+                {"type": "mainText", "text": "Welcome", "enabled": false},
+            ],
+        }))
+        .unwrap();
+        (data, library)
+    }
+
+    #[test]
+    fn steps_through_arrangements_and_verse_orders() {
+        let (data, library) = service();
+        let positions: Vec<_> = positions(&data, &library)
+            .into_iter()
+            .map(|position| {
+                (
+                    position.item_index,
+                    position.verse_index,
+                    position.sub_item,
+                    position.verse,
+                    position.enabled,
+                )
+            })
+            .collect();
+
+        let verse = |item, index, sub_item: &str, verse: &str, enabled| {
+            (
+                item,
+                index,
+                String::from(sub_item),
+                Some(String::from(verse)),
+                enabled,
+            )
+        };
+        assert_eq!(
+            positions,
+            [
+                // arrangement (all verses are shown)
+                verse(0, 0, "1:1", "1", true),
+                verse(0, 1, "2:c1", "c1", true),
+                verse(0, 2, "3:1", "1", true),
+                // verse order (only the chosen verses are shown, missing verses are skipped)
+                verse(1, 0, "1:1", "1", true),
+                verse(1, 1, "2:c1", "c1", true),
+                verse(1, 2, "3:2", "2", false),
+                verse(1, 3, "4:c1", "c1", true),
+                // no verse order
+                verse(2, 0, "1", "1", true),
+                verse(2, 1, "2", "2", true),
+                verse(2, 2, "10", "10", true),
+                // the song that isn't in the library has no verses
+                (4, 0, String::from("0"), None, false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,7 @@
+pub mod api;
+pub mod cursor;
+pub mod models;
+pub mod planning_center;
+pub mod print;
+pub mod service;
+pub mod templates;
@@ -0,0 +1,453 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Service (order of worship), in the format the client exports services in
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceData {
+    #[serde(default)]
+    pub service_items: Vec<ServiceItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Languages to show songs in (the first is the primary language)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub languages: Vec<String>,
+    /// Any other fields (kept so that they aren't lost when the service is saved)
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Item in a service
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceItem {
+    /// Item ID (used by the client to keep track of items while editing)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub item_type: ServiceItemType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub song: Option<ServiceSong>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Item name shown to operators
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Service item type
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServiceItemType {
+    Empty,
+    Song,
+    MainText,
+    SubText,
+    SmallText,
+}
+
+/// Song in a service
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceSong {
+    /// Song library title
+    pub title: String,
+    /// Verses to show (all verses if empty)
+    #[serde(default)]
+    pub verses: Vec<String>,
+    /// Song arrangement to show the verses in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrangement: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Service stored on the server
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredService {
+    pub id: String,
+    /// Date of the service
+    pub date: Option<NaiveDate>,
+    /// Incremented every time the service is changed, used to detect concurrent edits
+    pub revision: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub data: ServiceData,
+}
+
+/// Service in the list of stored services
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceSummary {
+    pub id: String,
+    pub title: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub revision: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Service sent by the client when creating or updating a service
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServiceRequest {
+    /// Date of the service
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    #[serde(flatten)]
+    pub data: ServiceData,
+}
+
+/// Change to a stored service, sent to everyone editing services
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServiceUpdate {
+    /// Service was created or changed
+    Saved { service: StoredService },
+    /// Service was deleted
+    Deleted { id: String },
+    /// Operation was applied to a service by someone editing it live
+    Operation {
+        id: String,
+        /// Revision of the service after the operation
+        revision: u64,
+        /// ID given to the operation by the client that sent it
+        op_id: String,
+        operation: ServiceOperation,
+    },
+}
+
+/// Change to a single part of a service, made while editing it live.
+/// Items are referred to by ID so that operations still apply after other people's operations.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServiceOperation {
+    /// Inserts an item at a position (or at the end, if past the end)
+    Insert { index: usize, item: ServiceItem },
+    /// Moves an item to a position (or to the end, if past the end)
+    Move { item_id: String, index: usize },
+    /// Replaces an item
+    Update { item: ServiceItem },
+    /// Removes an item
+    Delete { item_id: String },
+    /// Replaces the service title, description and languages
+    Details {
+        #[serde(default)]
+        title: Option<String>,
+        #[serde(default)]
+        description: Option<String>,
+        #[serde(default)]
+        languages: Vec<String>,
+    },
+}
+
+impl ServiceData {
+    /// Gives every item without an ID a new ID
+    pub fn assign_item_ids(&mut self) {
+        for item in &mut self.service_items {
+            if item.id.is_none() {
+                item.id = Some(Uuid::new_v4().to_string());
+            }
+        }
+    }
+
+    /// Position of an item
+    fn item_index(&self, item_id: &str) -> Option<usize> {
+        self.service_items
+            .iter()
+            .position(|item| item.id.as_deref() == Some(item_id))
+    }
+
+    /// Applies an operation, returning whether it could be applied.
+    /// Operations on items that don't exist (e.g. that someone else deleted) and
+    /// inserts of items that already exist can't be applied.
+    pub fn apply(&mut self, operation: &ServiceOperation) -> bool {
+        match operation {
+            ServiceOperation::Insert { index, item } => {
+                let Some(item_id) = &item.id else {
+                    return false;
+                };
+                if self.item_index(item_id).is_some() {
+                    return false;
+                }
+                let index = (*index).min(self.service_items.len());
+                self.service_items.insert(index, item.clone());
+            }
+            ServiceOperation::Move { item_id, index } => {
+                let Some(current_index) = self.item_index(item_id) else {
+                    return false;
+                };
+                let item = self.service_items.remove(current_index);
+                let index = (*index).min(self.service_items.len());
+                self.service_items.insert(index, item);
+            }
+            ServiceOperation::Update { item } => {
+                let Some(index) = item.id.as_deref().and_then(|id| self.item_index(id)) else {
+                    return false;
+                };
+                self.service_items[index] = item.clone();
+            }
+            ServiceOperation::Delete { item_id } => {
+                let Some(index) = self.item_index(item_id) else {
+                    return false;
+                };
+                self.service_items.remove(index);
+            }
+            ServiceOperation::Details {
+                title,
+                description,
+                languages,
+            } => {
+                self.title.clone_from(title);
+                self.description.clone_from(description);
+                self.languages.clone_from(languages);
+            }
+        }
+        true
+    }
+}
+
+/// Service template stored on the server
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceTemplate {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Service with placeholders (e.g. `{{speaker}}`) in its title, description and items
+    #[serde(flatten)]
+    pub data: ServiceData,
+}
+
+/// Service template in the list of templates
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateSummary {
+    pub id: String,
+    pub name: String,
+    pub updated_at: DateTime<Utc>,
+    /// Names of the placeholders in the template
+    pub placeholders: Vec<String>,
+}
+
+/// Service template sent by the client when creating or updating a template
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemplateRequest {
+    pub name: String,
+    #[serde(flatten)]
+    pub data: ServiceData,
+}
+
+/// Request to create a service from a template
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct InstantiateRequest {
+    /// Date of the new service (also the value of the `date` placeholder)
+    #[serde(default)]
+    pub date: Option<NaiveDate>,
+    /// Values of the placeholders, by name
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+/// Position in the service being shown on the displays
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveCursor {
+    /// ID of the stored service
+    pub service_id: String,
+    /// ID of the item shown (used to find the item again when items are moved)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<String>,
+    /// Position of the item shown
+    pub item_index: usize,
+    /// Position of the verse shown in the item's verses (always 0 for items other than songs)
+    pub verse_index: usize,
+    /// Sub item ID used by the client (the verse name, `1:c` for verses in an arrangement or verse order, or `0` for items other than songs)
+    pub sub_item: String,
+    /// Name of the verse shown
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verse: Option<String>,
+}
+
+/// Command that moves the live cursor
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum CursorCommand {
+    /// Shows the next enabled verse or item
+    Next,
+    /// Shows the previous enabled verse or item
+    Previous,
+    /// Shows a verse or item (in another service, if a service ID is given)
+    Goto(CursorGoto),
+    /// Shows the current verse or item again (e.g. after it was changed)
+    Show,
+    /// Stops tracking the live service (the displays aren't changed)
+    Stop,
+}
+
+/// Position to move the live cursor to
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CursorGoto {
+    /// ID of the stored service (the live service if not given)
+    #[serde(default)]
+    pub service_id: Option<String>,
+    /// Position of the item
+    pub item: usize,
+    /// Position of the verse in the item's verses
+    #[serde(default)]
+    pub verse: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, text: &str) -> ServiceItem {
+        ServiceItem {
+            id: Some(String::from(id)),
+            item_type: ServiceItemType::MainText,
+            song: None,
+            text: Some(String::from(text)),
+            comment: None,
+            enabled: None,
+            extra: Map::new(),
+        }
+    }
+
+    fn service() -> ServiceData {
+        ServiceData {
+            service_items: vec![item("a", "A"), item("b", "B"), item("c", "C")],
+            ..Default::default()
+        }
+    }
+
+    fn item_ids(service: &ServiceData) -> Vec<&str> {
+        service
+            .service_items
+            .iter()
+            .map(|item| item.id.as_deref().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn inserts_items() {
+        let mut service = service();
+        assert!(service.apply(&ServiceOperation::Insert {
+            index: 1,
+            item: item("d", "D"),
+        }));
+        assert_eq!(item_ids(&service), ["a", "d", "b", "c"]);
+
+        // past the end
+        assert!(service.apply(&ServiceOperation::Insert {
+            index: 10,
+            item: item("e", "E"),
+        }));
+        assert_eq!(item_ids(&service), ["a", "d", "b", "c", "e"]);
+
+        // existing item or item without an ID
+        assert!(!service.apply(&ServiceOperation::Insert {
+            index: 0,
+            item: item("b", "B again"),
+        }));
+        let mut without_id = item("f", "F");
+        without_id.id = None;
+        assert!(!service.apply(&ServiceOperation::Insert {
+            index: 0,
+            item: without_id,
+        }));
+        assert_eq!(item_ids(&service), ["a", "d", "b", "c", "e"]);
+        assert_eq!(service.service_items[2].text.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn moves_items() {
+        let mut service = service();
+        assert!(service.apply(&ServiceOperation::Move {
+            item_id: String::from("a"),
+            index: 2,
+        }));
+        assert_eq!(item_ids(&service), ["b", "c", "a"]);
+
+        assert!(service.apply(&ServiceOperation::Move {
+            item_id: String::from("c"),
+            index: 0,
+        }));
+        assert_eq!(item_ids(&service), ["c", "b", "a"]);
+
+        // past the end
+        assert!(service.apply(&ServiceOperation::Move {
+            item_id: String::from("c"),
+            index: 10,
+        }));
+        assert_eq!(item_ids(&service), ["b", "a", "c"]);
+
+        assert!(!service.apply(&ServiceOperation::Move {
+            item_id: String::from("x"),
+            index: 0,
+        }));
+        assert_eq!(item_ids(&service), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn updates_items() {
+        let mut service = service();
+        assert!(service.apply(&ServiceOperation::Update {
+            item: item("b", "B changed"),
+        }));
+        assert_eq!(item_ids(&service), ["a", "b", "c"]);
+        assert_eq!(service.service_items[1].text.as_deref(), Some("B changed"));
+
+        assert!(!service.apply(&ServiceOperation::Update {
+            item: item("x", "X"),
+        }));
+        let mut without_id = item("b", "B");
+        without_id.id = None;
+        assert!(!service.apply(&ServiceOperation::Update { item: without_id }));
+        assert_eq!(service.service_items[1].text.as_deref(), Some("B changed"));
+    }
+
+    #[test]
+    fn deletes_items() {
+        let mut service = service();
+        assert!(service.apply(&ServiceOperation::Delete {
+            item_id: String::from("b"),
+        }));
+        assert_eq!(item_ids(&service), ["a", "c"]);
+
+        // someone else deleted the item already
+        assert!(!service.apply(&ServiceOperation::Delete {
+            item_id: String::from("b"),
+        }));
+        assert_eq!(item_ids(&service), ["a", "c"]);
+    }
+
+    #[test]
+    fn replaces_details() {
+        let mut service = ServiceData {
+            title: Some(String::from("Morning")),
+            description: Some(String::from("Old")),
+            languages: vec![String::from("en")],
+            ..service()
+        };
+        assert!(service.apply(&ServiceOperation::Details {
+            title: Some(String::from("Evening")),
+            description: None,
+            languages: vec![String::from("de"), String::from("en")],
+        }));
+        assert_eq!(service.title.as_deref(), Some("Evening"));
+        assert_eq!(service.description, None);
+        assert_eq!(service.languages, ["de", "en"]);
+        assert_eq!(item_ids(&service), ["a", "b", "c"]);
+    }
+}
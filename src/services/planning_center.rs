@@ -0,0 +1,452 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::songs::{duplicates::normalize_title, models::SongLibrary};
+
+use super::models::{ServiceData, ServiceItem, ServiceItemType, ServiceSong};
+
+/// Service converted from a Planning Center Services plan
+#[derive(Serialize, Deserialize)]
+pub struct PlanImport {
+    /// Service in the format the client exports services in
+    pub service: ServiceData,
+    /// Date of the plan
+    pub date: Option<NaiveDate>,
+    /// Songs in the plan and the library songs they were matched to
+    pub songs: Vec<ImportedSong>,
+}
+
+/// Song in a Planning Center plan
+#[derive(Serialize, Deserialize)]
+pub struct ImportedSong {
+    /// Position of the item in the service
+    pub item: usize,
+    /// Song title in Planning Center
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ccli_number: Option<String>,
+    /// Title of the song in the song library (`None` if it isn't in the song library)
+    pub matched: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matched_by: Option<SongMatch>,
+    /// Arrangement name in Planning Center
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arrangement: Option<String>,
+    /// Whether the library song has an arrangement with the same name
+    pub arrangement_matched: bool,
+}
+
+/// How a song was matched to a library song
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SongMatch {
+    CcliNumber,
+    Title,
+}
+
+/// Comment added to songs that aren't in the song library
+const UNMATCHED_COMMENT: &str = "Not in the song library";
+
+/// JSON:API resources in an export, by type and ID
+struct Resources<'a> {
+    resources: Vec<&'a Value>,
+    by_id: HashMap<(&'a str, &'a str), &'a Value>,
+}
+
+impl<'a> Resources<'a> {
+    /// Collects the resources in `data` and `included` (an export can also be a list of documents)
+    fn new(export: &'a Value) -> Self {
+        let mut resources = Vec::new();
+        let documents: Vec<&Value> = match export {
+            Value::Array(documents) => documents.iter().collect(),
+            document => vec![document],
+        };
+        for document in documents {
+            for key in ["data", "included"] {
+                match document.get(key) {
+                    Some(Value::Array(values)) => resources.extend(values),
+                    Some(value @ Value::Object(_)) => resources.push(value),
+                    _ => {}
+                }
+            }
+        }
+
+        let by_id = resources
+            .iter()
+            .filter_map(|resource| {
+                Some((
+                    (resource_type(resource)?, resource_id(resource)?),
+                    *resource,
+                ))
+            })
+            .collect();
+        Self { resources, by_id }
+    }
+
+    /// Resources of a type, in the order they appear
+    fn of_type(&self, resource_type_name: &str) -> Vec<&'a Value> {
+        let mut seen = Vec::new();
+        self.resources
+            .iter()
+            .copied()
+            .filter(|resource| resource_type(resource) == Some(resource_type_name))
+            // resources can be both in `data` and `included`
+            .filter(|resource| {
+                let id = resource_id(resource);
+                if seen.contains(&id) {
+                    return false;
+                }
+                seen.push(id);
+                true
+            })
+            .collect()
+    }
+
+    /// Resources a resource is related to
+    fn related(&self, resource: &Value, relationship: &str) -> Vec<&'a Value> {
+        let related = match resource
+            .get("relationships")
+            .and_then(|relationships| relationships.get(relationship))
+            .and_then(|relationship| relationship.get("data"))
+        {
+            Some(Value::Array(values)) => values.iter().collect(),
+            Some(value @ Value::Object(_)) => vec![value],
+            _ => Vec::new(),
+        };
+        related
+            .into_iter()
+            .filter_map(|identifier| {
+                self.by_id
+                    .get(&(resource_type(identifier)?, resource_id(identifier)?))
+                    .copied()
+            })
+            .collect()
+    }
+}
+
+fn resource_type(resource: &Value) -> Option<&str> {
+    resource.get("type").and_then(Value::as_str)
+}
+
+fn resource_id(resource: &Value) -> Option<&str> {
+    resource.get("id").and_then(Value::as_str)
+}
+
+/// Gets a text attribute of a resource (empty text is ignored)
+fn text_attribute(resource: &Value, name: &str) -> Option<String> {
+    match resource.get("attributes")?.get(name)? {
+        Value::String(text) if !text.trim().is_empty() => Some(String::from(text.trim())),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// Converts a Planning Center Services plan export (the JSON:API documents of the plan and its items,
+/// with the songs, arrangements and item notes included) into a service.
+/// Returns `None` if the export doesn't contain any plan items.
+pub fn convert(export: &Value, library: &SongLibrary) -> Option<PlanImport> {
+    let resources = Resources::new(export);
+    let plan = resources.of_type("Plan").into_iter().next();
+
+    let mut items = resources.of_type("Item");
+    if items.is_empty() {
+        return None;
+    }
+    items.sort_by_key(|item| {
+        item.get("attributes")
+            .and_then(|attributes| attributes.get("sequence"))
+            .and_then(Value::as_i64)
+            .unwrap_or(i64::MAX)
+    });
+
+    let mut service = ServiceData::default();
+    let mut date = None;
+    if let Some(plan) = plan {
+        service.title = text_attribute(plan, "title")
+            .or_else(|| text_attribute(plan, "series_title"))
+            .or_else(|| text_attribute(plan, "dates"));
+        service.description = text_attribute(plan, "series_title")
+            .filter(|series| service.title.as_ref() != Some(series));
+        date = text_attribute(plan, "sort_date")
+            .and_then(|sort_date| sort_date.get(..10).and_then(|day| day.parse().ok()));
+    }
+
+    let mut songs = Vec::new();
+    for item in items {
+        let item_type = text_attribute(item, "item_type").unwrap_or_default();
+        let title = text_attribute(item, "title");
+        let mut notes: Vec<String> = text_attribute(item, "description").into_iter().collect();
+        for note in resources.related(item, "item_notes") {
+            if let Some(content) = text_attribute(note, "content") {
+                notes.push(match text_attribute(note, "category_name") {
+                    Some(category) => format!("{}: {}", category, content),
+                    None => content,
+                });
+            }
+        }
+
+        let mut service_item = ServiceItem {
+            id: None,
+            item_type: ServiceItemType::MainText,
+            song: None,
+            text: title.clone(),
+            comment: None,
+            enabled: None,
+            extra: Map::new(),
+        };
+
+        if item_type == "song" {
+            let song = resources.related(item, "song").into_iter().next();
+            let arrangement = resources
+                .related(item, "arrangement")
+                .into_iter()
+                .next()
+                .and_then(|arrangement| text_attribute(arrangement, "name"));
+            let song_title = song
+                .and_then(|song| text_attribute(song, "title"))
+                .or(title.clone())
+                .unwrap_or_default();
+            let ccli_number = song.and_then(|song| text_attribute(song, "ccli_number"));
+
+            let matched = match_song(library, &song_title, ccli_number.as_deref());
+            let library_arrangement = matched.as_ref().and_then(|(matched_title, _)| {
+                let library_song = library.get(matched_title)?;
+                let arrangement = arrangement.as_ref()?;
+                library_song
+                    .arrangements
+                    .keys()
+                    .find(|name| name.to_lowercase() == arrangement.to_lowercase())
+                    .cloned()
+            });
+
+            service_item.item_type = ServiceItemType::Song;
+            service_item.song = Some(ServiceSong {
+                title: matched
+                    .as_ref()
+                    .map_or(song_title.clone(), |(matched_title, _)| {
+                        matched_title.clone()
+                    }),
+                verses: Vec::new(),
+                arrangement: library_arrangement.clone(),
+                extra: Map::new(),
+            });
+            // the item title is shown instead of the song title, so it is only kept if it is different
+            service_item.text = title.filter(|title| {
+                normalize_title(title) != normalize_title(&song_title)
+                    && matched.as_ref().is_none_or(|(matched_title, _)| {
+                        normalize_title(title) != normalize_title(matched_title)
+                    })
+            });
+            if matched.is_none() {
+                notes.insert(0, String::from(UNMATCHED_COMMENT));
+            }
+
+            songs.push(ImportedSong {
+                item: service.service_items.len(),
+                title: song_title,
+                ccli_number,
+                matched_by: matched.as_ref().map(|(_, matched_by)| *matched_by),
+                matched: matched.map(|(matched_title, _)| matched_title),
+                arrangement,
+                arrangement_matched: library_arrangement.is_some(),
+            });
+        } else if item_type == "header" {
+            service_item.item_type = ServiceItemType::SubText;
+        }
+
+        if !notes.is_empty() {
+            service_item.comment = Some(notes.join("; "));
+        }
+        service.service_items.push(service_item);
+    }
+
+    Some(PlanImport {
+        service,
+        date,
+        songs,
+    })
+}
+
+/// Finds a song in the song library by CCLI number, then by title or alternate title
+/// (exactly, then ignoring punctuation and bracketed parts)
+fn match_song(
+    library: &SongLibrary,
+    title: &str,
+    ccli_number: Option<&str>,
+) -> Option<(String, SongMatch)> {
+    if let Some(ccli_number) = ccli_number
+        .map(str::trim)
+        .filter(|number| !number.is_empty())
+    {
+        if let Some((library_title, _)) = library
+            .iter()
+            .find(|(_, song)| song.ccli_number.as_deref().map(str::trim) == Some(ccli_number))
+        {
+            return Some((library_title.clone(), SongMatch::CcliNumber));
+        }
+    }
+
+    // titles are matched exactly (ignoring case) first, so that a song with a bracketed part in its title
+    // (e.g. `Amazing Grace (My Chains Are Gone)`) isn't matched to the song without it
+    let find_title = |matches: &dyn Fn(&str) -> bool| {
+        library
+            .keys()
+            .find(|library_title| matches(library_title))
+            .or_else(|| {
+                library.iter().find_map(|(library_title, song)| {
+                    song.alternate_titles
+                        .iter()
+                        .any(|alternate| matches(alternate))
+                        .then_some(library_title)
+                })
+            })
+    };
+
+    let lowercase = title.trim().to_lowercase();
+    if lowercase.is_empty() {
+        return None;
+    }
+    let normalized = normalize_title(title);
+    find_title(&|other| other.trim().to_lowercase() == lowercase)
+        .or_else(|| {
+            // then ignoring punctuation and bracketed parts
+            (!normalized.is_empty())
+                .then(|| find_title(&|other| normalize_title(other) == normalized))
+                .flatten()
+        })
+        .map(|library_title| (library_title.clone(), SongMatch::Title))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn library() -> SongLibrary {
+        serde_json::from_value(json!({
+            "Amazing Grace": {"verses": {"1": "Amazing grace"}, "ccliNumber": "22025"},
+            "Amazing Grace (My Chains Are Gone)": {
+                "verses": {"1": "My chains are gone"},
+                "arrangements": {"Short": ["1"]},
+            },
+            "Be Thou My Vision": {"verses": {"1": "Be Thou my vision"}, "alternateTitles": ["Slane"]},
+        }))
+        .unwrap()
+    }
+
+    fn matched(title: &str, ccli_number: Option<&str>) -> Option<String> {
+        match_song(&library(), title, ccli_number).map(|(title, _)| title)
+    }
+
+    #[test]
+    fn matches_songs_by_ccli_number_first() {
+        assert!(matches!(
+            match_song(&library(), "Something Else", Some(" 22025 ")),
+            Some((_, SongMatch::CcliNumber))
+        ));
+        assert_eq!(
+            matched("Amazing Grace (My Chains Are Gone)", Some("22025")).as_deref(),
+            Some("Amazing Grace")
+        );
+    }
+
+    #[test]
+    fn matches_exact_titles_before_bracketed_titles() {
+        assert_eq!(
+            matched("amazing grace (my chains are gone)", Some("7046284")).as_deref(),
+            Some("Amazing Grace (My Chains Are Gone)")
+        );
+        assert_eq!(
+            matched(" AMAZING GRACE ", None).as_deref(),
+            Some("Amazing Grace")
+        );
+        assert_eq!(
+            matched("Amazing Grace!", None).as_deref(),
+            Some("Amazing Grace")
+        );
+    }
+
+    #[test]
+    fn matches_alternate_titles() {
+        assert_eq!(matched("slane", None).as_deref(), Some("Be Thou My Vision"));
+        assert_eq!(
+            matched("Slane (Traditional)", None).as_deref(),
+            Some("Be Thou My Vision")
+        );
+        assert_eq!(matched("Unknown Song", None), None);
+        assert_eq!(matched("  ", None), None);
+    }
+
+    #[test]
+    fn converts_plans() {
+        let export = json!({
+            "data": {
+                "type": "Plan",
+                "id": "1",
+                "attributes": {"title": "Sunday Morning", "series_title": "Grace", "sort_date": "2026-10-18T10:00:00Z"},
+            },
+            "included": [
+                {
+                    "type": "Item",
+                    "id": "11",
+                    "attributes": {"item_type": "song", "title": "Amazing Grace (My Chains Are Gone)", "sequence": 2},
+                    "relationships": {
+                        "song": {"data": {"type": "Song", "id": "21"}},
+                        "arrangement": {"data": {"type": "Arrangement", "id": "31"}},
+                    },
+                },
+                {
+                    "type": "Item",
+                    "id": "12",
+                    "attributes": {"item_type": "header", "title": "Worship", "sequence": 1},
+                },
+                {
+                    "type": "Item",
+                    "id": "13",
+                    "attributes": {"item_type": "song", "title": "New Song", "sequence": 3},
+                    "relationships": {"item_notes": {"data": [{"type": "ItemNote", "id": "41"}]}},
+                },
+                {"type": "Song", "id": "21", "attributes": {"title": "Amazing Grace (My Chains Are Gone)", "ccli_number": 4768151}},
+                {"type": "Arrangement", "id": "31", "attributes": {"name": "short"}},
+                {"type": "ItemNote", "id": "41", "attributes": {"category_name": "Band", "content": "Acoustic"}},
+            ],
+        });
+
+        let import = convert(&export, &library()).unwrap();
+
+        assert_eq!(import.service.title.as_deref(), Some("Sunday Morning"));
+        assert_eq!(import.service.description.as_deref(), Some("Grace"));
+        assert_eq!(import.date, NaiveDate::from_ymd_opt(2026, 10, 18));
+
+        let items = &import.service.service_items;
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items[0].item_type, ServiceItemType::SubText));
+        assert_eq!(items[0].text.as_deref(), Some("Worship"));
+
+        let song = items[1].song.as_ref().unwrap();
+        assert_eq!(song.title, "Amazing Grace (My Chains Are Gone)");
+        assert_eq!(song.arrangement.as_deref(), Some("Short"));
+        assert_eq!(items[1].text, None);
+
+        assert_eq!(items[2].song.as_ref().unwrap().title, "New Song");
+        assert_eq!(
+            items[2].comment.as_deref(),
+            Some("Not in the song library; Band: Acoustic")
+        );
+
+        assert_eq!(import.songs.len(), 2);
+        assert_eq!(import.songs[0].item, 1);
+        assert_eq!(import.songs[0].ccli_number.as_deref(), Some("4768151"));
+        assert!(import.songs[0].arrangement_matched);
+        assert_eq!(import.songs[1].matched, None);
+    }
+
+    #[test]
+    fn rejects_exports_without_items() {
+        assert!(convert(&json!({"data": []}), &library()).is_none());
+    }
+}
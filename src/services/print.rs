@@ -0,0 +1,394 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::songs::{
+    chordpro::section_for_name,
+    chords::{self, ChordAnnotation},
+    models::{Song, SongLibrary},
+    slides::is_split_marker,
+};
+
+use super::models::{ServiceData, ServiceItem, ServiceItemType};
+
+/// Print format
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrintFormat {
+    /// Print-ready HTML page
+    #[default]
+    Html,
+    /// Plain text
+    Text,
+}
+
+/// What to print
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct PrintOptions {
+    #[serde(default)]
+    pub format: PrintFormat,
+    /// Whether to print chords above the lyrics
+    #[serde(default)]
+    pub chords: bool,
+    /// Whether to print the lyrics of the songs after the order of service
+    #[serde(default = "default_true")]
+    pub lyrics: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Line of lyrics, with the chords above it
+struct LyricLine {
+    chords: Option<String>,
+    text: String,
+}
+
+/// Verse of a song as printed
+struct PrintedVerse {
+    label: String,
+    lines: Vec<LyricLine>,
+}
+
+/// Song in a service as printed
+struct PrintedSong {
+    title: String,
+    /// Authors, copyright and CCLI number
+    credits: Option<String>,
+    /// `None` if the song isn't in the song library
+    verses: Option<Vec<PrintedVerse>>,
+}
+
+/// Renders an order of service (and the lyrics of its songs) for printing
+pub fn render(
+    data: &ServiceData,
+    date: Option<NaiveDate>,
+    library: &SongLibrary,
+    options: PrintOptions,
+) -> String {
+    let songs: Vec<PrintedSong> = if options.lyrics {
+        data.service_items
+            .iter()
+            .filter(|item| item.item_type == ServiceItemType::Song && item.enabled != Some(false))
+            .filter_map(|item| item.song.as_ref().map(|song| (item, song)))
+            .map(|(item, song)| PrintedSong {
+                title: item_title(item),
+                credits: library.get(&song.title).and_then(credits),
+                verses: library
+                    .get(&song.title)
+                    .map(|library_song| printed_verses(item, library_song, options.chords)),
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    match options.format {
+        PrintFormat::Html => render_html(data, date, &songs),
+        PrintFormat::Text => render_text(data, date, &songs),
+    }
+}
+
+/// Title of the service, or a generic title if it has none
+fn service_title(data: &ServiceData) -> &str {
+    data.title
+        .as_deref()
+        .filter(|title| !title.trim().is_empty())
+        .unwrap_or("Order of Service")
+}
+
+/// Name of an item type
+fn item_type_name(item: &ServiceItem) -> &'static str {
+    match item.item_type {
+        ServiceItemType::Empty => "Empty",
+        ServiceItemType::Song => "Song",
+        ServiceItemType::MainText => "Main text",
+        ServiceItemType::SubText => "Sub text",
+        ServiceItemType::SmallText => "Small text",
+    }
+}
+
+/// Title of an item in the order of service: the song title (or the title shown for it), or the first line of the text
+fn item_title(item: &ServiceItem) -> String {
+    let text = item
+        .text
+        .as_deref()
+        .and_then(|text| text.lines().find(|line| !line.trim().is_empty()))
+        .map(str::trim);
+    match (item.item_type, &item.song) {
+        // songs can be shown with a different title in a service
+        (ServiceItemType::Song, Some(song)) => match text {
+            Some(text) if text != song.title => format!("{} ({})", text, song.title),
+            _ => song.title.clone(),
+        },
+        _ => String::from(text.unwrap_or_default()),
+    }
+}
+
+/// Authors, copyright and CCLI number of a song
+fn credits(song: &Song) -> Option<String> {
+    let attribution = song
+        .attribution
+        .clone()
+        .filter(|attribution| !attribution.trim().is_empty())
+        .or_else(|| song.default_attribution());
+    let ccli = song
+        .ccli_number
+        .as_ref()
+        .map(|number| format!("CCLI {}", number));
+    let credits: Vec<_> = attribution.into_iter().chain(ccli).collect();
+    (!credits.is_empty()).then(|| credits.join(" · "))
+}
+
+/// Gets the verses of a song in a service in the order they are sung:
+/// the verses of the chosen arrangement, or the chosen verses in the song's verse order
+fn verse_names(item: &ServiceItem, song: &Song) -> Vec<String> {
+    let service_song = item.song.as_ref();
+    if let Some(arrangement) = service_song
+        .and_then(|service_song| service_song.arrangement.as_ref())
+        .and_then(|name| song.arrangements.get(name))
+    {
+        return arrangement.clone();
+    }
+
+    let chosen = service_song.map_or(&[][..], |service_song| &service_song.verses);
+    song.arrangement(None)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| chosen.is_empty() || chosen.contains(name))
+        .collect()
+}
+
+/// Gets the verses of a song in a service with their lines (and chords if requested)
+fn printed_verses(item: &ServiceItem, song: &Song, with_chords: bool) -> Vec<PrintedVerse> {
+    verse_names(item, song)
+        .into_iter()
+        .filter_map(|name| {
+            let text = song.verses.get(&name)?;
+            let annotations = song.chords.get(&name).map_or(&[][..], Vec::as_slice);
+            Some(PrintedVerse {
+                label: section_for_name(&name).1,
+                lines: lyric_lines(text, annotations, with_chords),
+            })
+        })
+        .collect()
+}
+
+/// Splits verse text into lines without inline chords or slide split markers,
+/// with the chords (inline and annotated) written above each line if requested
+fn lyric_lines(text: &str, annotations: &[ChordAnnotation], with_chords: bool) -> Vec<LyricLine> {
+    let (plain, mut all_annotations) = chords::extract(text);
+    all_annotations.extend(annotations.iter().cloned());
+
+    plain
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !is_split_marker(line))
+        .map(|(line_index, line)| {
+            let chords = with_chords
+                .then(|| {
+                    let mut line_annotations: Vec<_> = all_annotations
+                        .iter()
+                        .filter(|annotation| annotation.line == line_index)
+                        .collect();
+                    line_annotations.sort_by_key(|annotation| annotation.position);
+                    chord_line(&line_annotations)
+                })
+                .flatten();
+            LyricLine {
+                chords,
+                text: String::from(line.trim_end()),
+            }
+        })
+        .collect()
+}
+
+/// Writes chords at their positions, moving chords right if they would run into the previous chord
+fn chord_line(annotations: &[&ChordAnnotation]) -> Option<String> {
+    if annotations.is_empty() {
+        return None;
+    }
+
+    let mut line = String::new();
+    let mut length = 0;
+    for annotation in annotations {
+        let position = if length == 0 {
+            annotation.position
+        } else {
+            annotation.position.max(length + 1)
+        };
+        line.extend(std::iter::repeat_n(' ', position - length));
+        line.push_str(&annotation.chord);
+        length = position + annotation.chord.chars().count();
+    }
+    Some(line)
+}
+
+/// Renders an order of service as plain text
+fn render_text(data: &ServiceData, date: Option<NaiveDate>, songs: &[PrintedSong]) -> String {
+    let mut text = String::new();
+    let heading = |text: &mut String, title: &str, underline: char| {
+        text.push_str(title);
+        text.push('\n');
+        text.extend(std::iter::repeat_n(underline, title.chars().count()));
+        text.push_str("\n\n");
+    };
+
+    heading(&mut text, service_title(data), '=');
+    if let Some(date) = date {
+        text.push_str(&format!("{}\n\n", date.format("%A %-d %B %Y")));
+    }
+    if let Some(description) = data.description.as_ref().filter(|d| !d.trim().is_empty()) {
+        text.push_str(description.trim_end());
+        text.push_str("\n\n");
+    }
+
+    heading(&mut text, "Order of Service", '-');
+    for (index, item) in data.service_items.iter().enumerate() {
+        text.push_str(&format!("{:>2}. {}", index + 1, item_type_name(item)));
+        let title = item_title(item);
+        if !title.is_empty() {
+            text.push_str(&format!(": {}", title));
+        }
+        if let Some(comment) = item.comment.as_ref().filter(|c| !c.trim().is_empty()) {
+            text.push_str(&format!(" ({})", comment.trim()));
+        }
+        if item.enabled == Some(false) {
+            text.push_str(" [skipped]");
+        }
+        text.push('\n');
+    }
+
+    for song in songs {
+        text.push('\n');
+        heading(&mut text, &song.title, '-');
+        if let Some(credits) = &song.credits {
+            text.push_str(&format!("{}\n\n", credits));
+        }
+        let Some(verses) = &song.verses else {
+            text.push_str("(Not in the song library)\n");
+            continue;
+        };
+        for verse in verses {
+            text.push_str(&format!("[{}]\n", verse.label));
+            for line in &verse.lines {
+                if let Some(chords) = &line.chords {
+                    text.push_str(chords);
+                    text.push('\n');
+                }
+                text.push_str(&line.text);
+                text.push('\n');
+            }
+            text.push('\n');
+        }
+    }
+
+    text
+}
+
+/// Renders an order of service as an HTML page styled for printing
+fn render_html(data: &ServiceData, date: Option<NaiveDate>, songs: &[PrintedSong]) -> String {
+    let title = escape(service_title(data));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        title, PRINT_STYLE
+    );
+
+    html.push_str(&format!("<h1>{}</h1>\n", title));
+    if let Some(date) = date {
+        html.push_str(&format!(
+            "<p class=\"date\">{}</p>\n",
+            date.format("%A %-d %B %Y")
+        ));
+    }
+    if let Some(description) = data.description.as_ref().filter(|d| !d.trim().is_empty()) {
+        html.push_str(&format!(
+            "<p class=\"description\">{}</p>\n",
+            escape(description.trim_end())
+        ));
+    }
+
+    html.push_str("<h2>Order of Service</h2>\n<ol class=\"items\">\n");
+    for item in &data.service_items {
+        let class = if item.enabled == Some(false) {
+            " class=\"skipped\""
+        } else {
+            ""
+        };
+        html.push_str(&format!(
+            "<li{}><span class=\"type\">{}</span> {}",
+            class,
+            item_type_name(item),
+            escape(&item_title(item))
+        ));
+        if let Some(comment) = item.comment.as_ref().filter(|c| !c.trim().is_empty()) {
+            html.push_str(&format!(
+                " <span class=\"comment\">{}</span>",
+                escape(comment.trim())
+            ));
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ol>\n");
+
+    for song in songs {
+        html.push_str(&format!(
+            "<section class=\"song\">\n<h2>{}</h2>\n",
+            escape(&song.title)
+        ));
+        if let Some(credits) = &song.credits {
+            html.push_str(&format!("<p class=\"credits\">{}</p>\n", escape(credits)));
+        }
+        let Some(verses) = &song.verses else {
+            html.push_str("<p class=\"missing\">Not in the song library</p>\n</section>\n");
+            continue;
+        };
+        for verse in verses {
+            html.push_str(&format!(
+                "<div class=\"verse\">\n<h3>{}</h3>\n<pre>",
+                escape(&verse.label)
+            ));
+            for line in &verse.lines {
+                if let Some(chords) = &line.chords {
+                    html.push_str(&format!(
+                        "<span class=\"chords\">{}</span>\n",
+                        escape(chords)
+                    ));
+                }
+                html.push_str(&escape(&line.text));
+                html.push('\n');
+            }
+            html.push_str("</pre>\n</div>\n");
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Styles of the printed HTML page
+const PRINT_STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+h1 { margin-bottom: 0.2em; }
+.date, .credits { color: #555; margin-top: 0; }
+.description { white-space: pre-wrap; }
+.items .type { color: #555; font-size: 0.8em; text-transform: uppercase; }
+.items .comment { font-style: italic; }
+.items .skipped { color: #999; text-decoration: line-through; }
+.song { break-before: page; }
+.verse { break-inside: avoid; }
+.verse h3 { font-size: 1em; margin-bottom: 0.2em; }
+.verse pre { font-family: monospace; font-size: 1.1em; margin-top: 0; }
+.chords { font-weight: bold; }
+.missing { color: #a00; }
+@media print { body { margin: 0; } }
+";
+
+/// Escapes text for use in HTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
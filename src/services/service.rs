@@ -0,0 +1,328 @@
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::{NaiveDate, Utc};
+use rusqlite::{params, types::Type, OptionalExtension, Row, TransactionBehavior};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::{database::Database, helpers::errors::GenericError};
+
+use super::models::{ServiceData, ServiceOperation, ServiceSummary, ServiceUpdate, StoredService};
+
+/// Number of service updates kept for clients that are slow to receive them
+const UPDATE_CHANNEL_CAPACITY: usize = 64;
+
+/// Stores services (orders of worship) so that every operator can see and edit them
+pub struct ServicePlanService {
+    database: Database,
+    /// Sends changes to services to everyone editing services
+    pub updates: broadcast::Sender<ServiceUpdate>,
+    /// Held while changing services, so that changes are sent in the order they were made
+    write_lock: Mutex<()>,
+}
+
+impl ServicePlanService {
+    pub fn new(database: Database) -> Self {
+        let (updates, _) = broadcast::channel(UPDATE_CHANNEL_CAPACITY);
+        Self {
+            database,
+            updates,
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// Lists stored services between two dates (inclusive), newest first.
+    /// Services without a date are listed last.
+    pub fn list(
+        &self,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Vec<ServiceSummary>, GenericError> {
+        let conn = self.database.get()?;
+        let mut statement = conn
+            .prepare_cached(
+                "
+                SELECT id, title, service_date, revision, updated_at
+                FROM services
+                WHERE (?1 IS NULL OR service_date >= ?1) AND (?2 IS NULL OR service_date <= ?2)
+                ORDER BY service_date IS NULL, service_date DESC, updated_at DESC
+                ",
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        statement
+            .query_map(params![from, to], |row| {
+                Ok(ServiceSummary {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    date: row.get(2)?,
+                    revision: row.get(3)?,
+                    updated_at: row.get(4)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Gets a stored service
+    pub fn get(&self, id: &str) -> Result<StoredService, GenericError> {
+        let conn = self.database.get()?;
+        conn.query_row(
+            "
+            SELECT id, service_date, revision, created_at, updated_at, data
+            FROM services
+            WHERE id = ?1
+            ",
+            params![id],
+            read_service,
+        )
+        .optional()
+        .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?
+        .ok_or(GenericError::NOT_FOUND)
+    }
+
+    /// Stores a new service
+    pub fn create(
+        &self,
+        date: Option<NaiveDate>,
+        mut data: ServiceData,
+        modified_by: Option<String>,
+    ) -> Result<StoredService, GenericError> {
+        data.assign_item_ids();
+        let now = Utc::now();
+        let service = StoredService {
+            id: Uuid::new_v4().to_string(),
+            date,
+            revision: 1,
+            created_at: now,
+            updated_at: now,
+            data,
+        };
+
+        let _write_lock = self.write_lock()?;
+        let conn = self.database.get()?;
+        conn.execute(
+            "
+            INSERT INTO services (id, title, service_date, data, revision, created_at, updated_at, modified_by)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ",
+            params![
+                service.id,
+                service.data.title,
+                service.date,
+                serialize_data(&service.data)?,
+                service.revision,
+                service.created_at,
+                service.updated_at,
+                modified_by,
+            ],
+        )
+        .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        self.notify(ServiceUpdate::Saved {
+            service: service.clone(),
+        });
+        Ok(service)
+    }
+
+    /// Replaces a stored service.
+    /// If a revision is specified, the service is only updated if it hasn't been changed since that revision.
+    pub fn update(
+        &self,
+        id: &str,
+        date: Option<NaiveDate>,
+        mut data: ServiceData,
+        expected_revision: Option<u64>,
+        modified_by: Option<String>,
+    ) -> Result<StoredService, GenericError> {
+        data.assign_item_ids();
+        let _write_lock = self.write_lock()?;
+        let mut conn = self.database.get()?;
+        let transaction = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let existing = transaction
+            .query_row(
+                "
+                SELECT id, service_date, revision, created_at, updated_at, data
+                FROM services
+                WHERE id = ?1
+                ",
+                params![id],
+                read_service,
+            )
+            .optional()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?
+            .ok_or(GenericError::NOT_FOUND)?;
+        if expected_revision.is_some_and(|revision| revision != existing.revision) {
+            return Err(GenericError::PRECONDITION_FAILED);
+        }
+
+        let service = StoredService {
+            date,
+            revision: existing.revision + 1,
+            updated_at: Utc::now(),
+            data,
+            ..existing
+        };
+        transaction
+            .execute(
+                "
+                UPDATE services
+                SET title = ?2, service_date = ?3, data = ?4, revision = ?5, updated_at = ?6, modified_by = ?7
+                WHERE id = ?1
+                ",
+                params![
+                    service.id,
+                    service.data.title,
+                    service.date,
+                    serialize_data(&service.data)?,
+                    service.revision,
+                    service.updated_at,
+                    modified_by,
+                ],
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        transaction
+            .commit()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        self.notify(ServiceUpdate::Saved {
+            service: service.clone(),
+        });
+        Ok(service)
+    }
+
+    /// Deletes a stored service.
+    /// If a revision is specified, the service is only deleted if it hasn't been changed since that revision.
+    pub fn delete(&self, id: &str, expected_revision: Option<u64>) -> Result<(), GenericError> {
+        let _write_lock = self.write_lock()?;
+        let conn = self.database.get()?;
+        let revision: u64 = conn
+            .query_row(
+                "SELECT revision FROM services WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?
+            .ok_or(GenericError::NOT_FOUND)?;
+        if expected_revision.is_some_and(|expected| expected != revision) {
+            return Err(GenericError::PRECONDITION_FAILED);
+        }
+
+        let deleted = conn
+            .execute(
+                "DELETE FROM services WHERE id = ?1 AND revision = ?2",
+                params![id, revision],
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        if deleted == 0 {
+            // changed since the revision was read
+            return Err(GenericError::PRECONDITION_FAILED);
+        }
+
+        self.notify(ServiceUpdate::Deleted {
+            id: String::from(id),
+        });
+        Ok(())
+    }
+
+    /// Applies an operation to a stored service, returning the new revision,
+    /// or `None` if the operation couldn't be applied (e.g. the item was deleted by someone else).
+    /// Operations are applied (and sent to everyone editing services) one at a time, in the order they arrive.
+    pub fn apply_operation(
+        &self,
+        id: &str,
+        op_id: String,
+        operation: ServiceOperation,
+        modified_by: Option<String>,
+    ) -> Result<Option<u64>, GenericError> {
+        let _write_lock = self.write_lock()?;
+        let mut conn = self.database.get()?;
+        let transaction = conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        let mut service = transaction
+            .query_row(
+                "
+                SELECT id, service_date, revision, created_at, updated_at, data
+                FROM services
+                WHERE id = ?1
+                ",
+                params![id],
+                read_service,
+            )
+            .optional()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?
+            .ok_or(GenericError::NOT_FOUND)?;
+        if !service.data.apply(&operation) {
+            return Ok(None);
+        }
+
+        service.revision += 1;
+        service.updated_at = Utc::now();
+        transaction
+            .execute(
+                "
+                UPDATE services
+                SET title = ?2, data = ?3, revision = ?4, updated_at = ?5, modified_by = ?6
+                WHERE id = ?1
+                ",
+                params![
+                    service.id,
+                    service.data.title,
+                    serialize_data(&service.data)?,
+                    service.revision,
+                    service.updated_at,
+                    modified_by,
+                ],
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        transaction
+            .commit()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        self.notify(ServiceUpdate::Operation {
+            id: service.id,
+            revision: service.revision,
+            op_id,
+            operation,
+        });
+        Ok(Some(service.revision))
+    }
+
+    /// Locks services for writing
+    fn write_lock(&self) -> Result<MutexGuard<'_, ()>, GenericError> {
+        self.write_lock
+            .lock()
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Sends a change to everyone editing services (nobody may be listening)
+    fn notify(&self, update: ServiceUpdate) {
+        let _ = self.updates.send(update);
+    }
+}
+
+/// Serializes service data for storage
+fn serialize_data(data: &ServiceData) -> Result<String, GenericError> {
+    serde_json::to_string(data).map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+}
+
+/// Reads a stored service from a row of `id, service_date, revision, created_at, updated_at, data`
+fn read_service(row: &Row) -> rusqlite::Result<StoredService> {
+    let data: String = row.get(5)?;
+    Ok(StoredService {
+        id: row.get(0)?,
+        date: row.get(1)?,
+        revision: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        data: serde_json::from_str(&data).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(5, Type::Text, Box::new(err))
+        })?,
+    })
+}
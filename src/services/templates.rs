@@ -0,0 +1,395 @@
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use rusqlite::{params, types::Type, OptionalExtension, Row};
+use uuid::Uuid;
+
+use crate::{database::Database, helpers::errors::GenericError};
+
+use super::models::{ServiceData, ServiceTemplate, TemplateSummary};
+
+/// Placeholder filled in with the service date when no value is given
+pub const DATE_PLACEHOLDER: &str = "date";
+
+/// Stores service templates, which new services can be created from
+pub struct ServiceTemplateService {
+    database: Database,
+}
+
+impl ServiceTemplateService {
+    pub fn new(database: Database) -> Self {
+        Self { database }
+    }
+
+    /// Lists stored templates by name
+    pub fn list(&self) -> Result<Vec<TemplateSummary>, GenericError> {
+        let conn = self.database.get()?;
+        let mut statement = conn
+            .prepare_cached(
+                "
+                SELECT id, name, created_at, updated_at, data
+                FROM service_templates
+                ORDER BY name COLLATE NOCASE
+                ",
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        statement
+            .query_map([], read_template)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+            .map(|templates| {
+                templates
+                    .into_iter()
+                    .map(|template| TemplateSummary {
+                        placeholders: placeholders(&template.data),
+                        id: template.id,
+                        name: template.name,
+                        updated_at: template.updated_at,
+                    })
+                    .collect()
+            })
+    }
+
+    /// Gets a stored template
+    pub fn get(&self, id: &str) -> Result<ServiceTemplate, GenericError> {
+        let conn = self.database.get()?;
+        conn.query_row(
+            "
+            SELECT id, name, created_at, updated_at, data
+            FROM service_templates
+            WHERE id = ?1
+            ",
+            params![id],
+            read_template,
+        )
+        .optional()
+        .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?
+        .ok_or(GenericError::NOT_FOUND)
+    }
+
+    /// Stores a new template
+    pub fn create(&self, name: String, data: ServiceData) -> Result<ServiceTemplate, GenericError> {
+        let name = template_name(name)?;
+        let now = Utc::now();
+        let template = ServiceTemplate {
+            id: Uuid::new_v4().to_string(),
+            name,
+            created_at: now,
+            updated_at: now,
+            data: without_item_ids(data),
+        };
+
+        let conn = self.database.get()?;
+        conn.execute(
+            "
+            INSERT INTO service_templates (id, name, data, created_at, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ",
+            params![
+                template.id,
+                template.name,
+                serialize_data(&template.data)?,
+                template.created_at,
+                template.updated_at,
+            ],
+        )
+        .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        Ok(template)
+    }
+
+    /// Replaces a stored template
+    pub fn update(
+        &self,
+        id: &str,
+        name: String,
+        data: ServiceData,
+    ) -> Result<ServiceTemplate, GenericError> {
+        let template = ServiceTemplate {
+            name: template_name(name)?,
+            updated_at: Utc::now(),
+            data: without_item_ids(data),
+            ..self.get(id)?
+        };
+
+        let conn = self.database.get()?;
+        let updated = conn
+            .execute(
+                "
+                UPDATE service_templates
+                SET name = ?2, data = ?3, updated_at = ?4
+                WHERE id = ?1
+                ",
+                params![
+                    template.id,
+                    template.name,
+                    serialize_data(&template.data)?,
+                    template.updated_at,
+                ],
+            )
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        if updated == 0 {
+            return Err(GenericError::NOT_FOUND);
+        }
+
+        Ok(template)
+    }
+
+    /// Deletes a stored template
+    pub fn delete(&self, id: &str) -> Result<(), GenericError> {
+        let conn = self.database.get()?;
+        let deleted = conn
+            .execute("DELETE FROM service_templates WHERE id = ?1", params![id])
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        if deleted == 0 {
+            return Err(GenericError::NOT_FOUND);
+        }
+        Ok(())
+    }
+}
+
+/// Checks a template name, which can't be empty
+fn template_name(name: String) -> Result<String, GenericError> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() {
+        return Err(GenericError::BAD_REQUEST);
+    }
+    Ok(String::from(trimmed))
+}
+
+/// Removes the item IDs from a service, so that every service created from a template gets new IDs
+fn without_item_ids(mut data: ServiceData) -> ServiceData {
+    for item in &mut data.service_items {
+        item.id = None;
+    }
+    data
+}
+
+/// Gets the names of the placeholders (e.g. `{{speaker}}`) in a service, in the order they first appear
+pub fn placeholders(data: &ServiceData) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for text in service_texts(data) {
+        for name in placeholder_names(text) {
+            if !names.iter().any(|existing| existing == name) {
+                names.push(String::from(name));
+            }
+        }
+    }
+    names
+}
+
+/// Fills in the placeholders in a service.
+/// Placeholders without a value are left as they are, so that they can be filled in by hand.
+pub fn fill_placeholders(mut data: ServiceData, values: &BTreeMap<String, String>) -> ServiceData {
+    let fill = |text: &mut Option<String>| {
+        if let Some(text) = text {
+            *text = fill_text(text, values);
+        }
+    };
+
+    fill(&mut data.title);
+    fill(&mut data.description);
+    for item in &mut data.service_items {
+        fill(&mut item.text);
+        fill(&mut item.comment);
+    }
+    data
+}
+
+/// Texts in a service that can contain placeholders
+fn service_texts(data: &ServiceData) -> impl Iterator<Item = &str> {
+    [&data.title, &data.description]
+        .into_iter()
+        .chain(
+            data.service_items
+                .iter()
+                .flat_map(|item| [&item.text, &item.comment]),
+        )
+        .filter_map(|text| text.as_deref())
+}
+
+/// Finds the placeholders in a text, returning the position and name of each one
+fn find_placeholders(text: &str) -> Vec<(std::ops::Range<usize>, &str)> {
+    let mut found = Vec::new();
+    let mut position = 0;
+    while let Some(start) = text[position..].find("{{").map(|start| position + start) {
+        let Some(end) = text[start + 2..].find("}}").map(|end| start + 2 + end) else {
+            break;
+        };
+        let name = text[start + 2..end].trim();
+        if is_placeholder_name(name) {
+            found.push((start..end + 2, name));
+            position = end + 2;
+        } else {
+            position = start + 2;
+        }
+    }
+    found
+}
+
+/// Gets the names of the placeholders in a text
+fn placeholder_names(text: &str) -> impl Iterator<Item = &str> {
+    find_placeholders(text).into_iter().map(|(_, name)| name)
+}
+
+/// Whether a name is a placeholder name: letters, numbers, `_` and `-` (e.g. `sermon_title`)
+fn is_placeholder_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Fills in the placeholders in a text
+fn fill_text(text: &str, values: &BTreeMap<String, String>) -> String {
+    let mut filled = String::with_capacity(text.len());
+    let mut position = 0;
+    for (range, name) in find_placeholders(text) {
+        if let Some(value) = values.get(name) {
+            filled.push_str(&text[position..range.start]);
+            filled.push_str(value);
+            position = range.end;
+        }
+    }
+    filled.push_str(&text[position..]);
+    filled
+}
+
+/// Serializes template data for storage
+fn serialize_data(data: &ServiceData) -> Result<String, GenericError> {
+    serde_json::to_string(data).map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+}
+
+/// Reads a stored template from a row of `id, name, created_at, updated_at, data`
+fn read_template(row: &Row) -> rusqlite::Result<ServiceTemplate> {
+    let data: String = row.get(4)?;
+    Ok(ServiceTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        data: serde_json::from_str(&data).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(4, Type::Text, Box::new(err))
+        })?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Map;
+
+    use super::*;
+    use crate::services::models::{ServiceItem, ServiceItemType};
+
+    fn text_item(text: &str, comment: Option<&str>) -> ServiceItem {
+        ServiceItem {
+            id: None,
+            item_type: ServiceItemType::MainText,
+            song: None,
+            text: Some(String::from(text)),
+            comment: comment.map(String::from),
+            enabled: None,
+            extra: Map::new(),
+        }
+    }
+
+    fn values(values: &[(&str, &str)]) -> BTreeMap<String, String> {
+        values
+            .iter()
+            .map(|(name, value)| (String::from(*name), String::from(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn finds_placeholders() {
+        assert_eq!(
+            find_placeholders("Speaker: {{speaker}}, {{ sermon_title }}"),
+            [(9..20, "speaker"), (22..40, "sermon_title")]
+        );
+        assert_eq!(
+            find_placeholders("{{week-1}} {{2}} {{Prédicateur}}"),
+            [(0..10, "week-1"), (11..16, "2"), (17..33, "Prédicateur")]
+        );
+    }
+
+    #[test]
+    fn skips_unterminated_placeholders() {
+        assert!(find_placeholders("Speaker: {{speaker").is_empty());
+        assert!(find_placeholders("{{ }}").is_empty());
+        assert_eq!(
+            find_placeholders("{{speaker}} and {{reader"),
+            [(0..11, "speaker")]
+        );
+        assert_eq!(find_placeholders("{{{{speaker}}"), [(2..13, "speaker")]);
+    }
+
+    #[test]
+    fn skips_invalid_placeholder_names() {
+        assert!(find_placeholders("{{}}").is_empty());
+        assert!(find_placeholders("{{sermon title}}").is_empty());
+        assert!(find_placeholders("{{speaker!}} {{a.b}} {{{x}}}").is_empty());
+        assert_eq!(
+            find_placeholders("{{not valid}} {{valid}}"),
+            [(14..23, "valid")]
+        );
+    }
+
+    #[test]
+    fn lists_placeholders_once_in_order() {
+        let data = ServiceData {
+            title: Some(String::from("{{date}} {{speaker}}")),
+            description: Some(String::from("{{speaker}} on {{topic}}")),
+            service_items: vec![
+                text_item("{{reader}} reads {{passage}}", Some("{{topic}}")),
+                text_item("{{reader}}", None),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            placeholders(&data),
+            ["date", "speaker", "topic", "reader", "passage"]
+        );
+    }
+
+    #[test]
+    fn fills_placeholders() {
+        let values = values(&[("speaker", "Sam"), ("date", "2024-03-10")]);
+        assert_eq!(
+            fill_text("{{date}}: {{ speaker }} and {{speaker}}", &values),
+            "2024-03-10: Sam and Sam"
+        );
+
+        // missing values, invalid names and unterminated placeholders are left as they are
+        assert_eq!(
+            fill_text("{{speaker}} with {{reader}}", &values),
+            "Sam with {{reader}}"
+        );
+        assert_eq!(
+            fill_text("{{sermon title}} {{speaker", &values),
+            "{{sermon title}} {{speaker"
+        );
+        assert_eq!(fill_text("No placeholders", &values), "No placeholders");
+    }
+
+    #[test]
+    fn fills_placeholders_in_a_service() {
+        let data = ServiceData {
+            title: Some(String::from("Service with {{speaker}}")),
+            description: None,
+            service_items: vec![text_item("{{passage}}", Some("Reading by {{reader}}"))],
+            ..Default::default()
+        };
+        let filled = fill_placeholders(
+            data,
+            &values(&[("speaker", "Sam"), ("passage", "Psalm 23")]),
+        );
+        assert_eq!(filled.title.as_deref(), Some("Service with Sam"));
+        assert_eq!(filled.description, None);
+        assert_eq!(filled.service_items[0].text.as_deref(), Some("Psalm 23"));
+        assert_eq!(
+            filled.service_items[0].comment.as_deref(),
+            Some("Reading by {{reader}}")
+        );
+    }
+}
@@ -0,0 +1,592 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppServices, config::file::AppConfig};
+
+use super::{
+    chordpro,
+    chords::Notation,
+    duplicates::{self, DEFAULT_SIMILARITY_THRESHOLD},
+    export::{self, export_filename},
+    import::{self, ConflictStrategy, ImportParser, ImportReport},
+    models::{SortedSongLibrary, TitledSong},
+    openlp, openlyrics,
+    service::etag,
+    slides::{self, SlideLimits, VerseSlides},
+    songselect,
+    transpose::{self, TransposeOptions},
+    validation::{validate_arrangement, validate_song},
+};
+
+/// Song routes
+/// (single songs are under `/titles` so that song titles can't clash with the other routes)
+pub fn route(config: &AppConfig) -> Router<Arc<AppServices>> {
+    // imported files are limited to the maximum content file size
+    let max_file_size = usize::try_from(config.content_max_file_size).unwrap_or(usize::MAX);
+
+    Router::new()
+        .route("/", get(list_songs))
+        .route("/", post(create_song))
+        .route("/export", get(export_songs))
+        .route("/migrate", post(migrate_songs))
+        .route("/duplicates", get(find_duplicates))
+        .route("/lint", get(lint_songs))
+        .route("/merge", post(merge_songs))
+        .route(
+            "/import/openlyrics",
+            post(import_openlyrics).layer(DefaultBodyLimit::max(max_file_size)),
+        )
+        .route(
+            "/import/chordpro",
+            post(import_chordpro).layer(DefaultBodyLimit::max(max_file_size)),
+        )
+        .route(
+            "/import/songselect",
+            post(import_songselect).layer(DefaultBodyLimit::max(max_file_size)),
+        )
+        .route(
+            "/import/openlp",
+            post(import_openlp).layer(DefaultBodyLimit::max(max_file_size)),
+        )
+        .route("/titles/:id", get(get_song))
+        .route("/titles/:id", put(update_song))
+        .route("/titles/:id", delete(delete_song))
+        .route("/titles/:id/arrangements", get(list_arrangements))
+        .route("/titles/:id/arrangements/:name", put(set_arrangement))
+        .route("/titles/:id/arrangements/:name", delete(delete_arrangement))
+        .route("/titles/:id/slides", get(song_slides))
+        .route("/titles/:id/transpose", get(transpose_song))
+}
+
+/// Gets the entity tag from the `If-Match` header
+fn if_match(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim())
+}
+
+/// Lists all songs in the song library
+pub async fn list_songs(State(state): State<Arc<AppServices>>) -> impl IntoResponse {
+    match state.song_service.load(&state.content_service) {
+        Ok(library) => Json(SortedSongLibrary(&library)).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Gets a single song, with its entity tag in the `ETag` header
+pub async fn get_song(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.song_service.get(&state.content_service, &id) {
+        Ok(song) => ([(header::ETAG, etag(&song))], Json(song)).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Arrangements of a song's verses
+#[derive(Serialize, Deserialize)]
+pub struct SongArrangements {
+    /// Verse names in the default order (the verse order, or all verses sorted by name)
+    pub default: Vec<String>,
+    /// Named arrangements, mapping arrangement names to verse names
+    pub arrangements: BTreeMap<String, Vec<String>>,
+}
+
+/// Lists the arrangements of a song's verses
+pub async fn list_arrangements(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    match state.song_service.get(&state.content_service, &id) {
+        Ok(song) => Json(SongArrangements {
+            default: song.arrangement(None).unwrap_or_default(),
+            arrangements: song.arrangements,
+        })
+        .into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Adds or replaces a named arrangement of a song's verses (a list of verse names)
+pub async fn set_arrangement(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path((id, name)): Path<(String, String)>,
+    Json(verses): Json<Vec<String>>,
+) -> impl IntoResponse {
+    let song = match state.song_service.get(&state.content_service, &id) {
+        Ok(song) => song,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+    let problems = validate_arrangement(&id, &song, &name, &verses);
+    if !problems.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(problems)).into_response();
+    }
+
+    match state.song_service.set_arrangement(
+        &state.content_service,
+        &id,
+        &name,
+        Some(verses),
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Removes a named arrangement from a song
+pub async fn delete_arrangement(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path((id, name)): Path<(String, String)>,
+) -> impl IntoResponse {
+    match state.song_service.set_arrangement(
+        &state.content_service,
+        &id,
+        &name,
+        None,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Slides query
+#[derive(Serialize, Deserialize)]
+pub struct SlidesQuery {
+    /// Display to fit the slides to (uses `display_default` if not given)
+    pub display: Option<String>,
+    /// Only get the slides of this verse
+    pub verse: Option<String>,
+    /// Arrangement to get the verses in (uses the default verse order if not given)
+    pub arrangement: Option<String>,
+}
+
+/// Gets a song's verses split into slides that fit on a display.
+/// Verses are in the order of the arrangement, so repeated verses are included each time.
+pub async fn song_slides(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+    Query(query): Query<SlidesQuery>,
+) -> impl IntoResponse {
+    let song = match state.song_service.get(&state.content_service, &id) {
+        Ok(song) => song,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    let verses = match query.verse {
+        Some(verse) if song.verses.contains_key(&verse) => vec![verse],
+        Some(_) => return StatusCode::NOT_FOUND.into_response(),
+        None => match song.arrangement(query.arrangement.as_deref()) {
+            Some(verses) => verses,
+            None => return StatusCode::NOT_FOUND.into_response(),
+        },
+    };
+
+    let limits = SlideLimits::for_display(&state.config.client_options, query.display.as_deref());
+    let slides: Vec<_> = verses
+        .into_iter()
+        .filter_map(|verse| {
+            let text = song.verses.get(&verse)?;
+            Some(VerseSlides {
+                slides: slides::split_verse(text, limits),
+                verse,
+            })
+        })
+        .collect();
+
+    Json(slides).into_response()
+}
+
+/// Transpose query
+#[derive(Serialize, Deserialize)]
+pub struct TransposeQuery {
+    /// Key to play the song in
+    pub key: Option<String>,
+    /// Semitones to move the song up (or down) by, used if no key is given
+    pub semitones: Option<i32>,
+    /// Fret the capo is on
+    #[serde(default)]
+    pub capo: u8,
+    /// How to write the chords
+    pub notation: Option<Notation>,
+}
+
+/// Gets a song with its chords transposed to another key.
+/// The song in the song library is not changed.
+pub async fn transpose_song(
+    State(state): State<Arc<AppServices>>,
+    Path(id): Path<String>,
+    Query(query): Query<TransposeQuery>,
+) -> impl IntoResponse {
+    let song = match state.song_service.get(&state.content_service, &id) {
+        Ok(song) => song,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    match transpose::transpose(
+        song,
+        TransposeOptions {
+            key: query.key.as_deref(),
+            semitones: query.semitones,
+            capo: query.capo,
+            notation: query.notation,
+        },
+    ) {
+        Ok(song) => Json(song).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Adds a song to the song library
+pub async fn create_song(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Json(request): Json<TitledSong>,
+) -> impl IntoResponse {
+    let problems = validate_song(request.title.trim(), &request.song);
+    if !problems.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(problems)).into_response();
+    }
+    let issues = state
+        .song_service
+        .lint_before_save(request.title.trim(), &request.song);
+    if !issues.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(issues)).into_response();
+    }
+
+    let song_etag = etag(&request.song);
+    match state.song_service.create(
+        &state.content_service,
+        &request.title,
+        request.song,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(()) => (StatusCode::CREATED, [(header::ETAG, song_etag)]).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Replaces a song in the song library.
+/// The song is renamed if the title in the request is different.
+/// If the `If-Match` header is set, the song is only updated if it hasn't been changed by someone else.
+pub async fn update_song(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(request): Json<TitledSong>,
+) -> impl IntoResponse {
+    let problems = validate_song(request.title.trim(), &request.song);
+    if !problems.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(problems)).into_response();
+    }
+    let issues = state
+        .song_service
+        .lint_before_save(request.title.trim(), &request.song);
+    if !issues.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json(issues)).into_response();
+    }
+
+    let song_etag = etag(&request.song);
+    match state.song_service.update(
+        &state.content_service,
+        &id,
+        Some(&request.title),
+        request.song,
+        if_match(&headers),
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(()) => (StatusCode::NO_CONTENT, [(header::ETAG, song_etag)]).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Removes a song from the song library.
+/// If the `If-Match` header is set, the song is only removed if it hasn't been changed by someone else.
+pub async fn delete_song(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match state.song_service.delete(
+        &state.content_service,
+        &id,
+        if_match(&headers),
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Import query
+#[derive(Serialize, Deserialize)]
+pub struct ImportQuery {
+    /// How to handle songs with the same title as an existing song
+    #[serde(default)]
+    pub conflict: ConflictStrategy,
+    /// Only report what would change without changing the song library
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Imports songs from an OpenLyrics XML file or a zip file of OpenLyrics XML files.
+/// Returns a report of the imported songs and conflicts.
+pub async fn import_openlyrics(
+    state: State<Arc<AppServices>>,
+    remote_address: ConnectInfo<SocketAddr>,
+    query: Query<ImportQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    import_with(state, remote_address, query, body, openlyrics::import)
+}
+
+/// Imports songs from a ChordPro file or a zip file of ChordPro files.
+/// Returns a report of the imported songs and conflicts.
+pub async fn import_chordpro(
+    state: State<Arc<AppServices>>,
+    remote_address: ConnectInfo<SocketAddr>,
+    query: Query<ImportQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    import_with(state, remote_address, query, body, chordpro::import)
+}
+
+/// Imports songs from a CCLI SongSelect file (`.usr` or plain text lyrics) or a zip file of them.
+/// Returns a report of the imported songs and conflicts.
+pub async fn import_songselect(
+    state: State<Arc<AppServices>>,
+    remote_address: ConnectInfo<SocketAddr>,
+    query: Query<ImportQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    import_with(state, remote_address, query, body, songselect::import)
+}
+
+/// Imports songs from an OpenLP song database (`songs.sqlite`) or a zip file of them.
+/// Returns a report of the imported songs and conflicts.
+pub async fn import_openlp(
+    state: State<Arc<AppServices>>,
+    remote_address: ConnectInfo<SocketAddr>,
+    query: Query<ImportQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    import_with(state, remote_address, query, body, openlp::import)
+}
+
+/// Reads the uploaded file (or the files in an uploaded zip file) with the parser
+/// of the import format and merges the songs into the song library
+fn import_with(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+    parser: ImportParser,
+) -> Response {
+    if body.len() as u64 > state.config.content_max_file_size {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    }
+
+    let mut report = ImportReport::default();
+    let files = import::extract_files(
+        "upload",
+        body.to_vec(),
+        state.config.content_max_file_size,
+        &mut report,
+    );
+    let songs = parser(files, &mut report);
+
+    match state.song_service.import(
+        &state.content_service,
+        songs,
+        query.conflict,
+        report,
+        query.dry_run,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(report) => Json(report).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Query of changes that can be tried out first
+#[derive(Serialize, Deserialize)]
+pub struct DryRunQuery {
+    /// Only report what would change without changing the song library
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Migrates older songs in the song library to the current song format.
+/// Returns the migrated songs and any problems that need to be fixed by hand.
+pub async fn migrate_songs(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(query): Query<DryRunQuery>,
+) -> impl IntoResponse {
+    match state.song_service.migrate(
+        &state.content_service,
+        query.dry_run,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(report) => Json(report).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Lints the song library, listing problems like lines that are too long for the displays
+pub async fn lint_songs(State(state): State<Arc<AppServices>>) -> impl IntoResponse {
+    match state.song_service.lint(&state.content_service) {
+        Ok(issues) => Json(issues).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Duplicate search query
+#[derive(Serialize, Deserialize)]
+pub struct DuplicatesQuery {
+    /// Lyric similarity (0 to 1) above which songs are reported
+    pub threshold: Option<f64>,
+}
+
+/// Finds songs in the song library that may be duplicates (the same title or similar lyrics)
+pub async fn find_duplicates(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<DuplicatesQuery>,
+) -> impl IntoResponse {
+    let threshold = query.threshold.unwrap_or(DEFAULT_SIMILARITY_THRESHOLD);
+    if !(0.0..=1.0).contains(&threshold) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+
+    let library = match state.song_service.load(&state.content_service) {
+        Ok(library) => library,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    // comparing every pair of songs is slow for large libraries
+    match tokio::task::spawn_blocking(move || duplicates::find_duplicates(&library, threshold))
+        .await
+    {
+        Ok(duplicates) => Json(duplicates).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Song merge request
+#[derive(Serialize, Deserialize)]
+pub struct MergeRequest {
+    /// Title of the song to merge into (its title and fields are kept)
+    pub target: String,
+    /// Title of the song to merge, which is removed after merging
+    pub source: String,
+    /// Keep the merged song instead of removing it
+    #[serde(default)]
+    pub keep_source: bool,
+}
+
+/// Merges one song into another, combining their metadata and verses.
+/// Returns the merged song.
+pub async fn merge_songs(
+    State(state): State<Arc<AppServices>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    Query(query): Query<DryRunQuery>,
+    Json(request): Json<MergeRequest>,
+) -> impl IntoResponse {
+    match state.song_service.merge(
+        &state.content_service,
+        &request.target,
+        &request.source,
+        request.keep_source,
+        query.dry_run,
+        Some(remote_address.ip().to_string()),
+    ) {
+        Ok(song) => ([(header::ETAG, etag(&song))], Json(song)).into_response(),
+        Err(err) => err.to_status_code().into_response(),
+    }
+}
+
+/// Export query
+#[derive(Serialize, Deserialize)]
+pub struct ExportQuery {
+    /// Export format
+    pub format: ExportFormat,
+}
+
+/// Song export format
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// OpenLyrics 0.9 XML
+    Openlyrics,
+    /// ChordPro with inline chords
+    Chordpro,
+}
+
+/// Exports the song library as a zip file with one file per song
+pub async fn export_songs(
+    State(state): State<Arc<AppServices>>,
+    Query(query): Query<ExportQuery>,
+) -> impl IntoResponse {
+    let library = match state.song_service.load(&state.content_service) {
+        Ok(library) => library,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    let mut used_filenames = HashSet::new();
+    let files = library
+        .iter()
+        .map(|(title, song)| match query.format {
+            ExportFormat::Openlyrics => (
+                export_filename(title, "xml", &mut used_filenames),
+                openlyrics::write(title, song),
+            ),
+            ExportFormat::Chordpro => (
+                export_filename(title, "cho", &mut used_filenames),
+                chordpro::write(title, song),
+            ),
+        })
+        .collect();
+
+    let zip = match export::zip_files(files) {
+        Ok(zip) => zip,
+        Err(err) => return err.to_status_code().into_response(),
+    };
+
+    let filename = match query.format {
+        ExportFormat::Openlyrics => "songs-openlyrics.zip",
+        ExportFormat::Chordpro => "songs-chordpro.zip",
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, String::from("application/zip")),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        zip,
+    )
+        .into_response()
+}
@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+
+use crate::{content::encoding, helpers::sort::natcasecmp};
+
+use super::{
+    chords,
+    import::{
+        section_name, section_prefix, set_verse_order, ImportReport, ImportedSong, SECTION_LABELS,
+    },
+    models::Song,
+};
+
+/// Parses ChordPro files, recording any files that couldn't be parsed in the import report.
+/// Songs without a title directive are named after the file.
+pub fn import(files: Vec<(String, Vec<u8>)>, report: &mut ImportReport) -> Vec<ImportedSong> {
+    let mut songs = Vec::new();
+    for (source, bytes) in files {
+        let file_name = source.rsplit('/').next().unwrap_or(&source);
+        let fallback_title = file_name
+            .rsplit_once('.')
+            .map_or(file_name, |(stem, _)| stem);
+
+        match parse(&encoding::decode(&bytes).text, fallback_title) {
+            Ok((title, song)) => songs.push(ImportedSong {
+                source,
+                title,
+                song,
+            }),
+            Err(err) => report.error(&source, err),
+        }
+    }
+    songs
+}
+
+/// Section being read
+struct Section {
+    /// Verse name prefix
+    prefix: &'static str,
+    /// Label given in the start directive
+    label: Option<String>,
+    lines: Vec<String>,
+    /// Whether the section was started by a directive (and ends with one)
+    explicit: bool,
+}
+
+/// Song being read
+#[derive(Default)]
+struct Parser {
+    title: Option<String>,
+    song: Song,
+    order: Vec<String>,
+    counters: HashMap<&'static str, u32>,
+    last_chorus: Option<String>,
+    section: Option<Section>,
+    /// Whether the parser is inside a tab or grid section (not lyrics)
+    skipping: bool,
+}
+
+/// Parses a ChordPro document into a song title and song.
+///
+/// Sections (`{start_of_verse}`, `{start_of_chorus}`...) and blank-line separated
+/// paragraphs become verses, inline chords (`[G]`) become chord annotations and
+/// chorus recalls (`{chorus}`) and repeated sections become the verse order.
+pub fn parse(text: &str, fallback_title: &str) -> Result<(String, Song), String> {
+    let mut parser = Parser::default();
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+
+        if let Some(directive) = trimmed
+            .strip_prefix('{')
+            .and_then(|rest| rest.strip_suffix('}'))
+        {
+            parser.directive(directive);
+            continue;
+        }
+
+        if parser.skipping || trimmed.starts_with('#') {
+            continue;
+        }
+
+        match &mut parser.section {
+            Some(section) if section.explicit || !trimmed.is_empty() => {
+                section.lines.push(String::from(line.trim_end()));
+            }
+            Some(_) => parser.end_section(),
+            None if !trimmed.is_empty() => {
+                parser.section = Some(Section {
+                    prefix: "",
+                    label: None,
+                    lines: vec![String::from(line.trim_end())],
+                    explicit: false,
+                });
+            }
+            None => {}
+        }
+    }
+    parser.end_section();
+
+    let Parser {
+        title,
+        mut song,
+        order,
+        ..
+    } = parser;
+
+    if song.verses.is_empty() {
+        return Err(String::from("Song has no lyrics"));
+    }
+
+    set_verse_order(&mut song, order);
+
+    let title = title
+        .or_else(|| Some(String::from(fallback_title.trim())))
+        .filter(|title| !title.is_empty())
+        .ok_or_else(|| String::from("Song has no title"))?;
+
+    Ok((title, song))
+}
+
+impl Parser {
+    /// Handles a directive (the text between the braces)
+    fn directive(&mut self, directive: &str) {
+        let (name, value) = match directive.find(|c: char| c == ':' || c.is_whitespace()) {
+            Some(index) => (
+                &directive[..index],
+                directive[index + 1..].trim_start_matches(':').trim(),
+            ),
+            None => (directive, ""),
+        };
+        let name = name.trim().to_lowercase();
+        let value = String::from(value);
+
+        match name.as_str() {
+            "title" | "t" => match &self.title {
+                Some(_) => self.add_alternate_title(value),
+                None => self.title = Some(value).filter(|title| !title.is_empty()),
+            },
+            "subtitle" | "st" | "alttitle" => self.add_alternate_title(value),
+            "artist" | "composer" | "lyricist" | "arranger" => {
+                if !value.is_empty() && !self.song.authors.contains(&value) {
+                    self.song.authors.push(value);
+                }
+            }
+            "copyright" => self.song.copyright = Some(value).filter(|value| !value.is_empty()),
+            "ccli" => self.song.ccli_number = Some(value).filter(|value| !value.is_empty()),
+            "key" => self.song.key = Some(value).filter(|value| !value.is_empty()),
+            "tempo" => self.song.tempo = value.parse().ok(),
+            "meta" => {
+                if let Some((name, value)) = value.split_once(char::is_whitespace) {
+                    self.directive(&format!("{}: {}", name, value));
+                }
+            }
+            "start_of_tab" | "sot" | "start_of_grid" | "sog" => {
+                self.end_section();
+                self.skipping = true;
+            }
+            "end_of_tab" | "eot" | "end_of_grid" | "eog" => self.skipping = false,
+            "chorus" => {
+                self.end_section();
+                let label = parse_label(&value);
+                let name = label
+                    .as_deref()
+                    .and_then(|label| section_name("c", label))
+                    .filter(|name| self.song.verses.contains_key(name))
+                    .or_else(|| self.last_chorus.clone());
+                if let Some(name) = name {
+                    self.order.push(name);
+                }
+            }
+            _ => {
+                if let Some(kind) = name.strip_prefix("start_of_").or(match name.as_str() {
+                    "sov" => Some("verse"),
+                    "soc" => Some("chorus"),
+                    "sob" => Some("bridge"),
+                    _ => None,
+                }) {
+                    self.end_section();
+                    self.section = Some(Section {
+                        prefix: match kind {
+                            "chorus" => "c",
+                            "bridge" => "b",
+                            _ => "",
+                        },
+                        label: parse_label(&value),
+                        lines: Vec::new(),
+                        explicit: true,
+                    });
+                } else if name.starts_with("end_of_")
+                    || matches!(name.as_str(), "eov" | "eoc" | "eob")
+                {
+                    self.end_section();
+                }
+                // other directives (comments, formatting...) aren't part of the song
+            }
+        }
+    }
+
+    /// Adds an alternate title if it isn't already known
+    fn add_alternate_title(&mut self, title: String) {
+        if !title.is_empty()
+            && self.title.as_ref() != Some(&title)
+            && !self.song.alternate_titles.contains(&title)
+        {
+            self.song.alternate_titles.push(title);
+        }
+    }
+
+    /// Finishes the current section, adding it to the song as a verse
+    fn end_section(&mut self) {
+        let Some(section) = self.section.take() else {
+            return;
+        };
+
+        // remove leading and trailing blank lines (before extracting chords so that line numbers match)
+        let start = section
+            .lines
+            .iter()
+            .position(|line| !line.trim().is_empty());
+        let end = section
+            .lines
+            .iter()
+            .rposition(|line| !line.trim().is_empty());
+        let (Some(start), Some(end)) = (start, end) else {
+            return;
+        };
+        let (text, annotations) = chords::extract(&section.lines[start..=end].join("\n"));
+        let text = text
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // the section label decides the verse name if it has a known kind or number
+        let prefix = section
+            .label
+            .as_deref()
+            .and_then(section_prefix)
+            .unwrap_or(section.prefix);
+        let labelled_name = section
+            .label
+            .as_deref()
+            .and_then(|label| section_name(prefix, label));
+
+        // a repeated section is only added to the verse order
+        if let Some(name) = &labelled_name {
+            if self.song.verses.get(name) == Some(&text)
+                && self
+                    .song
+                    .chords
+                    .get(name)
+                    .map_or(annotations.is_empty(), |existing| *existing == annotations)
+            {
+                self.order.push(name.clone());
+                if prefix == "c" {
+                    self.last_chorus = Some(name.clone());
+                }
+                return;
+            }
+        }
+
+        let name = labelled_name
+            .filter(|name| !self.song.verses.contains_key(name))
+            .unwrap_or_else(|| self.next_name(prefix));
+
+        self.song.verses.insert(name.clone(), text);
+        if !annotations.is_empty() {
+            self.song.chords.insert(name.clone(), annotations);
+        }
+        if prefix == "c" {
+            self.last_chorus = Some(name.clone());
+        }
+        self.order.push(name);
+    }
+
+    /// Generates the next unused verse name with a prefix
+    fn next_name(&mut self, prefix: &'static str) -> String {
+        let counter = self.counters.entry(prefix).or_default();
+        loop {
+            *counter += 1;
+            let name = format!("{}{}", prefix, counter);
+            if !self.song.verses.contains_key(&name) {
+                return name;
+            }
+        }
+    }
+}
+
+/// Gets the label of a section from a directive value (`Verse 1` or `label="Verse 1"`)
+fn parse_label(value: &str) -> Option<String> {
+    let label = match value.strip_prefix("label=") {
+        Some(label) => label.trim_matches(|c| c == '"' || c == '\''),
+        None => value,
+    };
+    Some(String::from(label.trim())).filter(|label| !label.is_empty())
+}
+
+/// Gets the ChordPro section directive and label for a verse name
+pub fn section_for_name(name: &str) -> (&'static str, String) {
+    let number_start = name
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(name.len());
+    let (prefix, number) = name.split_at(number_start);
+
+    let Some((_, label)) = SECTION_LABELS.iter().find(|(kind, _)| *kind == prefix) else {
+        return ("verse", String::from(name));
+    };
+    let directive = match prefix {
+        "c" => "chorus",
+        "b" => "bridge",
+        _ => "verse",
+    };
+    let label = if number.is_empty() {
+        String::from(*label)
+    } else {
+        format!("{} {}", label, number)
+    };
+    (directive, label)
+}
+
+/// Writes a song as a ChordPro document, with chords inline in the lyrics
+pub fn write(title: &str, song: &Song) -> String {
+    let mut text = String::new();
+    text.push_str(&format!("{{title: {}}}\n", title));
+    for alternate_title in &song.alternate_titles {
+        text.push_str(&format!("{{subtitle: {}}}\n", alternate_title));
+    }
+    for author in &song.authors {
+        text.push_str(&format!("{{artist: {}}}\n", author));
+    }
+    if let Some(copyright) = &song.copyright {
+        text.push_str(&format!("{{copyright: {}}}\n", copyright));
+    }
+    if let Some(ccli_number) = &song.ccli_number {
+        text.push_str(&format!("{{ccli: {}}}\n", ccli_number));
+    }
+    if let Some(key) = &song.key {
+        text.push_str(&format!("{{key: {}}}\n", key));
+    }
+    if let Some(tempo) = song.tempo {
+        text.push_str(&format!("{{tempo: {}}}\n", tempo));
+    }
+
+    // sections in the verse order (verses not in it are added at the end)
+    let mut names: Vec<_> = song.verses.keys().collect();
+    names.sort_by(|a, b| natcasecmp(a, b));
+    let order: Vec<_> = song
+        .verse_order
+        .iter()
+        .filter(|name| song.verses.contains_key(*name))
+        .chain(
+            names
+                .into_iter()
+                .filter(|name| !song.verse_order.contains(name)),
+        )
+        .collect();
+
+    let mut written = Vec::new();
+    for name in order {
+        let (directive, label) = section_for_name(name);
+        text.push('\n');
+
+        // repeated choruses are recalled instead of written out again
+        if directive == "chorus" && written.contains(&name) {
+            text.push_str(&format!("{{chorus: {}}}\n", label));
+            continue;
+        }
+
+        let lyrics = chords::insert(
+            &song.verses[name],
+            song.chords.get(name).map_or(&[], Vec::as_slice),
+        );
+        text.push_str(&format!("{{start_of_{}: {}}}\n", directive, label));
+        text.push_str(&lyrics);
+        text.push_str(&format!("\n{{end_of_{}}}\n", directive));
+        written.push(name);
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = "{title: Amazing Grace}
+{subtitle: New Britain}
+{artist: John Newton}
+{copyright: Public Domain}
+{ccli: 22025}
+{key: G}
+{tempo: 72}
+# a comment
+
+{start_of_verse: Verse 1}
+[G]Amazing [D/F#]grace how [G]sweet the sound
+That saved a wretch like me
+{end_of_verse}
+
+{start_of_chorus}
+[C]My chains are gone
+{end_of_chorus}
+
+{start_of_tab}
+e|---3---|
+{end_of_tab}
+
+Twas grace that taught
+my heart to fear
+
+{chorus}
+";
+
+    #[test]
+    fn parses_songs() {
+        let (title, song) = parse(SONG, "file").unwrap();
+
+        assert_eq!(title, "Amazing Grace");
+        assert_eq!(song.alternate_titles, ["New Britain"]);
+        assert_eq!(song.authors, ["John Newton"]);
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+        assert_eq!(song.ccli_number.as_deref(), Some("22025"));
+        assert_eq!(song.key.as_deref(), Some("G"));
+        assert_eq!(song.tempo, Some(72));
+
+        assert_eq!(
+            song.verses["1"],
+            "Amazing grace how sweet the sound\nThat saved a wretch like me"
+        );
+        assert_eq!(song.verses["c1"], "My chains are gone");
+        assert_eq!(song.verses["2"], "Twas grace that taught\nmy heart to fear");
+        assert_eq!(song.verses.len(), 3);
+        assert_eq!(song.verse_order, ["1", "c1", "2", "c1"]);
+
+        let chords: Vec<_> = song.chords["1"]
+            .iter()
+            .map(|annotation| {
+                (
+                    annotation.line,
+                    annotation.position,
+                    annotation.chord.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(chords, [(0, 0, "G"), (0, 8, "D/F#"), (0, 18, "G")]);
+    }
+
+    #[test]
+    fn uses_the_fallback_title() {
+        let (title, _) = parse("Just lyrics", " File Name ").unwrap();
+        assert_eq!(title, "File Name");
+
+        assert!(parse("Just lyrics", "").is_err());
+        assert!(parse("{title: No Lyrics}", "file").is_err());
+    }
+
+    #[test]
+    fn names_sections_from_labels() {
+        let text = "{start_of_verse: Bridge}\nBridge\n{end_of_verse}\n\
+            {start_of_chorus: Chorus 2}\nSecond chorus\n{end_of_chorus}\n\
+            {start_of_verse: label=\"Verse 3\"}\nThird\n{end_of_verse}";
+        let (_, song) = parse(text, "file").unwrap();
+
+        let names: Vec<_> = song.verses.keys().map(String::as_str).collect();
+        assert_eq!(names, ["3", "b1", "c2"]);
+    }
+
+    #[test]
+    fn gets_sections_for_verse_names() {
+        assert_eq!(section_for_name("1"), ("verse", String::from("Verse 1")));
+        assert_eq!(section_for_name("c"), ("chorus", String::from("Chorus")));
+        assert_eq!(section_for_name("b2"), ("bridge", String::from("Bridge 2")));
+        assert_eq!(section_for_name("intro"), ("verse", String::from("intro")));
+    }
+
+    #[test]
+    fn written_songs_can_be_imported_again() {
+        let (title, song) = parse(SONG, "file").unwrap();
+        let (written_title, written_song) = parse(&write(&title, &song), "file").unwrap();
+
+        assert_eq!(written_title, title);
+        assert!(written_song == song);
+    }
+}
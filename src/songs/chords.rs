@@ -0,0 +1,413 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Chord shown above a position in a verse
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChordAnnotation {
+    /// Line in the verse (starting at 0)
+    pub line: usize,
+    /// Character in the line the chord is above (starting at 0)
+    pub position: usize,
+    /// Chord name (e.g. `G`, `F#m7`, `D/F#`)
+    pub chord: String,
+}
+
+/// Chords of each verse, mapping verse names to chord annotations
+pub type VerseChords = BTreeMap<String, Vec<ChordAnnotation>>;
+
+/// Note names using sharps
+const SHARP_NOTES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+/// Note names using flats
+const FLAT_NOTES: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "Gb", "G", "Ab", "A", "Bb", "B",
+];
+/// Nashville numbers for each semitone above the key
+const NASHVILLE_NUMBERS: [&str; 12] = [
+    "1", "b2", "2", "b3", "3", "4", "b5", "5", "b6", "6", "b7", "7",
+];
+/// Keys that are normally written with flats (major keys and minor keys)
+const FLAT_KEYS: &[&str] = &[
+    "F", "Bb", "Eb", "Ab", "Db", "Gb", "Cb", "Dm", "Gm", "Cm", "Fm", "Bbm", "Ebm", "Abm",
+];
+
+/// How chords are written
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Notation {
+    /// Note names with sharps (`C#`, `F#`)
+    Sharps,
+    /// Note names with flats (`Db`, `Gb`)
+    Flats,
+    /// Nashville numbers relative to the key (`1`, `4`, `6m`)
+    Nashville,
+}
+
+impl Notation {
+    /// Gets the usual notation for a key (flats for flat keys, sharps for everything else)
+    pub fn for_key(key: &Chord) -> Self {
+        let name = format!("{}{}", FLAT_NOTES[key.root as usize], key.minor_suffix());
+        if FLAT_KEYS.contains(&name.as_str()) {
+            Notation::Flats
+        } else {
+            Notation::Sharps
+        }
+    }
+}
+
+/// Parsed chord (also used for keys)
+#[derive(Clone, PartialEq)]
+pub struct Chord {
+    /// Root note (semitones above C)
+    pub root: u8,
+    /// Everything after the root note (e.g. `m7`, `sus4`)
+    pub quality: String,
+    /// Bass note (semitones above C)
+    pub bass: Option<u8>,
+}
+
+impl Chord {
+    /// Parses a chord name (a root note with optional quality and bass note)
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (chord, bass) = match text.split_once('/') {
+            Some((chord, bass)) => (chord, Some(bass)),
+            None => (text, None),
+        };
+
+        let (root, quality) = parse_note(chord)?;
+        if !is_chord_quality(quality) {
+            return None;
+        }
+        let bass = match bass {
+            Some(bass) => match parse_note(bass)? {
+                (bass, "") => Some(bass),
+                _ => return None,
+            },
+            None => None,
+        };
+
+        Some(Self {
+            root,
+            quality: String::from(quality),
+            bass,
+        })
+    }
+
+    /// Moves the chord up (or down for negative numbers) by a number of semitones
+    pub fn transpose(&self, semitones: i32) -> Self {
+        Self {
+            root: transpose_note(self.root, semitones),
+            quality: self.quality.clone(),
+            bass: self.bass.map(|bass| transpose_note(bass, semitones)),
+        }
+    }
+
+    /// Writes the chord name in a notation.
+    /// Nashville numbers are relative to the key root.
+    pub fn render(&self, notation: Notation, key_root: u8) -> String {
+        let note = |note: u8| match notation {
+            Notation::Sharps => SHARP_NOTES[note as usize],
+            Notation::Flats => FLAT_NOTES[note as usize],
+            Notation::Nashville => {
+                NASHVILLE_NUMBERS[transpose_note(note, -(key_root as i32)) as usize]
+            }
+        };
+
+        let mut name = format!("{}{}", note(self.root), self.quality);
+        if let Some(bass) = self.bass {
+            name.push('/');
+            name.push_str(note(bass));
+        }
+        name
+    }
+
+    /// Gets `m` for minor chords and keys, nothing otherwise
+    pub fn minor_suffix(&self) -> &'static str {
+        if self.quality.starts_with('m') && !self.quality.starts_with("maj") {
+            "m"
+        } else {
+            ""
+        }
+    }
+}
+
+/// Moves a note by a number of semitones, wrapping around the octave
+fn transpose_note(note: u8, semitones: i32) -> u8 {
+    (note as i32 % 12 + semitones.rem_euclid(12)).rem_euclid(12) as u8
+}
+
+/// Checks whether text is a chord name.
+/// Used to tell inline chords apart from other text in square brackets.
+pub fn is_chord(text: &str) -> bool {
+    Chord::parse(text).is_some()
+}
+
+/// Parts that chord qualities are made of (e.g. `m`, `maj7`, `sus4`, `7(b9)`)
+const QUALITY_PARTS: &[&str] = &[
+    "maj", "min", "dim", "aug", "sus", "add", "no", "m", "M", "b", "#", "+", "-", "(", ")", "°",
+    "ø", "Δ",
+];
+
+/// Checks whether text is made up only of chord quality parts and numbers
+fn is_chord_quality(mut quality: &str) -> bool {
+    while !quality.is_empty() {
+        if let Some(rest) = quality.strip_prefix(|c: char| c.is_ascii_digit()) {
+            quality = rest;
+        } else if let Some(part) = QUALITY_PARTS
+            .iter()
+            .find(|part| quality.starts_with(**part))
+        {
+            quality = &quality[part.len()..];
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+/// Parses a note name (`C`, `C#`, `Db`...) at the start of text, returning the note and the rest of the text
+fn parse_note(text: &str) -> Option<(u8, &str)> {
+    let mut chars = text.chars();
+    let natural = match chars.next()? {
+        'C' => 0,
+        'D' => 2,
+        'E' => 4,
+        'F' => 5,
+        'G' => 7,
+        'A' => 9,
+        'B' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+
+    Some(match rest.chars().next() {
+        Some(accidental @ ('#' | '♯')) => {
+            (transpose_note(natural, 1), &rest[accidental.len_utf8()..])
+        }
+        Some(accidental @ ('b' | '♭')) => {
+            (transpose_note(natural, -1), &rest[accidental.len_utf8()..])
+        }
+        _ => (natural, rest),
+    })
+}
+
+/// Changes the inline chords in text, leaving the lyrics unchanged
+pub fn map_inline(text: &str, change: impl Fn(&str) -> String) -> String {
+    let (plain, annotations) = extract(text);
+    if annotations.is_empty() {
+        return String::from(text);
+    }
+    let annotations: Vec<_> = annotations
+        .into_iter()
+        .map(|annotation| ChordAnnotation {
+            chord: change(&annotation.chord),
+            ..annotation
+        })
+        .collect();
+    insert(&plain, &annotations)
+}
+
+/// Splits verse text with inline chords (`[G]Amazing [D]grace`) into plain text and chord annotations.
+/// Text in square brackets that isn't a chord is left as-is.
+pub fn extract(text: &str) -> (String, Vec<ChordAnnotation>) {
+    let mut lines = Vec::new();
+    let mut annotations = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let mut plain = String::new();
+        let mut position = 0;
+        let mut rest = line;
+
+        while let Some(start) = rest.find('[') {
+            let before = &rest[..start];
+            plain.push_str(before);
+            position += before.chars().count();
+
+            let after = &rest[start + 1..];
+            match after.find(']') {
+                Some(end) if is_chord(after[..end].trim()) => {
+                    annotations.push(ChordAnnotation {
+                        line: line_index,
+                        position,
+                        chord: String::from(after[..end].trim()),
+                    });
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    plain.push('[');
+                    position += 1;
+                    rest = after;
+                }
+            }
+        }
+        plain.push_str(rest);
+
+        lines.push(plain);
+    }
+
+    (lines.join("\n"), annotations)
+}
+
+/// Combines plain verse text and chord annotations into text with inline chords.
+/// Lines are padded with spaces for chords past the end of the line.
+pub fn insert(text: &str, annotations: &[ChordAnnotation]) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(line_index, line)| {
+            let mut line_annotations: Vec<_> = annotations
+                .iter()
+                .filter(|annotation| annotation.line == line_index)
+                .collect();
+            line_annotations.sort_by_key(|annotation| annotation.position);
+
+            let mut result = String::new();
+            let mut chars = line.chars();
+            let mut position = 0;
+            for annotation in line_annotations {
+                while position < annotation.position {
+                    result.push(chars.next().unwrap_or(' '));
+                    position += 1;
+                }
+                result.push_str(&format!("[{}]", annotation.chord));
+            }
+            result.extend(chars);
+
+            result
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Removes inline chords from text, leaving only the lyrics.
+/// Text without chords is returned unchanged (including its line endings).
+pub fn strip(text: &str) -> String {
+    let (plain, annotations) = extract(text);
+    if annotations.is_empty() {
+        return String::from(text);
+    }
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a chord and writes it back out
+    fn round_trip(name: &str, notation: Notation) -> String {
+        Chord::parse(name).unwrap().render(notation, 0)
+    }
+
+    #[test]
+    fn parses_and_renders_chords() {
+        assert_eq!(round_trip("G", Notation::Sharps), "G");
+        assert_eq!(round_trip("F#m7", Notation::Sharps), "F#m7");
+        assert_eq!(round_trip("Bbmaj7", Notation::Flats), "Bbmaj7");
+        assert_eq!(round_trip("Csus4", Notation::Sharps), "Csus4");
+        assert_eq!(round_trip("D/F#", Notation::Sharps), "D/F#");
+        assert_eq!(round_trip("Eb/Bb", Notation::Flats), "Eb/Bb");
+    }
+
+    #[test]
+    fn converts_between_sharps_and_flats() {
+        assert_eq!(round_trip("C#m", Notation::Flats), "Dbm");
+        assert_eq!(round_trip("Gb", Notation::Sharps), "F#");
+        assert_eq!(round_trip("A♭", Notation::Sharps), "G#");
+        assert_eq!(round_trip("Cb", Notation::Sharps), "B");
+        assert_eq!(round_trip("B#", Notation::Sharps), "C");
+    }
+
+    #[test]
+    fn rejects_text_that_isnt_a_chord() {
+        for text in ["", "H", "Chorus", "x2", "G/", "G/F#m", "Am/B/C"] {
+            assert!(Chord::parse(text).is_none(), "{:?} parsed as a chord", text);
+        }
+    }
+
+    #[test]
+    fn transposes_chords() {
+        let transpose = |name: &str, semitones: i32, notation: Notation| {
+            Chord::parse(name)
+                .unwrap()
+                .transpose(semitones)
+                .render(notation, 0)
+        };
+
+        assert_eq!(transpose("G", 2, Notation::Sharps), "A");
+        assert_eq!(transpose("A", 3, Notation::Sharps), "C");
+        assert_eq!(transpose("C", -1, Notation::Sharps), "B");
+        assert_eq!(transpose("D/F#", 1, Notation::Flats), "Eb/G");
+        assert_eq!(transpose("Bbm7/Ab", -3, Notation::Sharps), "Gm7/F");
+        assert_eq!(transpose("E", 12, Notation::Sharps), "E");
+        assert_eq!(transpose("E", -25, Notation::Sharps), "D#");
+        assert_eq!(transpose("E", i32::MAX, Notation::Sharps), "B");
+        assert_eq!(transpose("E", i32::MIN, Notation::Sharps), "G#");
+    }
+
+    #[test]
+    fn transposing_there_and_back_keeps_the_chord() {
+        for name in ["C", "C#m7", "Dbmaj7", "F#/A#", "Gsus4/D", "Bb"] {
+            let chord = Chord::parse(name).unwrap();
+            for semitones in -13..=13 {
+                assert!(
+                    chord.transpose(semitones).transpose(-semitones) == chord,
+                    "{} moved by {} semitones",
+                    name,
+                    semitones
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn renders_nashville_numbers() {
+        let key = Chord::parse("G").unwrap();
+        let nashville = |name: &str| {
+            Chord::parse(name)
+                .unwrap()
+                .render(Notation::Nashville, key.root)
+        };
+
+        assert_eq!(nashville("G"), "1");
+        assert_eq!(nashville("Em"), "6m");
+        assert_eq!(nashville("D/F#"), "5/7");
+        assert_eq!(nashville("F"), "b7");
+    }
+
+    #[test]
+    fn extracts_and_inserts_inline_chords() {
+        let text = "[G]Amazing [D/F#]grace\nHow [C]sweet [not a chord] the sound[G]";
+        let (plain, annotations) = extract(text);
+
+        assert_eq!(plain, "Amazing grace\nHow sweet [not a chord] the sound");
+        let chords: Vec<_> = annotations
+            .iter()
+            .map(|annotation| {
+                (
+                    annotation.line,
+                    annotation.position,
+                    annotation.chord.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            chords,
+            [(0, 0, "G"), (0, 8, "D/F#"), (1, 4, "C"), (1, 33, "G")]
+        );
+        assert_eq!(insert(&plain, &annotations), text);
+    }
+
+    #[test]
+    fn strip_keeps_text_without_chords() {
+        assert_eq!(
+            strip("Amazing grace\r\nHow sweet\n"),
+            "Amazing grace\r\nHow sweet\n"
+        );
+        assert_eq!(
+            strip("[G]Amazing [D]grace\nHow sweet"),
+            "Amazing grace\nHow sweet"
+        );
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    chords,
+    models::{Song, SongLibrary},
+};
+
+/// Number of words in each shingle used to compare lyrics
+const SHINGLE_SIZE: usize = 3;
+
+/// Similarity (0 to 1) above which songs are reported as having similar lyrics by default
+pub const DEFAULT_SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// Why two songs may be duplicates
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// The titles are the same once punctuation, case and bracketed parts are ignored
+    SameTitle,
+    /// The lyrics are mostly the same
+    SimilarLyrics,
+}
+
+/// Pair of songs that may be duplicates
+#[derive(Serialize, Deserialize)]
+pub struct DuplicateSongs {
+    /// Song titles (sorted)
+    pub titles: [String; 2],
+    pub reasons: Vec<DuplicateReason>,
+    /// Lyric similarity from 0 (nothing in common) to 1 (the same once normalized)
+    pub similarity: f64,
+}
+
+/// Finds songs that may be duplicates, by title or by lyric similarity.
+/// Pairs are sorted with the most similar lyrics first.
+pub fn find_duplicates(library: &SongLibrary, threshold: f64) -> Vec<DuplicateSongs> {
+    let songs: Vec<_> = library
+        .iter()
+        .map(|(title, song)| (title, normalize_title(title), shingles(song)))
+        .collect();
+
+    let mut duplicates = Vec::new();
+    for (index, (title, normalized_title, shingles)) in songs.iter().enumerate() {
+        for (other_title, other_normalized_title, other_shingles) in &songs[index + 1..] {
+            let similarity = jaccard(shingles, other_shingles);
+
+            let mut reasons = Vec::new();
+            if !normalized_title.is_empty() && normalized_title == other_normalized_title {
+                reasons.push(DuplicateReason::SameTitle);
+            }
+            if similarity >= threshold {
+                reasons.push(DuplicateReason::SimilarLyrics);
+            }
+
+            if !reasons.is_empty() {
+                duplicates.push(DuplicateSongs {
+                    titles: [String::from(*title), String::from(*other_title)],
+                    reasons,
+                    similarity,
+                });
+            }
+        }
+    }
+
+    duplicates.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    duplicates
+}
+
+/// Normalizes a title for comparison, ignoring case, punctuation and bracketed parts
+/// (e.g. `Amazing Grace (My Chains Are Gone)` and `amazing grace` are the same)
+pub fn normalize_title(title: &str) -> String {
+    let mut depth = 0usize;
+    let without_brackets: String = title
+        .chars()
+        .filter(|c| match c {
+            '(' | '[' => {
+                depth += 1;
+                false
+            }
+            ')' | ']' => {
+                depth = depth.saturating_sub(1);
+                false
+            }
+            _ => depth == 0,
+        })
+        .collect();
+
+    words(&without_brackets).join(" ")
+}
+
+/// Splits text into lowercase words, ignoring punctuation
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.replace('\'', "").to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Gets the overlapping word sequences in a song's lyrics (chords, case and punctuation are ignored).
+/// Each verse is only counted once, so repeated choruses don't change the similarity.
+fn shingles(song: &Song) -> HashSet<Vec<String>> {
+    let mut shingles = HashSet::new();
+    for text in song.verses.values() {
+        let words = words(&chords::strip(text));
+        if words.len() < SHINGLE_SIZE {
+            if !words.is_empty() {
+                shingles.insert(words);
+            }
+            continue;
+        }
+        shingles.extend(words.windows(SHINGLE_SIZE).map(<[String]>::to_vec));
+    }
+    shingles
+}
+
+/// Jaccard similarity of two sets (0 if both are empty)
+fn jaccard(a: &HashSet<Vec<String>>, b: &HashSet<Vec<String>>) -> f64 {
+    let intersection = a.intersection(b).count();
+    let union = a.len() + b.len() - intersection;
+    if union == 0 {
+        return 0.0;
+    }
+    intersection as f64 / union as f64
+}
+
+/// Merges a song into another, returning the combined song.
+/// The target song's fields are kept, with empty fields filled from the other song.
+/// Lists are combined, and verses of the other song that aren't in the target song are added
+/// (renamed if the verse name is already used).
+pub fn merge_songs(target_title: &str, target: &Song, source_title: &str, source: &Song) -> Song {
+    let mut merged = target.clone();
+
+    // verses with the same lyrics are the same verse, even if they are named differently
+    let existing_verses: BTreeMap<_, _> = target
+        .verses
+        .iter()
+        .map(|(name, text)| (words(&chords::strip(text)), name.clone()))
+        .collect();
+    let mut verse_names = BTreeMap::new();
+    for (name, text) in &source.verses {
+        let new_name = match existing_verses.get(&words(&chords::strip(text))) {
+            Some(existing_name) => existing_name.clone(),
+            None => {
+                let new_name = unused_verse_name(&merged, name);
+                merged.verses.insert(new_name.clone(), text.clone());
+                if let Some(annotations) = source.chords.get(name) {
+                    merged.chords.insert(new_name.clone(), annotations.clone());
+                }
+                new_name
+            }
+        };
+        verse_names.insert(name.clone(), new_name);
+    }
+
+    for (language, verses) in &source.translations {
+        let translation = merged.translations.entry(language.clone()).or_default();
+        for (name, text) in verses {
+            if let Some(new_name) = verse_names.get(name) {
+                translation
+                    .entry(new_name.clone())
+                    .or_insert_with(|| text.clone());
+            }
+        }
+    }
+
+    merged.translations.retain(|_, verses| !verses.is_empty());
+
+    let rename = |names: &Vec<String>| -> Vec<String> {
+        names
+            .iter()
+            .filter_map(|name| verse_names.get(name).cloned())
+            .collect()
+    };
+    if merged.verse_order.is_empty() {
+        merged.verse_order = rename(&source.verse_order);
+    }
+    for (name, order) in &source.arrangements {
+        let order = rename(order);
+        if !order.is_empty() {
+            merged.arrangements.entry(name.clone()).or_insert(order);
+        }
+    }
+
+    let source_titles =
+        std::iter::once(String::from(source_title)).chain(source.alternate_titles.iter().cloned());
+    for title in source_titles {
+        if !is_copy_title(&title, target_title) && !merged.alternate_titles.contains(&title) {
+            merged.alternate_titles.push(title);
+        }
+    }
+    combine(&mut merged.authors, &source.authors);
+    combine(&mut merged.themes, &source.themes);
+    for songbook in &source.songbooks {
+        if !merged.songbooks.contains(songbook) {
+            merged.songbooks.push(songbook.clone());
+        }
+    }
+
+    for (field, value) in [
+        (&mut merged.attribution, &source.attribution),
+        (&mut merged.copyright, &source.copyright),
+        (&mut merged.ccli_number, &source.ccli_number),
+        (&mut merged.language, &source.language),
+        (&mut merged.key, &source.key),
+        (&mut merged.notes, &source.notes),
+    ] {
+        if field.is_none() {
+            field.clone_from(value);
+        }
+    }
+    if merged.tempo.is_none() {
+        merged.tempo = source.tempo;
+    }
+    for (key, value) in &source.extra {
+        merged
+            .extra
+            .entry(key.clone())
+            .or_insert_with(|| value.clone());
+    }
+
+    merged
+}
+
+/// Whether a title is the same as another title, or a numbered copy of it made when importing (e.g. `Title (2)`)
+fn is_copy_title(title: &str, original: &str) -> bool {
+    let Some(suffix) = title.strip_prefix(original) else {
+        return false;
+    };
+    suffix.is_empty()
+        || suffix
+            .strip_prefix(" (")
+            .and_then(|suffix| suffix.strip_suffix(')'))
+            .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Adds the values that aren't already in a list
+fn combine(values: &mut Vec<String>, other: &[String]) {
+    for value in other {
+        if !values.contains(value) {
+            values.push(value.clone());
+        }
+    }
+}
+
+/// Gets a verse name that isn't used in a song (ignoring case), numbering it if the name is used (e.g. `c2`, `c3`)
+fn unused_verse_name(song: &Song, name: &str) -> String {
+    let is_unused = |name: &str| {
+        !song
+            .verses
+            .keys()
+            .any(|existing| existing.eq_ignore_ascii_case(name))
+    };
+    if is_unused(name) {
+        return String::from(name);
+    }
+
+    let letters = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    (2..)
+        .map(|number| format!("{}{}", letters, number))
+        .find(|name| is_unused(name))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn song(value: serde_json::Value) -> Song {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn normalizes_titles() {
+        assert_eq!(normalize_title("Amazing Grace"), "amazing grace");
+        assert_eq!(
+            normalize_title("Amazing Grace (My Chains Are Gone)"),
+            "amazing grace"
+        );
+        assert_eq!(
+            normalize_title("  It's Well, With My Soul! "),
+            "its well with my soul"
+        );
+        assert_eq!(
+            normalize_title("Holy [Live] Holy (Version (2))"),
+            "holy holy"
+        );
+        assert_eq!(normalize_title("Ámen"), "ámen");
+        assert_eq!(normalize_title("(Intro)"), "");
+    }
+
+    #[test]
+    fn finds_duplicates_by_title_and_lyrics() {
+        let mut library = SongLibrary::new();
+        library.insert(
+            String::from("Amazing Grace"),
+            song(json!({"verses": {"1": "Amazing grace how sweet the sound\nThat saved a wretch like me"}})),
+        );
+        library.insert(
+            String::from("Amazing Grace (My Chains Are Gone)"),
+            song(json!({"verses": {"1": "My chains are gone\nI've been set free"}})),
+        );
+        library.insert(
+            String::from("Grace"),
+            song(json!({"verses": {"v1": "[G]Amazing grace, how sweet the sound,\nthat saved a wretch like me!"}})),
+        );
+
+        let duplicates = find_duplicates(&library, DEFAULT_SIMILARITY_THRESHOLD);
+        let pairs: Vec<_> = duplicates
+            .iter()
+            .map(|duplicate| {
+                (
+                    duplicate.titles[0].as_str(),
+                    duplicate.titles[1].as_str(),
+                    duplicate.reasons.clone(),
+                )
+            })
+            .collect();
+
+        assert!(
+            pairs
+                == [
+                    (
+                        "Amazing Grace",
+                        "Grace",
+                        vec![DuplicateReason::SimilarLyrics]
+                    ),
+                    (
+                        "Amazing Grace",
+                        "Amazing Grace (My Chains Are Gone)",
+                        vec![DuplicateReason::SameTitle]
+                    ),
+                ]
+        );
+        assert_eq!(duplicates[0].similarity, 1.0);
+    }
+
+    #[test]
+    fn jaccard_similarity() {
+        let set = |words: &[&str]| -> HashSet<Vec<String>> {
+            words.iter().map(|word| vec![String::from(*word)]).collect()
+        };
+
+        assert_eq!(jaccard(&set(&[]), &set(&[])), 0.0);
+        assert_eq!(jaccard(&set(&["a", "b"]), &set(&["a", "b"])), 1.0);
+        assert_eq!(jaccard(&set(&["a", "b", "c"]), &set(&["b", "c", "d"])), 0.5);
+        assert_eq!(jaccard(&set(&["a"]), &set(&["b"])), 0.0);
+    }
+
+    #[test]
+    fn merges_songs() {
+        let target = song(json!({
+            "verses": {"1": "Amazing grace how sweet the sound", "c": "My chains are gone"},
+            "verseOrder": ["1", "c"],
+            "authors": ["John Newton"],
+            "key": "G",
+        }));
+        let source = song(json!({
+            "verses": {
+                "v1": "[G]Amazing grace, how sweet the sound",
+                "c": "Unending love, amazing grace",
+            },
+            "verseOrder": ["v1", "c"],
+            "arrangements": {"Short": ["v1", "c", "c"]},
+            "translations": {"es": {"v1": "Sublime gracia", "c": "Amor sin fin"}},
+            "alternateTitles": ["Chains"],
+            "authors": ["John Newton", "Chris Tomlin"],
+            "key": "A",
+            "copyright": "2006 sixsteps Music",
+        }));
+
+        let merged = merge_songs("Amazing Grace", &target, "Amazing Grace (2)", &source);
+
+        // verses with the same lyrics are matched, others are added with unused names
+        assert_eq!(merged.verses.len(), 3);
+        assert_eq!(merged.verses["c2"], "Unending love, amazing grace");
+        assert_eq!(merged.verse_order, ["1", "c"]);
+        assert_eq!(merged.arrangements["Short"], ["1", "c2", "c2"]);
+        assert_eq!(merged.translations["es"]["1"], "Sublime gracia");
+        assert_eq!(merged.translations["es"]["c2"], "Amor sin fin");
+
+        // target fields are kept, missing fields and lists are filled from the source
+        assert_eq!(merged.key.as_deref(), Some("G"));
+        assert_eq!(merged.copyright.as_deref(), Some("2006 sixsteps Music"));
+        assert_eq!(merged.authors, ["John Newton", "Chris Tomlin"]);
+        assert_eq!(merged.alternate_titles, ["Chains"]);
+    }
+
+    #[test]
+    fn numbers_unused_verse_names() {
+        let target = song(json!({"verses": {"1": "a", "c": "b", "C2": "c", "v1": "d"}}));
+
+        assert_eq!(unused_verse_name(&target, "b"), "b");
+        assert_eq!(unused_verse_name(&target, "1"), "2");
+        assert_eq!(unused_verse_name(&target, "c"), "c3");
+        assert_eq!(unused_verse_name(&target, "v1"), "v2");
+    }
+}
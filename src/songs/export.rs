@@ -0,0 +1,43 @@
+use std::{
+    collections::HashSet,
+    io::{Cursor, Write},
+};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{content::service::sanitize_filename, helpers::errors::GenericError};
+
+/// Bundles exported files into a zip file
+pub fn zip_files(files: Vec<(String, String)>) -> Result<Vec<u8>, GenericError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (filename, contents) in files {
+        zip.start_file(filename, options)
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+
+    let cursor = zip
+        .finish()
+        .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+    Ok(cursor.into_inner())
+}
+
+/// Generates a unique filename for an exported song from its title
+pub fn export_filename(title: &str, extension: &str, used: &mut HashSet<String>) -> String {
+    let mut base = sanitize_filename(title);
+    if base.is_empty() {
+        base = String::from("song");
+    }
+
+    let mut filename = format!("{}.{}", base, extension);
+    let mut number = 2;
+    while !used.insert(filename.to_lowercase()) {
+        filename = format!("{} ({}).{}", base, number, extension);
+        number += 1;
+    }
+
+    filename
+}
@@ -0,0 +1,299 @@
+use std::io::{Cursor, Read};
+
+use serde::{Deserialize, Serialize};
+use zip::ZipArchive;
+
+use crate::helpers::sort::natcasecmp;
+
+use super::{
+    lint::{lint_song, LintIssue, SongLintConfig},
+    models::{Song, SongLibrary},
+    validation::validate_song,
+};
+
+/// Signature at the start of a zip file
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+/// How to handle an imported song that has the same title as a song in the library
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Keep the existing song
+    #[default]
+    Skip,
+    /// Replace the existing song with the imported song
+    Replace,
+    /// Add the imported song with a numbered title
+    Rename,
+}
+
+/// Parser of an import format, reading the songs from the files to import
+pub type ImportParser = fn(Vec<(String, Vec<u8>)>, &mut ImportReport) -> Vec<ImportedSong>;
+
+/// Song read by an importer
+pub struct ImportedSong {
+    /// File (or other source) the song was read from
+    pub source: String,
+    pub title: String,
+    pub song: Song,
+}
+
+/// Results of an import
+#[derive(Default, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Whether this is a dry run (the song library was not changed)
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Titles of songs that were added to the library
+    pub added: Vec<String>,
+    /// Titles of songs that were identical to songs already in the library
+    pub unchanged: Vec<String>,
+    /// Songs that had the same title as a song in the library
+    pub conflicts: Vec<ImportConflict>,
+    /// Sources that could not be imported
+    pub errors: Vec<ImportError>,
+}
+
+/// Imported song with the same title as a song in the library
+#[derive(Serialize, Deserialize)]
+pub struct ImportConflict {
+    /// Source of the imported song
+    pub source: String,
+    /// Title of the imported song
+    pub title: String,
+    /// How the conflict was resolved
+    pub resolution: ConflictResolution,
+    /// Title the imported song was added as if it was renamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_title: Option<String>,
+}
+
+/// How an import conflict was resolved
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    Skipped,
+    Replaced,
+    Renamed,
+}
+
+/// Source that could not be imported
+#[derive(Serialize, Deserialize)]
+pub struct ImportError {
+    /// File (or other source) that failed
+    pub source: String,
+    pub message: String,
+}
+
+impl ImportReport {
+    /// Records a source that could not be imported
+    pub fn error(&mut self, source: &str, message: impl Into<String>) {
+        self.errors.push(ImportError {
+            source: String::from(source),
+            message: message.into(),
+        });
+    }
+}
+
+/// Splits an uploaded file into the files to import.
+/// Zip files are expanded (ignoring directories and hidden files), anything else is returned as-is.
+/// Extraction stops once the extracted files add up to more than `max_size` bytes.
+pub fn extract_files(
+    name: &str,
+    bytes: Vec<u8>,
+    max_size: u64,
+    report: &mut ImportReport,
+) -> Vec<(String, Vec<u8>)> {
+    if !bytes.starts_with(ZIP_SIGNATURE) {
+        return vec![(String::from(name), bytes)];
+    }
+
+    let mut archive = match ZipArchive::new(Cursor::new(bytes)) {
+        Ok(archive) => archive,
+        Err(err) => {
+            report.error(name, format!("Invalid zip file: {}", err));
+            return Vec::new();
+        }
+    };
+
+    let mut files = Vec::new();
+    let mut remaining_size = max_size;
+    for index in 0..archive.len() {
+        let mut file = match archive.by_index(index) {
+            Ok(file) => file,
+            Err(err) => {
+                report.error(name, format!("Invalid zip file entry: {}", err));
+                continue;
+            }
+        };
+
+        let file_name = String::from(file.name());
+        let is_hidden = file_name
+            .split('/')
+            .any(|component| component.starts_with('.') || component == "__MACOSX");
+        if file.is_dir() || is_hidden {
+            continue;
+        }
+
+        // the sizes in the zip file can't be trusted, so reading stops one byte past the limit
+        let mut contents = Vec::new();
+        match file
+            .by_ref()
+            .take(remaining_size + 1)
+            .read_to_end(&mut contents)
+        {
+            Ok(size) if size as u64 > remaining_size => {
+                report.error(
+                    name,
+                    format!("Zip file contents are larger than {} bytes", max_size),
+                );
+                break;
+            }
+            Ok(size) => {
+                remaining_size -= size as u64;
+                files.push((format!("{}/{}", name, file_name), contents));
+            }
+            Err(err) => report.error(&file_name, format!("Failed to read file: {}", err)),
+        }
+    }
+
+    files
+}
+
+/// Merges imported songs into a song library.
+/// If lint rules are given, songs with lint issues are skipped.
+pub fn merge(
+    library: &mut SongLibrary,
+    songs: Vec<ImportedSong>,
+    strategy: ConflictStrategy,
+    lint: Option<&SongLintConfig>,
+    report: &mut ImportReport,
+) {
+    for ImportedSong {
+        source,
+        title,
+        mut song,
+    } in songs
+    {
+        if song.attribution.is_none() {
+            song.attribution = song.default_attribution();
+        }
+
+        // verse orders in other formats may refer to verses that don't exist
+        let verses = &song.verses;
+        song.verse_order.retain(|name| verses.contains_key(name));
+
+        if let Some(problem) = validate_song(&title, &song).first() {
+            report.error(&source, format!("{}: {}", problem.field, problem.message));
+            continue;
+        }
+        if let Some(issue) = lint.and_then(|lint| lint_song(&title, &song, lint).into_iter().next())
+        {
+            report.error(&source, lint_message(&issue));
+            continue;
+        }
+
+        let Some(existing) = library.get(&title) else {
+            library.insert(title.clone(), song);
+            report.added.push(title);
+            continue;
+        };
+
+        if *existing == song {
+            report.unchanged.push(title);
+            continue;
+        }
+
+        let (resolution, new_title) = match strategy {
+            ConflictStrategy::Skip => (ConflictResolution::Skipped, None),
+            ConflictStrategy::Replace => {
+                library.insert(title.clone(), song);
+                (ConflictResolution::Replaced, None)
+            }
+            ConflictStrategy::Rename => {
+                let new_title = (2..)
+                    .map(|number| format!("{} ({})", title, number))
+                    .find(|new_title| !library.contains_key(new_title))
+                    .unwrap();
+                library.insert(new_title.clone(), song);
+                (ConflictResolution::Renamed, Some(new_title))
+            }
+        };
+
+        report.conflicts.push(ImportConflict {
+            source,
+            title,
+            resolution,
+            new_title,
+        });
+    }
+}
+
+/// Describes a lint issue for an import report
+fn lint_message(issue: &LintIssue) -> String {
+    match (&issue.verse, issue.line) {
+        (Some(verse), Some(line)) => format!("{} (verse {}, line {})", issue.message, verse, line),
+        (Some(verse), None) => format!("{} (verse {})", issue.message, verse),
+        _ => issue.message.clone(),
+    }
+}
+
+/// Verse name prefixes and the labels used for them in song files
+pub const SECTION_LABELS: &[(&str, &str)] = &[
+    ("", "Verse"),
+    ("c", "Chorus"),
+    ("b", "Bridge"),
+    ("p", "Pre-Chorus"),
+    ("i", "Intro"),
+    ("e", "Ending"),
+    ("o", "Other"),
+];
+
+/// Gets the verse name prefix for a section label (e.g. `Chorus 2` is a chorus)
+pub fn section_prefix(label: &str) -> Option<&'static str> {
+    let kind = label
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c.is_whitespace())
+        .to_lowercase();
+    match kind.as_str() {
+        "refrain" => Some("c"),
+        "prechorus" => Some("p"),
+        "outro" | "tag" => Some("e"),
+        "misc" => Some("o"),
+        _ => SECTION_LABELS
+            .iter()
+            .find(|(_, section_label)| section_label.to_lowercase() == kind)
+            .map(|(prefix, _)| *prefix),
+    }
+}
+
+/// Gets the verse name for a section label ending with a number (e.g. `Chorus 2` becomes `c2`)
+pub fn section_name(prefix: &str, label: &str) -> Option<String> {
+    let number_start = label
+        .rfind(|c: char| !c.is_ascii_digit())
+        .map_or(0, |index| index + 1);
+    let number = &label[number_start..];
+    if number.is_empty() {
+        return None;
+    }
+    Some(format!("{}{}", prefix, number))
+}
+
+/// Sets the verse order of an imported song from the order its sections appeared in.
+/// The verse order is left empty if it's just the verses in order.
+pub fn set_verse_order(song: &mut Song, order: Vec<String>) {
+    let mut names: Vec<_> = song.verses.keys().cloned().collect();
+    names.sort_by(|a, b| natcasecmp(a, b));
+    if order != names {
+        song.verse_order = order;
+    }
+}
+
+/// Trims trailing whitespace from each line and removes leading and trailing blank lines
+pub fn clean_verse_text(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
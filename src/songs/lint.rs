@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    chords,
+    models::{Song, SongLibrary},
+    slides::split_at_markers,
+};
+
+/// Song library lint rules
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SongLintConfig {
+    /// Longest line (in characters, not counting chords) that fits on the displays
+    #[serde(default = "default_max_line_length")]
+    pub max_line_length: Option<usize>,
+
+    /// Most lines in a verse that fit on the displays at once
+    #[serde(default = "default_max_lines_per_slide")]
+    pub max_lines_per_slide: Option<usize>,
+
+    /// Characters that shouldn't be in lyrics
+    #[serde(default = "default_forbidden_characters")]
+    pub forbidden_characters: String,
+
+    /// Whether to report lines with whitespace at the start or end
+    #[serde(default = "default_true")]
+    pub trailing_whitespace: bool,
+
+    /// Whether to report verses without any lyrics
+    #[serde(default = "default_true")]
+    pub empty_sections: bool,
+
+    /// Whether to report verse names the client doesn't sort as expected
+    #[serde(default = "default_true")]
+    pub verse_names: bool,
+
+    /// Whether to reject songs with lint issues when they are saved or imported
+    #[serde(default)]
+    pub enforce_on_save: bool,
+}
+
+impl Default for SongLintConfig {
+    fn default() -> Self {
+        Self {
+            max_line_length: default_max_line_length(),
+            max_lines_per_slide: default_max_lines_per_slide(),
+            forbidden_characters: default_forbidden_characters(),
+            trailing_whitespace: true,
+            empty_sections: true,
+            verse_names: true,
+            enforce_on_save: false,
+        }
+    }
+}
+
+fn default_max_line_length() -> Option<usize> {
+    Some(60)
+}
+fn default_max_lines_per_slide() -> Option<usize> {
+    Some(8)
+}
+fn default_forbidden_characters() -> String {
+    String::from("\t\u{a0}\u{fffd}")
+}
+fn default_true() -> bool {
+    true
+}
+
+/// Lint rule
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    TrailingWhitespace,
+    LineTooLong,
+    TooManyLines,
+    ForbiddenCharacter,
+    EmptySection,
+    VerseName,
+}
+
+/// Lint issue found in a song
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LintIssue {
+    /// Song title
+    pub title: String,
+    /// Verse name (with the language for translations, e.g. `es/1`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verse: Option<String>,
+    /// Line number in the verse (starting at 1)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub rule: LintRule,
+    pub message: String,
+}
+
+/// Lints every song in a song library
+pub fn lint_library(library: &SongLibrary, config: &SongLintConfig) -> Vec<LintIssue> {
+    library
+        .iter()
+        .flat_map(|(title, song)| lint_song(title, song, config))
+        .collect()
+}
+
+/// Lints the songs in a song library that are new or different to the original library,
+/// so that songs that were already there don't stop other songs from being saved
+pub fn lint_changes(
+    original: &SongLibrary,
+    library: &SongLibrary,
+    config: &SongLintConfig,
+) -> Vec<LintIssue> {
+    library
+        .iter()
+        .filter(|(title, song)| original.get(*title) != Some(*song))
+        .flat_map(|(title, song)| lint_song(title, song, config))
+        .collect()
+}
+
+/// Lints a song's verses and translations
+pub fn lint_song(title: &str, song: &Song, config: &SongLintConfig) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    if config.verse_names {
+        lint_verse_names(title, song, &mut issues);
+    }
+
+    for (name, text) in &song.verses {
+        lint_verse(title, name, text, config, &mut issues);
+    }
+    for (language, verses) in &song.translations {
+        for (name, text) in verses {
+            let name = format!("{}/{}", language, name);
+            lint_verse(title, &name, text, config, &mut issues);
+        }
+    }
+
+    issues
+}
+
+/// Checks that verse names are sorted as expected by the client:
+/// letters followed by an optional number (e.g. `1`, `c`, `c2`), and not only different in case
+fn lint_verse_names(title: &str, song: &Song, issues: &mut Vec<LintIssue>) {
+    let mut lowercase_names = BTreeMap::new();
+    for name in song.verses.keys() {
+        let letters_end = name
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(name.len());
+        if name.is_empty() || !name[letters_end..].chars().all(|c| c.is_ascii_digit()) {
+            issues.push(LintIssue {
+                title: String::from(title),
+                verse: Some(name.clone()),
+                line: None,
+                rule: LintRule::VerseName,
+                message: format!(
+                    "Verse name \"{}\" should be letters followed by a number (e.g. 1, c, c2)",
+                    name
+                ),
+            });
+        }
+
+        if let Some(other) = lowercase_names.insert(name.to_lowercase(), name) {
+            issues.push(LintIssue {
+                title: String::from(title),
+                verse: Some(name.clone()),
+                line: None,
+                rule: LintRule::VerseName,
+                message: format!(
+                    "Verse names \"{}\" and \"{}\" are only different in case",
+                    other, name
+                ),
+            });
+        }
+    }
+}
+
+/// Lints the lines of a verse
+fn lint_verse(
+    title: &str,
+    name: &str,
+    text: &str,
+    config: &SongLintConfig,
+    issues: &mut Vec<LintIssue>,
+) {
+    let issue = |line: Option<usize>, rule: LintRule, message: String| LintIssue {
+        title: String::from(title),
+        verse: Some(String::from(name)),
+        line,
+        rule,
+        message,
+    };
+
+    let stripped = chords::strip(text);
+    if stripped.trim().is_empty() {
+        if config.empty_sections {
+            issues.push(issue(
+                None,
+                LintRule::EmptySection,
+                String::from("Verse is empty"),
+            ));
+        }
+        return;
+    }
+
+    // slide split markers start a new slide
+    let line_count = split_at_markers(&stripped)
+        .iter()
+        .map(Vec::len)
+        .max()
+        .unwrap_or_default();
+    if let Some(max_lines) = config.max_lines_per_slide {
+        if line_count > max_lines {
+            issues.push(issue(
+                None,
+                LintRule::TooManyLines,
+                format!(
+                    "Verse has {} lines on a slide, more than the {} that fit",
+                    line_count, max_lines
+                ),
+            ));
+        }
+    }
+
+    for (index, (line, stripped_line)) in text.lines().zip(stripped.lines()).enumerate() {
+        let line_number = Some(index + 1);
+
+        if config.trailing_whitespace && line.trim() != line && !line.trim().is_empty() {
+            issues.push(issue(
+                line_number,
+                LintRule::TrailingWhitespace,
+                String::from("Line starts or ends with whitespace"),
+            ));
+        }
+
+        if let Some(max_length) = config.max_line_length {
+            let length = stripped_line.trim().chars().count();
+            if length > max_length {
+                issues.push(issue(
+                    line_number,
+                    LintRule::LineTooLong,
+                    format!(
+                        "Line is {} characters long, longer than {}",
+                        length, max_length
+                    ),
+                ));
+            }
+        }
+
+        if let Some(forbidden) = line
+            .chars()
+            .find(|c| config.forbidden_characters.contains(*c))
+        {
+            issues.push(issue(
+                line_number,
+                LintRule::ForbiddenCharacter,
+                format!("Line contains the character {:?}", forbidden),
+            ));
+        }
+    }
+}
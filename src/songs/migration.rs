@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    models::{Song, SongLibrary},
+    validation::{validate_library, ValidationProblem},
+};
+
+/// Results of migrating the song library to the current song format
+#[derive(Default, Serialize, Deserialize)]
+pub struct MigrationReport {
+    /// Whether this is a dry run (the song library was not changed)
+    pub dry_run: bool,
+    /// Titles of songs that were changed
+    pub migrated: Vec<String>,
+    /// Problems with songs that need to be fixed by hand
+    pub problems: Vec<ValidationProblem>,
+}
+
+/// Prefixes used before author names in attribution text
+const AUTHOR_PREFIXES: &[&str] = &[
+    "words and music by",
+    "words & music by",
+    "words and music:",
+    "words & music:",
+    "lyrics by",
+    "music by",
+    "words by",
+    "words:",
+    "music:",
+    "by ",
+];
+
+/// Moves metadata in older songs into the current song fields.
+/// Songs that only have attribution text get authors, copyright and CCLI number from it.
+pub fn migrate_library(library: &mut SongLibrary, report: &mut MigrationReport) {
+    for (title, song) in library.iter_mut() {
+        if migrate_song(song) {
+            report.migrated.push(title.clone());
+        }
+    }
+    report.problems = validate_library(library);
+}
+
+/// Migrates a single song, returning whether it was changed
+pub fn migrate_song(song: &mut Song) -> bool {
+    let original = song.clone();
+
+    // verse orders from older imports may include verses that were never added
+    let verses = &song.verses;
+    song.verse_order.retain(|name| verses.contains_key(name));
+    for arrangement in song.arrangements.values_mut() {
+        arrangement.retain(|name| verses.contains_key(name));
+    }
+    song.arrangements
+        .retain(|_, arrangement| !arrangement.is_empty());
+
+    if let Some(attribution) = song.attribution.clone() {
+        let (attribution, ccli_number) = split_ccli_number(&attribution);
+        let (authors, copyright) = split_copyright(&attribution);
+
+        if song.ccli_number.is_none() {
+            song.ccli_number = ccli_number;
+        }
+        if song.authors.is_empty() && song.copyright.is_none() {
+            if authors.eq_ignore_ascii_case("public domain") {
+                song.copyright = Some(authors);
+            } else {
+                song.authors = split_authors(&authors);
+                song.copyright = copyright;
+            }
+        }
+    }
+
+    *song != original
+}
+
+/// Removes a CCLI song number (e.g. `CCLI Song # 22025`) from attribution text
+fn split_ccli_number(attribution: &str) -> (String, Option<String>) {
+    let Some(start) = attribution.find("CCLI") else {
+        return (String::from(attribution), None);
+    };
+
+    let rest = &attribution[start + 4..];
+    let number_start = rest.find(|c: char| c.is_ascii_digit()).filter(|index| {
+        rest[..*index]
+            .chars()
+            .all(|c| c.is_whitespace() || c.is_alphabetic() || matches!(c, '#' | '.' | ':'))
+    });
+    let Some(number_start) = number_start else {
+        return (String::from(attribution), None);
+    };
+    let number_end = rest[number_start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(rest.len(), |index| number_start + index);
+
+    let remaining = format!("{} {}", &attribution[..start], &rest[number_end..]);
+    (
+        String::from(remaining.trim()),
+        Some(String::from(&rest[number_start..number_end])),
+    )
+}
+
+/// Splits attribution text at the copyright symbol (or word) into authors and copyright
+fn split_copyright(attribution: &str) -> (String, Option<String>) {
+    let lower = attribution.to_ascii_lowercase();
+    let marker = ["©", "(c)", "copyright"]
+        .iter()
+        .filter_map(|marker| lower.find(marker).map(|index| (index, marker.len())))
+        .min();
+
+    match marker {
+        Some((index, length)) => {
+            let copyright = attribution[index + length..]
+                .trim()
+                .trim_start_matches(':')
+                .trim();
+            (
+                String::from(attribution[..index].trim()),
+                Some(String::from(copyright)).filter(|copyright| !copyright.is_empty()),
+            )
+        }
+        None => (String::from(attribution.trim()), None),
+    }
+}
+
+/// Splits author names from attribution text (separated by commas, `&`, `and`, `;` or `|`)
+fn split_authors(authors: &str) -> Vec<String> {
+    strip_author_prefix(authors)
+        .split([',', '&', ';', '|'])
+        .flat_map(|part| part.split(" and "))
+        .map(|author| String::from(strip_author_prefix(author)))
+        .filter(|author| !author.is_empty())
+        .collect()
+}
+
+/// Removes a prefix like `Words by` from the start of author names
+fn strip_author_prefix(authors: &str) -> &str {
+    let authors = authors.trim();
+    let lower = authors.to_ascii_lowercase();
+    AUTHOR_PREFIXES
+        .iter()
+        .find(|prefix| lower.starts_with(*prefix))
+        .map_or(authors, |prefix| authors[prefix.len()..].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::{
+        config::file::AppConfig,
+        content::service::ContentService,
+        database::Database,
+        songs::service::{SongService, SONGS_FILE},
+    };
+
+    /// Song library in the original `{title: {verses, attribution}}` format
+    fn baseline_library() -> serde_json::Value {
+        json!({
+            "Amazing Grace": {
+                "verses": { "1": "Amazing grace", "2": "'Twas grace" },
+                "attribution": "John Newton © Public Domain CCLI Song # 22025",
+            },
+            "Blessed Be Your Name": {
+                "verses": { "v1": "Blessed be Your name" },
+                "attribution": "Words and music by Matt Redman & Beth Redman (c) 2002 Thankyou Music",
+            },
+            "Empty Verse": {
+                "verses": { " ": "Verse without a name" },
+            },
+            "Holy, Holy, Holy": {
+                "verses": { "1": "Holy, holy, holy" },
+                "attribution": "Public Domain",
+            },
+            "Old Import": {
+                "verses": { "v1": "Verse", "c": "Chorus" },
+                "verseOrder": ["v1", "c", "v2", "c"],
+                "arrangements": { "Full": ["v1", "v2"], "Missing": ["b"] },
+            },
+            "Plain": {
+                "verses": { "1": "Nothing to migrate" },
+            },
+        })
+    }
+
+    #[test]
+    fn migrates_a_baseline_library() {
+        let mut library: SongLibrary = serde_json::from_value(baseline_library()).unwrap();
+        let mut report = MigrationReport::default();
+        migrate_library(&mut library, &mut report);
+
+        assert_eq!(
+            report.migrated,
+            [
+                "Amazing Grace",
+                "Blessed Be Your Name",
+                "Holy, Holy, Holy",
+                "Old Import"
+            ]
+        );
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].title, "Empty Verse");
+        assert_eq!(report.problems[0].field, "verses");
+
+        let song = &library["Amazing Grace"];
+        assert_eq!(song.authors, ["John Newton"]);
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+        assert_eq!(song.ccli_number.as_deref(), Some("22025"));
+        // the attribution text is kept for older clients
+        assert_eq!(
+            song.attribution.as_deref(),
+            Some("John Newton © Public Domain CCLI Song # 22025")
+        );
+
+        let song = &library["Blessed Be Your Name"];
+        assert_eq!(song.authors, ["Matt Redman", "Beth Redman"]);
+        assert_eq!(song.copyright.as_deref(), Some("2002 Thankyou Music"));
+        assert_eq!(song.ccli_number, None);
+
+        let song = &library["Holy, Holy, Holy"];
+        assert!(song.authors.is_empty());
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+
+        let song = &library["Old Import"];
+        assert_eq!(song.verse_order, ["v1", "c", "c"]);
+        assert_eq!(song.arrangements.len(), 1);
+        assert_eq!(song.arrangements["Full"], ["v1"]);
+
+        // migrating again changes nothing
+        let mut report = MigrationReport::default();
+        migrate_library(&mut library, &mut report);
+        assert!(report.migrated.is_empty());
+    }
+
+    #[test]
+    fn keeps_existing_metadata() {
+        let mut song: Song = serde_json::from_value(json!({
+            "verses": { "1": "Amazing grace" },
+            "attribution": "John Newton CCLI 22025",
+            "authors": ["J. Newton"],
+            "ccliNumber": "4755360",
+        }))
+        .unwrap();
+        assert!(!migrate_song(&mut song));
+        assert_eq!(song.authors, ["J. Newton"]);
+        assert_eq!(song.ccli_number.as_deref(), Some("4755360"));
+    }
+
+    #[test]
+    fn reports_dry_runs_without_changing_the_library() {
+        let root = std::env::temp_dir().join(format!("songs-{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let config: AppConfig = serde_json::from_value(json!({
+            "content_directory": root.join("content"),
+            "content_trash_directory": root.join("trash"),
+        }))
+        .unwrap();
+        fs::create_dir_all(&config.content_directory).unwrap();
+        let songs_path = root.join("content").join(SONGS_FILE);
+        let baseline = baseline_library().to_string();
+        fs::write(&songs_path, &baseline).unwrap();
+
+        let content = ContentService::new(&config, &Database::in_memory());
+        let song_service = SongService::new(&config);
+
+        let report = song_service.migrate(&content, true, None).unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.migrated.len(), 4);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(fs::read_to_string(&songs_path).unwrap(), baseline);
+
+        let report = song_service.migrate(&content, false, None).unwrap();
+        assert!(!report.dry_run);
+        assert_eq!(report.migrated.len(), 4);
+        let library = song_service.load(&content).unwrap();
+        assert_eq!(library["Amazing Grace"].authors, ["John Newton"]);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,16 @@
+pub mod api;
+pub mod chordpro;
+pub mod chords;
+pub mod duplicates;
+pub mod export;
+pub mod import;
+pub mod lint;
+pub mod migration;
+pub mod models;
+pub mod openlp;
+pub mod openlyrics;
+pub mod service;
+pub mod slides;
+pub mod songselect;
+pub mod transpose;
+pub mod validation;
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+
+use crate::helpers::sort::natcasecmp;
+
+use super::chords::VerseChords;
+
+/// Song library, mapping song titles to songs
+pub type SongLibrary = BTreeMap<String, Song>;
+
+/// Song as stored in the song library
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Song {
+    /// Verse names mapped to verse text
+    #[serde(default)]
+    pub verses: BTreeMap<String, String>,
+    /// Attribution text shown with the song
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attribution: Option<String>,
+    /// Other titles the song is known by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternate_titles: Vec<String>,
+    /// Authors (words, music and translation)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    /// Copyright notice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub copyright: Option<String>,
+    /// CCLI song number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ccli_number: Option<String>,
+    /// Default order to sing the verses in (verse names)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verse_order: Vec<String>,
+    /// Named arrangements, mapping arrangement names to the verse names to sing in order
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub arrangements: BTreeMap<String, Vec<String>>,
+    /// Language of the verses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Translated verses, mapping languages to verse names to verse text
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub translations: BTreeMap<String, BTreeMap<String, String>>,
+    /// Chords of each verse, kept separate from the verse text so that they aren't shown on displays
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub chords: VerseChords,
+    /// Musical key the chords are written in (e.g. `G`, `Em`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// Tempo in beats per minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tempo: Option<u32>,
+    /// Song books the song appears in
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub songbooks: Vec<SongbookEntry>,
+    /// Themes (topics) the song is about
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub themes: Vec<String>,
+    /// Notes about the song (not shown on displays)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Any other fields (kept so that they aren't lost when the song is saved)
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Song {
+    /// Gets the verse names to sing in order for an arrangement.
+    /// Without an arrangement name this is the verse order, or all verses sorted by name if there isn't one.
+    pub fn arrangement(&self, name: Option<&str>) -> Option<Vec<String>> {
+        match name {
+            Some(name) => self.arrangements.get(name).cloned(),
+            None if !self.verse_order.is_empty() => Some(self.verse_order.clone()),
+            None => {
+                let mut names: Vec<_> = self.verses.keys().cloned().collect();
+                names.sort_by(|a, b| natcasecmp(a, b));
+                Some(names)
+            }
+        }
+    }
+
+    /// Generates attribution text from the authors and copyright
+    pub fn default_attribution(&self) -> Option<String> {
+        let authors = self.authors.join(", ");
+        let copyright = self.copyright.as_deref().map(|copyright| {
+            if copyright.starts_with('©') {
+                String::from(copyright)
+            } else {
+                format!("© {}", copyright)
+            }
+        });
+
+        match (authors.is_empty(), copyright) {
+            (true, None) => None,
+            (true, Some(copyright)) => Some(copyright),
+            (false, None) => Some(authors),
+            (false, Some(copyright)) => Some(format!("{} {}", authors, copyright)),
+        }
+    }
+}
+
+/// Song book a song appears in
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SongbookEntry {
+    /// Song book name
+    pub name: String,
+    /// Song number in the song book
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+}
+
+/// Serializes a song library in the same order the client saves it in
+/// (songs and song fields sorted naturally)
+pub struct SortedSongLibrary<'a>(pub &'a SongLibrary);
+
+impl Serialize for SortedSongLibrary<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut titles: Vec<_> = self.0.keys().collect();
+        titles.sort_by(|a, b| natcasecmp(a, b));
+
+        let mut map = serializer.serialize_map(Some(titles.len()))?;
+        for title in titles {
+            // converting to a value sorts the song fields
+            let song = serde_json::to_value(&self.0[title]).map_err(serde::ser::Error::custom)?;
+            let mut fields: Vec<_> = match song {
+                Value::Object(fields) => fields.into_iter().collect(),
+                _ => Vec::new(),
+            };
+            fields.sort_by(|(a, _), (b, _)| natcasecmp(a, b));
+
+            map.serialize_entry(title, &SortedFields(fields))?;
+        }
+        map.end()
+    }
+}
+
+/// Serializes object fields in the order they are given in
+struct SortedFields(Vec<(String, Value)>);
+
+impl Serialize for SortedFields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Song with its title, used when creating or updating a song
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TitledSong {
+    /// Song title (used as the song ID)
+    pub title: String,
+    #[serde(flatten)]
+    pub song: Song,
+}
@@ -0,0 +1,451 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use roxmltree::Document;
+use rusqlite::{Connection, OpenFlags};
+use uuid::Uuid;
+
+use super::{
+    chords::{self, VerseChords},
+    import::{clean_verse_text, ImportReport, ImportedSong},
+    models::{Song, SongbookEntry},
+    openlyrics::convert_verse_name,
+};
+
+/// Reads the songs from OpenLP song databases (`songs.sqlite`),
+/// recording any songs that couldn't be read in the import report.
+/// SQLite can only open files, so each database is written to a temporary file first.
+pub fn import(files: Vec<(String, Vec<u8>)>, report: &mut ImportReport) -> Vec<ImportedSong> {
+    let mut songs = Vec::new();
+    for (source, bytes) in files {
+        let temp_path = std::env::temp_dir().join(format!("openlp-{}.sqlite", Uuid::new_v4()));
+        if let Err(err) = fs::write(&temp_path, bytes) {
+            report.error(&source, format!("Failed to write temporary file: {}", err));
+            continue;
+        }
+
+        songs.extend(import_file(&temp_path, &source, report));
+        let _ = fs::remove_file(&temp_path);
+    }
+    songs
+}
+
+/// Reads the songs from an OpenLP song database file
+fn import_file(path: &Path, source: &str, report: &mut ImportReport) -> Vec<ImportedSong> {
+    let conn = match Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    ) {
+        Ok(conn) => conn,
+        Err(err) => {
+            report.error(source, format!("Failed to open database: {}", err));
+            return Vec::new();
+        }
+    };
+
+    match read_songs(&conn, source, report) {
+        Ok(songs) => songs,
+        Err(err) => {
+            report.error(source, format!("Not an OpenLP song database: {}", err));
+            Vec::new()
+        }
+    }
+}
+
+/// Song row from the OpenLP `songs` table
+struct SongRow {
+    id: i64,
+    title: String,
+    alternate_title: Option<String>,
+    lyrics: String,
+    verse_order: Option<String>,
+    copyright: Option<String>,
+    comments: Option<String>,
+    ccli_number: Option<String>,
+}
+
+/// Reads all songs and their related authors, song books and topics
+fn read_songs(
+    conn: &Connection,
+    source: &str,
+    report: &mut ImportReport,
+) -> Result<Vec<ImportedSong>, rusqlite::Error> {
+    let rows: Vec<SongRow> = conn
+        .prepare(
+            "
+            SELECT id, title, alternate_title, lyrics, verse_order, copyright, comments, ccli_number
+            FROM songs
+            ORDER BY id
+            ",
+        )?
+        .query_map([], |row| {
+            Ok(SongRow {
+                id: row.get(0)?,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                alternate_title: row.get(2)?,
+                lyrics: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                verse_order: row.get(4)?,
+                copyright: row.get(5)?,
+                comments: row.get(6)?,
+                ccli_number: row.get(7)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut authors = read_authors(conn)?;
+    let mut songbooks = read_songbooks(conn)?;
+    let mut topics = read_topics(conn)?;
+
+    let mut songs = Vec::new();
+    for row in rows {
+        let title = row.title.trim().to_string();
+        let song_source = format!("{}#{}", source, row.id);
+        if title.is_empty() {
+            report.error(&song_source, "Song has no title");
+            continue;
+        }
+
+        let verses = match parse_lyrics(&row.lyrics) {
+            Ok(verses) => verses,
+            Err(err) => {
+                report.error(&song_source, err);
+                continue;
+            }
+        };
+
+        // OpenLP 2.4 and later store chords inline in the lyrics
+        let mut chords = VerseChords::new();
+        let verses = verses
+            .into_iter()
+            .map(|(name, text)| {
+                let (text, annotations) = chords::extract(&text);
+                if !annotations.is_empty() {
+                    chords.insert(name.clone(), annotations);
+                }
+                (name, text)
+            })
+            .collect();
+
+        let song = Song {
+            verses,
+            chords,
+            alternate_titles: non_empty(row.alternate_title)
+                .filter(|alternate| *alternate != title)
+                .into_iter()
+                .collect(),
+            authors: authors.remove(&row.id).unwrap_or_default(),
+            copyright: non_empty(row.copyright),
+            ccli_number: non_empty(row.ccli_number),
+            verse_order: row
+                .verse_order
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(|name| convert_verse_name(&name.to_lowercase()))
+                .collect(),
+            songbooks: songbooks.remove(&row.id).unwrap_or_default(),
+            themes: topics.remove(&row.id).unwrap_or_default(),
+            notes: non_empty(row.comments),
+            ..Default::default()
+        };
+
+        songs.push(ImportedSong {
+            source: song_source,
+            title,
+            song,
+        });
+    }
+
+    Ok(songs)
+}
+
+/// Reads the authors of each song, in the order they were added
+fn read_authors(conn: &Connection) -> Result<HashMap<i64, Vec<String>>, rusqlite::Error> {
+    let mut authors: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut statement = conn.prepare(
+        "
+        SELECT authors_songs.song_id, authors.display_name, authors.first_name, authors.last_name
+        FROM authors_songs
+        JOIN authors ON authors.id = authors_songs.author_id
+        ORDER BY authors_songs.rowid
+        ",
+    )?;
+    let rows = statement.query_map([], |row| {
+        let display_name = non_empty(row.get(1)?);
+        let full_name = [row.get::<_, Option<String>>(2)?, row.get(3)?]
+            .into_iter()
+            .flatten()
+            .map(|name| String::from(name.trim()))
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok((row.get(0)?, display_name.or(non_empty(Some(full_name)))))
+    })?;
+
+    for row in rows {
+        let (song_id, name) = row?;
+        if let Some(name) = name {
+            let song_authors = authors.entry(song_id).or_default();
+            // the same author can be listed once per author type (words, music...)
+            if !song_authors.contains(&name) {
+                song_authors.push(name);
+            }
+        }
+    }
+
+    Ok(authors)
+}
+
+/// Reads the song books each song appears in.
+/// OpenLP 2.4 and later link songs to song books in `songs_songbooks`,
+/// older versions store a single song book and number on the song.
+fn read_songbooks(conn: &Connection) -> Result<HashMap<i64, Vec<SongbookEntry>>, rusqlite::Error> {
+    let query = if has_table(conn, "songs_songbooks")? {
+        "
+        SELECT songs_songbooks.song_id, song_books.name, songs_songbooks.entry
+        FROM songs_songbooks
+        JOIN song_books ON song_books.id = songs_songbooks.songbook_id
+        ORDER BY songs_songbooks.rowid
+        "
+    } else {
+        "
+        SELECT songs.id, song_books.name, songs.song_number
+        FROM songs
+        JOIN song_books ON song_books.id = songs.song_book_id
+        "
+    };
+
+    let mut songbooks: HashMap<i64, Vec<SongbookEntry>> = HashMap::new();
+    let mut statement = conn.prepare(query)?;
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            non_empty(row.get(1)?),
+            // song numbers are text but may have been stored as integers
+            row.get::<_, rusqlite::types::Value>(2)?,
+        ))
+    })?;
+
+    for row in rows {
+        let (song_id, name, entry) = row?;
+        let Some(name) = name else {
+            continue;
+        };
+        let entry = match entry {
+            rusqlite::types::Value::Integer(number) => Some(number.to_string()),
+            rusqlite::types::Value::Text(text) => non_empty(Some(text)),
+            _ => None,
+        };
+        songbooks
+            .entry(song_id)
+            .or_default()
+            .push(SongbookEntry { name, entry });
+    }
+
+    Ok(songbooks)
+}
+
+/// Reads the topics of each song
+fn read_topics(conn: &Connection) -> Result<HashMap<i64, Vec<String>>, rusqlite::Error> {
+    let mut topics: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut statement = conn.prepare(
+        "
+        SELECT songs_topics.song_id, topics.name
+        FROM songs_topics
+        JOIN topics ON topics.id = songs_topics.topic_id
+        ORDER BY topics.name
+        ",
+    )?;
+    let rows = statement.query_map([], |row| Ok((row.get(0)?, non_empty(row.get(1)?))))?;
+
+    for row in rows {
+        if let (song_id, Some(name)) = row? {
+            topics.entry(song_id).or_default().push(name);
+        }
+    }
+
+    Ok(topics)
+}
+
+/// Checks whether a table exists in the database
+fn has_table(conn: &Connection, name: &str) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Parses OpenLP song lyrics into verse names and verse text.
+///
+/// Lyrics are normally stored as XML (`<song><lyrics><verse type="v" label="1">`),
+/// very old databases store plain text with verses separated by blank lines.
+fn parse_lyrics(lyrics: &str) -> Result<Vec<(String, String)>, String> {
+    if !lyrics.trim_start().starts_with('<') {
+        return Ok(lyrics
+            .split("\n\n")
+            .map(|verse| clean_verse_text(&strip_formatting(verse)))
+            .filter(|verse| !verse.is_empty())
+            .enumerate()
+            .map(|(index, verse)| ((index + 1).to_string(), verse))
+            .collect());
+    }
+
+    let document = Document::parse(lyrics).map_err(|err| format!("Invalid lyrics XML: {}", err))?;
+
+    let mut verses: Vec<(String, String)> = Vec::new();
+    for verse in document
+        .descendants()
+        .filter(|node| node.has_tag_name("verse"))
+    {
+        let verse_type = verse.attribute("type").unwrap_or("v").to_lowercase();
+        let label = verse.attribute("label").unwrap_or("1");
+        let name = convert_verse_name(&format!("{}{}", verse_type, label));
+        let text = clean_verse_text(&strip_formatting(&verse_text(verse)));
+
+        // verses with the same name are combined
+        match verses.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => {
+                existing.push('\n');
+                existing.push_str(&text);
+            }
+            None => verses.push((name, text)),
+        }
+    }
+
+    Ok(verses)
+}
+
+/// Gets all the text (including CDATA sections) in a verse element
+fn verse_text(verse: roxmltree::Node) -> String {
+    verse
+        .descendants()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect()
+}
+
+/// Removes OpenLP formatting tags (e.g. `{st}`, `{/st}`) and slide split markers (`[---]`, `[===]`)
+fn strip_formatting(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start..];
+        match after.find('}') {
+            Some(end) if is_formatting_tag(&after[1..end]) => rest = &after[end + 1..],
+            _ => {
+                result.push('{');
+                rest = &after[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+
+    result
+        .lines()
+        .filter(|line| !matches!(line.trim(), "[---]" | "[===]"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Checks whether text between braces is an OpenLP formatting tag name
+fn is_formatting_tag(tag: &str) -> bool {
+    let tag = tag.strip_prefix('/').unwrap_or(tag);
+    !tag.is_empty() && tag.len() <= 10 && tag.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Trims optional text, treating empty text as missing
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.map(|text| String::from(text.trim()))
+        .filter(|text| !text.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an in-memory OpenLP 2.4 song database with one song
+    fn database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE songs (
+                id INTEGER PRIMARY KEY, title TEXT, alternate_title TEXT, lyrics TEXT,
+                verse_order TEXT, copyright TEXT, comments TEXT, ccli_number TEXT
+            );
+            CREATE TABLE authors (id INTEGER PRIMARY KEY, first_name TEXT, last_name TEXT, display_name TEXT);
+            CREATE TABLE authors_songs (author_id INTEGER, song_id INTEGER, author_type TEXT);
+            CREATE TABLE song_books (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE songs_songbooks (songbook_id INTEGER, song_id INTEGER, entry TEXT);
+            CREATE TABLE topics (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE songs_topics (song_id INTEGER, topic_id INTEGER);
+
+            INSERT INTO songs VALUES (
+                1, 'Amazing Grace', 'New Britain',
+                '<?xml version="1.0" encoding="UTF-8"?><song version="1.0"><lyrics>
+                <verse type="v" label="1"><![CDATA[{st}Amazing{/st} [G]grace
+[---]
+how sweet the sound]]></verse>
+                <verse type="c" label="1"><![CDATA[My chains are gone]]></verse>
+                </lyrics></song>',
+                'V1 C1 V1', 'Public Domain', 'Traditional', '22025'
+            );
+            INSERT INTO songs VALUES (2, '  ', NULL, '', NULL, NULL, NULL, NULL);
+            INSERT INTO authors VALUES (1, 'John', 'Newton', '');
+            INSERT INTO authors_songs VALUES (1, 1, 'words'), (1, 1, 'music');
+            INSERT INTO song_books VALUES (1, 'Hymnal');
+            INSERT INTO songs_songbooks VALUES (1, 1, '42');
+            INSERT INTO topics VALUES (1, 'Grace');
+            INSERT INTO songs_topics VALUES (1, 1);
+            "#,
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn reads_songs() {
+        let mut report = ImportReport::default();
+        let songs = read_songs(&database(), "songs.sqlite", &mut report).unwrap();
+
+        assert_eq!(songs.len(), 1);
+        assert_eq!(report.errors.len(), 1);
+        let ImportedSong {
+            source,
+            title,
+            song,
+        } = &songs[0];
+        assert_eq!(source, "songs.sqlite#1");
+        assert_eq!(title, "Amazing Grace");
+        assert_eq!(song.verses["1"], "Amazing grace\nhow sweet the sound");
+        assert_eq!(song.verses["c1"], "My chains are gone");
+        assert_eq!(song.chords["1"][0].chord, "G");
+        assert_eq!(song.verse_order, ["1", "c1", "1"]);
+        assert_eq!(song.alternate_titles, ["New Britain"]);
+        assert_eq!(song.authors, ["John Newton"]);
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+        assert_eq!(song.ccli_number.as_deref(), Some("22025"));
+        assert_eq!(song.songbooks[0].name, "Hymnal");
+        assert_eq!(song.songbooks[0].entry.as_deref(), Some("42"));
+        assert_eq!(song.themes, ["Grace"]);
+        assert_eq!(song.notes.as_deref(), Some("Traditional"));
+    }
+
+    #[test]
+    fn parses_plain_text_lyrics() {
+        let verses = parse_lyrics("First verse\n\nSecond {it}verse{/it}\n").unwrap();
+
+        assert_eq!(
+            verses,
+            [
+                (String::from("1"), String::from("First verse")),
+                (String::from("2"), String::from("Second verse")),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_braces_that_are_not_formatting_tags() {
+        assert_eq!(strip_formatting("{b}x{/b} {not a tag}"), "x {not a tag}");
+    }
+}
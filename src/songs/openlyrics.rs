@@ -0,0 +1,570 @@
+use std::collections::BTreeMap;
+
+use chrono::Local;
+use roxmltree::{Document, Node};
+
+use crate::{content::encoding, helpers::sort::natcasecmp};
+
+use super::{
+    chords::{self, ChordAnnotation},
+    import::{clean_verse_text, ImportReport, ImportedSong},
+    models::{Song, SongbookEntry},
+};
+
+/// Parses OpenLyrics files, recording any files that couldn't be parsed in the import report
+pub fn import(files: Vec<(String, Vec<u8>)>, report: &mut ImportReport) -> Vec<ImportedSong> {
+    let mut songs = Vec::new();
+    for (source, bytes) in files {
+        match parse(&encoding::decode(&bytes).text) {
+            Ok((title, song)) => songs.push(ImportedSong {
+                source,
+                title,
+                song,
+            }),
+            Err(err) => report.error(&source, err),
+        }
+    }
+    songs
+}
+
+/// OpenLyrics XML namespace
+const NAMESPACE: &str = "http://openlyrics.info/namespace/2009/song";
+
+/// Parses an OpenLyrics XML document into a song title and song.
+///
+/// Verses in the same language as the first verse become the song verses (with their chords as annotations),
+/// verses in other languages become translations (with their chords inline).
+pub fn parse(xml: &str) -> Result<(String, Song), String> {
+    let document = Document::parse(xml).map_err(|err| format!("Invalid XML: {}", err))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "song" {
+        return Err(String::from("Not an OpenLyrics song"));
+    }
+
+    let mut song = Song::default();
+
+    let properties = child(root, "properties");
+
+    // first title is the main title, others are alternate titles
+    let mut titles = properties
+        .and_then(|properties| child(properties, "titles"))
+        .map(|titles| children_text(titles, "title"))
+        .unwrap_or_default()
+        .into_iter();
+    let title = titles
+        .next()
+        .ok_or_else(|| String::from("Song has no title"))?;
+    song.alternate_titles = titles.filter(|alternate| *alternate != title).collect();
+
+    if let Some(properties) = properties {
+        song.authors = child(properties, "authors")
+            .map(|authors| children_text(authors, "author"))
+            .unwrap_or_default();
+        song.copyright = child_text(properties, "copyright");
+        song.ccli_number = child_text(properties, "ccliNo");
+        song.key = child_text(properties, "key");
+        song.tempo = child(properties, "tempo")
+            .filter(|tempo| tempo.attribute("type").is_none_or(|kind| kind == "bpm"))
+            .and_then(|tempo| tempo.text())
+            .and_then(|tempo| tempo.trim().parse().ok());
+        song.verse_order = child_text(properties, "verseOrder")
+            .map(|verse_order| {
+                verse_order
+                    .split_whitespace()
+                    .map(convert_verse_name)
+                    .collect()
+            })
+            .unwrap_or_default();
+        song.songbooks = child(properties, "songbooks")
+            .map(|songbooks| {
+                songbooks
+                    .children()
+                    .filter(|node| node.has_tag_name("songbook"))
+                    .filter_map(|songbook| {
+                        Some(SongbookEntry {
+                            name: String::from(songbook.attribute("name")?.trim()),
+                            entry: songbook
+                                .attribute("entry")
+                                .map(|entry| String::from(entry.trim()))
+                                .filter(|entry| !entry.is_empty()),
+                        })
+                    })
+                    .filter(|songbook| !songbook.name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        song.themes = child(properties, "themes")
+            .map(|themes| children_text(themes, "theme"))
+            .unwrap_or_default();
+        song.notes = child(properties, "comments")
+            .map(|comments| children_text(comments, "comment").join("\n"))
+            .filter(|notes| !notes.is_empty());
+    }
+
+    let verses = child(root, "lyrics")
+        .map(|lyrics| {
+            lyrics
+                .children()
+                .filter(|node| node.has_tag_name("verse"))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    song.language = verses
+        .first()
+        .and_then(|verse| verse.attribute("lang"))
+        .map(String::from);
+
+    for verse in verses {
+        let Some(name) = verse.attribute("name") else {
+            continue;
+        };
+        let name = convert_verse_name(name);
+
+        let text = clean_verse_text(
+            &verse
+                .children()
+                .filter(|node| node.has_tag_name("lines"))
+                .map(lines_text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        );
+
+        match verse.attribute("lang") {
+            Some(language) if Some(language) != song.language.as_deref() => {
+                song.translations
+                    .entry(String::from(language))
+                    .or_insert_with(BTreeMap::new)
+                    .insert(name, text);
+            }
+            _ => {
+                let (text, annotations) = chords::extract(&text);
+                if !annotations.is_empty() {
+                    song.chords.insert(name.clone(), annotations);
+                }
+                // chords past the end of a line are written after spaces
+                let text = text.lines().map(str::trim_end).collect::<Vec<_>>();
+                song.verses.insert(name, text.join("\n"));
+            }
+        }
+    }
+
+    Ok((title, song))
+}
+
+/// Converts an OpenLyrics verse name to the name used in the song library
+/// (verses are numbered without a prefix, e.g. "v1" becomes "1")
+pub fn convert_verse_name(name: &str) -> String {
+    match name.strip_prefix('v') {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => String::from(rest),
+        _ => String::from(name),
+    }
+}
+
+/// Converts a song library verse name to an OpenLyrics verse name
+/// (numbered verses get a "v" prefix, e.g. "1" becomes "v1")
+pub fn export_verse_name(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("v{}", name)
+    } else {
+        String::from(name)
+    }
+}
+
+/// Writes a song as an OpenLyrics 0.9 XML document
+pub fn write(title: &str, song: &Song) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<song xmlns=\"{}\" version=\"0.9\" createdIn=\"{} {}\" modifiedDate=\"{}\">\n",
+        NAMESPACE,
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        Local::now().format("%Y-%m-%dT%H:%M:%S")
+    ));
+
+    // properties
+    xml.push_str("  <properties>\n    <titles>\n");
+    for title in std::iter::once(title).chain(song.alternate_titles.iter().map(String::as_str)) {
+        xml.push_str(&format!("      <title>{}</title>\n", escape(title)));
+    }
+    xml.push_str("    </titles>\n");
+    if !song.authors.is_empty() {
+        xml.push_str("    <authors>\n");
+        for author in &song.authors {
+            xml.push_str(&format!("      <author>{}</author>\n", escape(author)));
+        }
+        xml.push_str("    </authors>\n");
+    }
+    if let Some(copyright) = &song.copyright {
+        xml.push_str(&format!(
+            "    <copyright>{}</copyright>\n",
+            escape(copyright)
+        ));
+    }
+    if let Some(ccli_number) = &song.ccli_number {
+        xml.push_str(&format!("    <ccliNo>{}</ccliNo>\n", escape(ccli_number)));
+    }
+    if let Some(tempo) = song.tempo {
+        xml.push_str(&format!("    <tempo type=\"bpm\">{}</tempo>\n", tempo));
+    }
+    if let Some(key) = &song.key {
+        xml.push_str(&format!("    <key>{}</key>\n", escape(key)));
+    }
+    if !song.verse_order.is_empty() {
+        let verse_order: Vec<_> = song
+            .verse_order
+            .iter()
+            .map(|name| export_verse_name(name))
+            .collect();
+        xml.push_str(&format!(
+            "    <verseOrder>{}</verseOrder>\n",
+            escape(&verse_order.join(" "))
+        ));
+    }
+    if !song.songbooks.is_empty() {
+        xml.push_str("    <songbooks>\n");
+        for songbook in &song.songbooks {
+            xml.push_str(&format!(
+                "      <songbook name=\"{}\"",
+                escape(&songbook.name)
+            ));
+            if let Some(entry) = &songbook.entry {
+                xml.push_str(&format!(" entry=\"{}\"", escape(entry)));
+            }
+            xml.push_str("/>\n");
+        }
+        xml.push_str("    </songbooks>\n");
+    }
+    if !song.themes.is_empty() {
+        xml.push_str("    <themes>\n");
+        for theme in &song.themes {
+            xml.push_str(&format!("      <theme>{}</theme>\n", escape(theme)));
+        }
+        xml.push_str("    </themes>\n");
+    }
+    if let Some(notes) = &song.notes {
+        xml.push_str("    <comments>\n");
+        for comment in notes.lines().filter(|line| !line.trim().is_empty()) {
+            xml.push_str(&format!("      <comment>{}</comment>\n", escape(comment)));
+        }
+        xml.push_str("    </comments>\n");
+    }
+    xml.push_str("  </properties>\n");
+
+    // lyrics (song verses first so that they are detected as the main language when imported)
+    xml.push_str("  <lyrics>\n");
+    write_verses(
+        &mut xml,
+        &song.verses,
+        &song.chords,
+        song.language.as_deref(),
+    );
+    for (language, verses) in &song.translations {
+        write_verses(&mut xml, verses, &BTreeMap::new(), Some(language));
+    }
+    xml.push_str("  </lyrics>\n</song>\n");
+
+    xml
+}
+
+/// Writes `<verse>` elements, with chord annotations and inline chords as `<chord>` elements
+fn write_verses(
+    xml: &mut String,
+    verses: &BTreeMap<String, String>,
+    verse_chords: &BTreeMap<String, Vec<ChordAnnotation>>,
+    language: Option<&str>,
+) {
+    let mut names: Vec<_> = verses.keys().collect();
+    names.sort_by(|a, b| natcasecmp(a, b));
+
+    for name in names {
+        xml.push_str(&format!(
+            "    <verse name=\"{}\"",
+            escape(&export_verse_name(name))
+        ));
+        if let Some(language) = language {
+            xml.push_str(&format!(" lang=\"{}\"", escape(language)));
+        }
+        let annotations = verse_chords.get(name).map_or(&[][..], Vec::as_slice);
+        let (text, annotations) = chords::extract(&chords::insert(&verses[name], annotations));
+        let lines: Vec<_> = text
+            .lines()
+            .enumerate()
+            .map(|(line_index, line)| {
+                let mut line_annotations: Vec<_> = annotations
+                    .iter()
+                    .filter(|annotation| annotation.line == line_index)
+                    .collect();
+                line_annotations.sort_by_key(|annotation| annotation.position);
+
+                let mut result = String::new();
+                let mut chars = line.chars();
+                let mut position = 0;
+                for annotation in line_annotations {
+                    let before: String = chars
+                        .by_ref()
+                        .take(annotation.position - position)
+                        .collect();
+                    result.push_str(&escape(&before));
+                    position = annotation.position;
+                    result.push_str(&format!("<chord name=\"{}\"/>", escape(&annotation.chord)));
+                }
+                result.push_str(&escape(&chars.collect::<String>()));
+                result
+            })
+            .collect();
+        xml.push_str(&format!(
+            ">\n      <lines>{}</lines>\n    </verse>\n",
+            lines.join("<br/>")
+        ));
+    }
+}
+
+/// Escapes text for use in XML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Gets the text of a `<lines>` element.
+/// Line breaks come from `<br/>` elements, all other whitespace is collapsed.
+/// Chords are written inline (`[G]`), other markup is dropped and comments are skipped.
+fn lines_text(lines: Node) -> String {
+    fn collect(node: Node, text: &mut String) {
+        for child in node.children() {
+            if child.is_text() {
+                // line breaks in the XML are just formatting
+                let value = child.text().unwrap_or_default();
+                if value.contains('\n') {
+                    // only the whitespace around the line breaks is collapsed,
+                    // so that spaces next to other elements are kept
+                    let parts: Vec<_> = value.split('\n').collect();
+                    let last = parts.len() - 1;
+                    let parts: Vec<_> = parts
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, part)| {
+                            let part = if index > 0 { part.trim_start() } else { part };
+                            if index < last {
+                                part.trim_end()
+                            } else {
+                                part
+                            }
+                        })
+                        .collect();
+                    text.push_str(&parts.join(" "));
+                } else {
+                    text.push_str(value);
+                }
+            } else if child.has_tag_name("br") {
+                text.push('\n');
+            } else if child.has_tag_name("chord") {
+                if let Some(name) = child
+                    .attribute("name")
+                    .filter(|name| chords::is_chord(name))
+                {
+                    text.push_str(&format!("[{}]", name.trim()));
+                }
+                // chords can also contain the lyrics they are above
+                collect(child, text);
+            } else if !child.has_tag_name("comment") {
+                collect(child, text);
+            }
+        }
+    }
+
+    let mut text = String::new();
+    collect(lines, &mut text);
+
+    text.lines()
+        .map(|line| line.trim())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Finds the first child element with a tag name
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+/// Gets the trimmed text of the first child element with a tag name
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(|text| String::from(text.trim()))
+        .filter(|text| !text.is_empty())
+}
+
+/// Gets the trimmed text of all child elements with a tag name
+fn children_text(node: Node, name: &str) -> Vec<String> {
+    node.children()
+        .filter(|child| child.has_tag_name(name))
+        .filter_map(|child| child.text())
+        .map(|text| String::from(text.trim()))
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<song xmlns="http://openlyrics.info/namespace/2009/song" version="0.9">
+  <properties>
+    <titles>
+      <title>Amazing Grace</title>
+      <title>Amazing Grace</title>
+      <title>New Britain</title>
+    </titles>
+    <authors>
+      <author type="words">John Newton</author>
+    </authors>
+    <copyright>Public Domain</copyright>
+    <ccliNo>22025</ccliNo>
+    <tempo type="bpm">72</tempo>
+    <key>G</key>
+    <verseOrder>v1 c v2 c</verseOrder>
+    <songbooks><songbook name="Hymns" entry="378"/></songbooks>
+    <themes><theme>Grace</theme></themes>
+    <comments><comment>Slow</comment><comment>Key of G</comment></comments>
+  </properties>
+  <lyrics>
+    <verse name="v1" lang="en">
+      <lines><chord name="G"/>Amazing <chord name="D/F#">grace</chord> how sweet the sound<br/>
+        That saved a <comment>softly</comment>wretch like me<chord name="X"/></lines>
+    </verse>
+    <verse name="c" lang="en"><lines>Chorus &amp; more</lines></verse>
+    <verse name="v2" lang="en"><lines>'Twas grace</lines><lines>that taught</lines></verse>
+    <verse name="v1" lang="es"><lines><chord name="G"/>Sublime gracia</lines></verse>
+  </lyrics>
+</song>"#;
+
+    #[test]
+    fn parses_songs() {
+        let (title, song) = parse(SONG).unwrap();
+
+        assert_eq!(title, "Amazing Grace");
+        assert_eq!(song.alternate_titles, ["New Britain"]);
+        assert_eq!(song.authors, ["John Newton"]);
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+        assert_eq!(song.ccli_number.as_deref(), Some("22025"));
+        assert_eq!(song.verse_order, ["1", "c", "2", "c"]);
+        assert_eq!(song.songbooks[0].name, "Hymns");
+        assert_eq!(song.songbooks[0].entry.as_deref(), Some("378"));
+        assert_eq!(song.themes, ["Grace"]);
+        assert_eq!(song.notes.as_deref(), Some("Slow\nKey of G"));
+        assert_eq!(song.key.as_deref(), Some("G"));
+        assert_eq!(song.tempo, Some(72));
+
+        assert_eq!(
+            song.verses["1"],
+            "Amazing grace how sweet the sound\nThat saved a wretch like me"
+        );
+        assert_eq!(song.verses["c"], "Chorus & more");
+        assert_eq!(song.verses["2"], "'Twas grace\nthat taught");
+        assert_eq!(song.language.as_deref(), Some("en"));
+        assert_eq!(song.translations["es"]["1"], "[G]Sublime gracia");
+
+        // chords are annotations, names that aren't chords are dropped
+        let chords: Vec<_> = song.chords["1"]
+            .iter()
+            .map(|annotation| {
+                (
+                    annotation.line,
+                    annotation.position,
+                    annotation.chord.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(chords, [(0, 0, "G"), (0, 8, "D/F#")]);
+    }
+
+    #[test]
+    fn verses_in_the_first_verse_language_are_the_song_verses() {
+        let xml = r#"<song><properties><titles><title>Song</title></titles></properties><lyrics>
+            <verse name="v1"><lines>One</lines></verse>
+            <verse name="v1" lang="es"><lines>Uno</lines></verse>
+        </lyrics></song>"#;
+        let (_, song) = parse(xml).unwrap();
+
+        assert_eq!(song.language, None);
+        assert_eq!(song.verses["1"], "One");
+        assert_eq!(song.translations["es"]["1"], "Uno");
+    }
+
+    #[test]
+    fn rejects_documents_that_arent_songs() {
+        assert!(parse("<song><properties/></song>").is_err());
+        assert!(parse("<html/>").is_err());
+        assert!(parse("not xml").is_err());
+    }
+
+    #[test]
+    fn converts_verse_names() {
+        assert_eq!(convert_verse_name("v1"), "1");
+        assert_eq!(convert_verse_name("c"), "c");
+        assert_eq!(convert_verse_name("verse"), "verse");
+        assert_eq!(export_verse_name("1"), "v1");
+        assert_eq!(export_verse_name("c2"), "c2");
+    }
+
+    #[test]
+    fn writes_chords_as_elements() {
+        let mut song = Song::default();
+        song.verses
+            .insert(String::from("1"), String::from("Amazing [C]grace\n<sweet>"));
+        song.chords.insert(
+            String::from("1"),
+            vec![
+                ChordAnnotation {
+                    line: 0,
+                    position: 0,
+                    chord: String::from("G"),
+                },
+                ChordAnnotation {
+                    line: 1,
+                    position: 9,
+                    chord: String::from("D7"),
+                },
+            ],
+        );
+        song.key = Some(String::from("G"));
+        song.tempo = Some(90);
+
+        let xml = write("Song", &song);
+        assert!(xml.contains("<tempo type=\"bpm\">90</tempo>\n    <key>G</key>"));
+        assert!(xml.contains(
+            "<lines><chord name=\"G\"/>Amazing <chord name=\"C\"/>grace<br/>&lt;sweet&gt;  <chord name=\"D7\"/></lines>"
+        ));
+
+        // inline chords become annotations when the song is imported again
+        let (_, written_song) = parse(&xml).unwrap();
+        assert_eq!(written_song.verses["1"], "Amazing grace\n<sweet>");
+        let chords: Vec<_> = written_song.chords["1"]
+            .iter()
+            .map(|annotation| {
+                (
+                    annotation.line,
+                    annotation.position,
+                    annotation.chord.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(chords, [(0, 0, "G"), (0, 8, "C"), (1, 9, "D7")]);
+        assert_eq!(written_song.key.as_deref(), Some("G"));
+        assert_eq!(written_song.tempo, Some(90));
+    }
+
+    #[test]
+    fn written_songs_can_be_imported_again() {
+        let (title, song) = parse(SONG).unwrap();
+        let (written_title, written_song) = parse(&write(&title, &song)).unwrap();
+
+        assert_eq!(written_title, title);
+        assert!(written_song == song);
+    }
+}
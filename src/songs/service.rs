@@ -0,0 +1,393 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config::file::AppConfig, content::service::ContentService, helpers::errors::GenericError,
+};
+
+use super::{
+    duplicates,
+    import::{self, ConflictStrategy, ImportReport, ImportedSong},
+    lint::{self, LintIssue, SongLintConfig},
+    migration::{self, MigrationReport},
+    models::{Song, SongLibrary, SortedSongLibrary},
+    validation::{validate_arrangement, validate_song},
+};
+
+/// Song library file in the content directory (hard-coded in the client)
+pub const SONGS_FILE: &str = "songs.json";
+
+/// How often single song changes copy the previous song library to the trash
+/// (so that a day of edits doesn't fill the trash with copies of the whole library)
+const SONG_CHANGE_TRASH_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Song service
+pub struct SongService {
+    /// Held while the song library is being modified so that concurrent edits don't overwrite each other,
+    /// with when a single song change last copied the song library to the trash
+    lock: Mutex<Option<Instant>>,
+    /// Lint rules
+    lint: SongLintConfig,
+}
+
+impl SongService {
+    pub fn new(config: &AppConfig) -> Self {
+        Self {
+            lock: Mutex::new(None),
+            lint: config.song_lint.clone(),
+        }
+    }
+
+    /// Loads the song library.
+    /// A missing or empty song file is treated as an empty library.
+    pub fn load(&self, content: &ContentService) -> Result<SongLibrary, GenericError> {
+        let path = content.resolve_path(SONGS_FILE)?;
+        if !path.is_file() {
+            return Ok(SongLibrary::new());
+        }
+
+        let (text, _) = content.read_text(&path)?;
+        if text.trim().is_empty() {
+            return Ok(SongLibrary::new());
+        }
+
+        serde_json::from_str(&text).map_err(|_| GenericError::INTERNAL_SERVER_ERROR)
+    }
+
+    /// Loads the song library, applies a change to it and saves it.
+    /// The library is not saved if the change returns an error or doesn't change anything.
+    pub fn modify<T>(
+        &self,
+        content: &ContentService,
+        modified_by: Option<String>,
+        change: impl FnOnce(&mut SongLibrary) -> Result<T, GenericError>,
+    ) -> Result<T, GenericError> {
+        self.modify_library(content, modified_by, false, change)
+    }
+
+    /// Applies a change to a single song in the song library.
+    /// The previous library is only copied to the trash if it hasn't been recently.
+    fn modify_song<T>(
+        &self,
+        content: &ContentService,
+        modified_by: Option<String>,
+        change: impl FnOnce(&mut SongLibrary) -> Result<T, GenericError>,
+    ) -> Result<T, GenericError> {
+        self.modify_library(content, modified_by, true, change)
+    }
+
+    /// Loads the song library, applies a change to it and saves it,
+    /// skipping the trash for single song changes made soon after the last one
+    fn modify_library<T>(
+        &self,
+        content: &ContentService,
+        modified_by: Option<String>,
+        song_change: bool,
+        change: impl FnOnce(&mut SongLibrary) -> Result<T, GenericError>,
+    ) -> Result<T, GenericError> {
+        let mut last_song_change_trashed = self.lock.lock().unwrap();
+
+        let original = self.load(content)?;
+        let mut library = original.clone();
+        let result = change(&mut library)?;
+        if library != original {
+            let trash = !song_change
+                || last_song_change_trashed
+                    .is_none_or(|trashed| trashed.elapsed() >= SONG_CHANGE_TRASH_INTERVAL);
+            self.save(content, &library, modified_by, trash)?;
+            if song_change && trash {
+                *last_song_change_trashed = Some(Instant::now());
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Gets a single song
+    pub fn get(&self, content: &ContentService, title: &str) -> Result<Song, GenericError> {
+        self.load(content)?
+            .remove(title)
+            .ok_or(GenericError::NOT_FOUND)
+    }
+
+    /// Adds a new song
+    pub fn create(
+        &self,
+        content: &ContentService,
+        title: &str,
+        song: Song,
+        modified_by: Option<String>,
+    ) -> Result<(), GenericError> {
+        let title = validate_title(title)?;
+
+        self.modify_song(content, modified_by, |library| {
+            if library.contains_key(&title) {
+                return Err(GenericError::CONFLICT);
+            }
+            library.insert(title, song);
+            Ok(())
+        })
+    }
+
+    /// Replaces a song, optionally renaming it.
+    /// If an entity tag is specified, the song is only updated if it hasn't changed since the tag was generated.
+    pub fn update(
+        &self,
+        content: &ContentService,
+        title: &str,
+        new_title: Option<&str>,
+        song: Song,
+        expected_etag: Option<&str>,
+        modified_by: Option<String>,
+    ) -> Result<(), GenericError> {
+        let new_title = validate_title(new_title.unwrap_or(title))?;
+
+        self.modify_song(content, modified_by, |library| {
+            let existing = library.get(title).ok_or(GenericError::NOT_FOUND)?;
+            check_etag(existing, expected_etag)?;
+
+            if new_title != title && library.contains_key(&new_title) {
+                return Err(GenericError::CONFLICT);
+            }
+
+            library.remove(title);
+            library.insert(new_title, song);
+            Ok(())
+        })
+    }
+
+    /// Removes a song.
+    /// If an entity tag is specified, the song is only removed if it hasn't changed since the tag was generated.
+    pub fn delete(
+        &self,
+        content: &ContentService,
+        title: &str,
+        expected_etag: Option<&str>,
+        modified_by: Option<String>,
+    ) -> Result<(), GenericError> {
+        self.modify_song(content, modified_by, |library| {
+            let existing = library.get(title).ok_or(GenericError::NOT_FOUND)?;
+            check_etag(existing, expected_etag)?;
+
+            library.remove(title);
+            Ok(())
+        })
+    }
+
+    /// Adds or replaces a named arrangement of a song's verses, or removes it if no verses are given
+    pub fn set_arrangement(
+        &self,
+        content: &ContentService,
+        title: &str,
+        name: &str,
+        verses: Option<Vec<String>>,
+        modified_by: Option<String>,
+    ) -> Result<(), GenericError> {
+        self.modify_song(content, modified_by, |library| {
+            let song = library.get_mut(title).ok_or(GenericError::NOT_FOUND)?;
+            match verses {
+                Some(verses) => {
+                    if !validate_arrangement(title, song, name, &verses).is_empty() {
+                        return Err(GenericError::BAD_REQUEST);
+                    }
+                    song.arrangements.insert(String::from(name), verses);
+                }
+                None => {
+                    song.arrangements
+                        .remove(name)
+                        .ok_or(GenericError::NOT_FOUND)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Merges a song into another song and removes it (unless it should be kept).
+    /// For a dry run the merged song is returned without saving the library.
+    pub fn merge(
+        &self,
+        content: &ContentService,
+        target: &str,
+        source: &str,
+        keep_source: bool,
+        dry_run: bool,
+        modified_by: Option<String>,
+    ) -> Result<Song, GenericError> {
+        let merge = |library: &mut SongLibrary| {
+            if target == source {
+                return Err(GenericError::BAD_REQUEST);
+            }
+            let target_song = library.get(target).ok_or(GenericError::NOT_FOUND)?;
+            let source_song = library.get(source).ok_or(GenericError::NOT_FOUND)?;
+
+            let merged = duplicates::merge_songs(target, target_song, source, source_song);
+            if !validate_song(target, &merged).is_empty() {
+                return Err(GenericError::BAD_REQUEST);
+            }
+
+            library.insert(String::from(target), merged.clone());
+            if !keep_source {
+                library.remove(source);
+            }
+            Ok(merged)
+        };
+
+        if dry_run {
+            return merge(&mut self.load(content)?);
+        }
+        self.modify(content, modified_by, merge)
+    }
+
+    /// Merges imported songs into the song library.
+    /// For a dry run the report is generated without saving the library.
+    pub fn import(
+        &self,
+        content: &ContentService,
+        songs: Vec<ImportedSong>,
+        strategy: ConflictStrategy,
+        mut report: ImportReport,
+        dry_run: bool,
+        modified_by: Option<String>,
+    ) -> Result<ImportReport, GenericError> {
+        report.dry_run = dry_run;
+        if dry_run {
+            let mut library = self.load(content)?;
+            import::merge(
+                &mut library,
+                songs,
+                strategy,
+                self.enforced_lint(),
+                &mut report,
+            );
+            return Ok(report);
+        }
+
+        self.modify(content, modified_by, |library| {
+            import::merge(library, songs, strategy, self.enforced_lint(), &mut report);
+            Ok(())
+        })?;
+
+        Ok(report)
+    }
+
+    /// Migrates older songs in the song library to the current song format.
+    /// For a dry run the report is generated without saving the library.
+    pub fn migrate(
+        &self,
+        content: &ContentService,
+        dry_run: bool,
+        modified_by: Option<String>,
+    ) -> Result<MigrationReport, GenericError> {
+        let mut report = MigrationReport {
+            dry_run,
+            ..Default::default()
+        };
+        if dry_run {
+            let mut library = self.load(content)?;
+            migration::migrate_library(&mut library, &mut report);
+            return Ok(report);
+        }
+
+        self.modify(content, modified_by, |library| {
+            migration::migrate_library(library, &mut report);
+            Ok(())
+        })?;
+
+        Ok(report)
+    }
+
+    /// Lints every song in the song library
+    pub fn lint(&self, content: &ContentService) -> Result<Vec<LintIssue>, GenericError> {
+        Ok(lint::lint_library(&self.load(content)?, &self.lint))
+    }
+
+    /// Lints a song before it is saved.
+    /// There are no issues if the lint rules aren't enforced.
+    pub fn lint_before_save(&self, title: &str, song: &Song) -> Vec<LintIssue> {
+        match self.enforced_lint() {
+            Some(config) => lint::lint_song(title, song, config),
+            None => Vec::new(),
+        }
+    }
+
+    /// Replaces the whole song library.
+    /// The new and changed songs are linted first, and the library isn't replaced if there are any issues.
+    pub fn replace_library(
+        &self,
+        content: &ContentService,
+        library: SongLibrary,
+        modified_by: Option<String>,
+    ) -> Result<Vec<LintIssue>, GenericError> {
+        self.modify(content, modified_by, |current| {
+            if let Some(config) = self.enforced_lint() {
+                let issues = lint::lint_changes(current, &library, config);
+                if !issues.is_empty() {
+                    return Ok(issues);
+                }
+            }
+
+            *current = library;
+            Ok(Vec::new())
+        })
+    }
+
+    /// Gets the lint rules if songs with lint issues shouldn't be saved
+    fn enforced_lint(&self) -> Option<&SongLintConfig> {
+        Some(&self.lint).filter(|lint| lint.enforce_on_save)
+    }
+
+    /// Writes the song library, copying the previous version to the trash if requested
+    fn save(
+        &self,
+        content: &ContentService,
+        library: &SongLibrary,
+        modified_by: Option<String>,
+        trash: bool,
+    ) -> Result<(), GenericError> {
+        let path = content.resolve_path(SONGS_FILE)?;
+        let json = serde_json::to_string(&SortedSongLibrary(library))
+            .map_err(|_| GenericError::INTERNAL_SERVER_ERROR)?;
+
+        // write to a temporary file first so that the library is never partially written
+        let temp_path = path.with_file_name(format!(".{}.tmp", SONGS_FILE));
+        fs::write(&temp_path, json)?;
+        if trash {
+            content.trash_before_overwrite(&path, modified_by)?;
+        }
+        fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+}
+
+/// Generates an entity tag for a song, used to detect concurrent edits
+pub fn etag(song: &Song) -> String {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_string(song)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// Checks that a song matches an expected entity tag
+fn check_etag(song: &Song, expected_etag: Option<&str>) -> Result<(), GenericError> {
+    match expected_etag {
+        Some(expected_etag) if expected_etag != "*" && expected_etag != etag(song) => {
+            Err(GenericError::PRECONDITION_FAILED)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Trims a song title and makes sure it isn't empty
+fn validate_title(title: &str) -> Result<String, GenericError> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(GenericError::BAD_REQUEST);
+    }
+    Ok(String::from(title))
+}
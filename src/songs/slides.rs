@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::chords;
+
+/// Lines that force a new slide (`[---]` is the OpenLP optional split marker)
+const SPLIT_MARKERS: &[&str] = &["[---]", "[===]"];
+
+/// Limits on how much text fits on a slide
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SlideLimits {
+    /// Most lines on a slide (blank lines between stanzas count)
+    pub max_lines: Option<usize>,
+    /// Most characters on a slide (not counting line breaks)
+    pub max_characters: Option<usize>,
+}
+
+impl SlideLimits {
+    /// Gets the slide limits for a display from the client options
+    /// (`max_lines_per_slide` and `max_characters_per_slide` in the display's config,
+    /// falling back to `display_default`)
+    pub fn for_display(client_options: &Value, display: Option<&str>) -> Self {
+        let display_config = display.and_then(|display| {
+            client_options
+                .get("displays")
+                .and_then(|displays| displays.get(display))
+        });
+        let default_config = client_options.get("display_default");
+        let option = |key: &str| {
+            [display_config, default_config]
+                .into_iter()
+                .flatten()
+                .find_map(|config| config.get(key))
+                .and_then(Value::as_u64)
+                .filter(|value| *value > 0)
+                .map(|value| value as usize)
+        };
+
+        Self {
+            max_lines: option("max_lines_per_slide"),
+            max_characters: option("max_characters_per_slide"),
+        }
+    }
+
+    /// Whether lines fit on a single slide
+    fn fits(&self, lines: &[&str]) -> bool {
+        self.max_lines
+            .is_none_or(|max_lines| lines.len() <= max_lines)
+            && self.max_characters.is_none_or(|max_characters| {
+                lines.iter().map(|line| line.chars().count()).sum::<usize>() <= max_characters
+            })
+    }
+}
+
+/// Whether a line is a slide split marker
+pub fn is_split_marker(line: &str) -> bool {
+    SPLIT_MARKERS.contains(&line.trim())
+}
+
+/// Removes slide split markers from verse text so that they aren't shown on displays
+pub fn remove_split_markers(text: &str) -> String {
+    if !text.lines().any(is_split_marker) {
+        return String::from(text);
+    }
+    text.lines()
+        .filter(|line| !is_split_marker(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Splits verse text at the split markers, with blank lines at the start and end removed
+pub fn split_at_markers(text: &str) -> Vec<Vec<&str>> {
+    let mut parts = vec![Vec::new()];
+    for line in text.lines() {
+        if is_split_marker(line) {
+            parts.push(Vec::new());
+        } else {
+            parts.last_mut().unwrap().push(line);
+        }
+    }
+
+    parts
+        .into_iter()
+        .map(|mut lines| {
+            while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+            let start = lines
+                .iter()
+                .position(|line| !line.trim().is_empty())
+                .unwrap_or(lines.len());
+            lines.split_off(start)
+        })
+        .filter(|lines| !lines.is_empty())
+        .collect()
+}
+
+/// Slides of a verse
+#[derive(Serialize, Deserialize)]
+pub struct VerseSlides {
+    /// Verse name
+    pub verse: String,
+    /// Text of each slide
+    pub slides: Vec<String>,
+}
+
+/// Splits a verse into slides that fit within the limits, with chords removed.
+/// Slides always start at split markers, and are split between stanzas (at blank lines) where possible.
+/// Stanzas that don't fit on a slide are split into slides with about the same number of lines.
+pub fn split_verse(text: &str, limits: SlideLimits) -> Vec<String> {
+    let text = chords::strip(text);
+    let mut slides = Vec::new();
+
+    for part in split_at_markers(&text) {
+        let mut current: Vec<&str> = Vec::new();
+        for stanza in part.split(|line| line.trim().is_empty()) {
+            if stanza.is_empty() {
+                continue;
+            }
+
+            if !current.is_empty() {
+                let mut candidate = current.clone();
+                candidate.push("");
+                candidate.extend(stanza);
+                if limits.fits(&candidate) {
+                    current = candidate;
+                    continue;
+                }
+                slides.push(current.join("\n"));
+                current.clear();
+            }
+
+            if limits.fits(stanza) {
+                current.extend(stanza);
+            } else {
+                slides.extend(split_stanza(stanza, limits));
+            }
+        }
+        if !current.is_empty() {
+            slides.push(current.join("\n"));
+        }
+    }
+
+    slides
+}
+
+/// Splits a stanza that doesn't fit on a slide into slides with about the same number of lines.
+/// A line that doesn't fit on a slide by itself gets its own slide.
+fn split_stanza(lines: &[&str], limits: SlideLimits) -> Vec<String> {
+    let chunk_size = match limits.max_lines {
+        Some(max_lines) => lines.len().div_ceil(lines.len().div_ceil(max_lines)),
+        None => lines.len(),
+    };
+
+    let mut slides = Vec::new();
+    for chunk in lines.chunks(chunk_size) {
+        let mut current: Vec<&str> = Vec::new();
+        for line in chunk {
+            current.push(line);
+            if !limits.fits(&current) && current.len() > 1 {
+                current.pop();
+                slides.push(current.join("\n"));
+                current = vec![line];
+            }
+        }
+        slides.push(current.join("\n"));
+    }
+    slides
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn limits(max_lines: Option<usize>, max_characters: Option<usize>) -> SlideLimits {
+        SlideLimits {
+            max_lines,
+            max_characters,
+        }
+    }
+
+    #[test]
+    fn keeps_verses_that_fit_on_one_slide() {
+        let text = "[G]Amazing grace\nHow sweet the sound\n\nThat saved a wretch";
+        assert_eq!(
+            split_verse(text, limits(Some(4), None)),
+            ["Amazing grace\nHow sweet the sound\n\nThat saved a wretch"]
+        );
+        assert_eq!(split_verse(text, SlideLimits::default()).len(), 1);
+    }
+
+    #[test]
+    fn splits_between_stanzas() {
+        let text = "1\n2\n3\n\n4\n5\n\n6";
+        assert_eq!(
+            split_verse(text, limits(Some(4), None)),
+            ["1\n2\n3", "4\n5\n\n6"]
+        );
+    }
+
+    #[test]
+    fn splits_long_stanzas_evenly() {
+        let text = "1\n2\n3\n4\n5\n6\n7";
+        assert_eq!(
+            split_verse(text, limits(Some(4), None)),
+            ["1\n2\n3\n4", "5\n6\n7"]
+        );
+        assert_eq!(
+            split_verse(text, limits(Some(3), None)),
+            ["1\n2\n3", "4\n5\n6", "7"]
+        );
+    }
+
+    #[test]
+    fn splits_by_characters() {
+        let text = "aaaa\nbbbb\ncccc\n\ndddddddddddd";
+        assert_eq!(
+            split_verse(text, limits(None, Some(8))),
+            ["aaaa\nbbbb", "cccc", "dddddddddddd"]
+        );
+    }
+
+    #[test]
+    fn splits_at_markers() {
+        let text = "1\n2\n[---]\n\n3\n [===] \n4\n[---]";
+        assert_eq!(
+            split_verse(text, SlideLimits::default()),
+            ["1\n2", "3", "4"]
+        );
+        assert_eq!(
+            split_verse(text, limits(Some(1), None)),
+            ["1", "2", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn removes_split_markers() {
+        assert_eq!(remove_split_markers("1\n[---]\n2"), "1\n2");
+        assert_eq!(remove_split_markers("1\r\n2\n"), "1\r\n2\n");
+    }
+
+    #[test]
+    fn gets_limits_for_displays() {
+        let options = json!({
+            "display_default": {"max_lines_per_slide": 4, "max_characters_per_slide": 200},
+            "displays": {"stage": {"max_lines_per_slide": 8, "max_characters_per_slide": 0}},
+        });
+
+        // 0 turns off the default limit for a display
+        let stage = SlideLimits::for_display(&options, Some("stage"));
+        assert_eq!((stage.max_lines, stage.max_characters), (Some(8), None));
+        let other = SlideLimits::for_display(&options, Some("other"));
+        assert_eq!(
+            (other.max_lines, other.max_characters),
+            (Some(4), Some(200))
+        );
+        let none = SlideLimits::for_display(&json!({}), None);
+        assert_eq!((none.max_lines, none.max_characters), (None, None));
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+
+use crate::content::encoding;
+
+use super::{
+    import::{
+        clean_verse_text, section_name, section_prefix, set_verse_order, ImportReport, ImportedSong,
+    },
+    models::Song,
+};
+
+/// Parses CCLI SongSelect files (`.usr` files and plain text lyrics),
+/// recording any files that couldn't be parsed in the import report
+pub fn import(files: Vec<(String, Vec<u8>)>, report: &mut ImportReport) -> Vec<ImportedSong> {
+    let mut songs = Vec::new();
+    for (source, bytes) in files {
+        let text = encoding::decode(&bytes).text;
+        let result = if is_usr(&text) {
+            parse_usr(&text)
+        } else {
+            parse_text(&text)
+        };
+
+        match result {
+            Ok((title, song)) => songs.push(ImportedSong {
+                source,
+                title,
+                song,
+            }),
+            Err(err) => report.error(&source, err),
+        }
+    }
+    songs
+}
+
+/// Checks whether a file is a SongSelect `.usr` file (rather than plain text lyrics)
+fn is_usr(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| line.eq_ignore_ascii_case("[File]"))
+}
+
+/// Parses a SongSelect `.usr` file into a song title and song.
+///
+/// These are INI files with the song in a `[S A<CCLI number>]` section.
+/// Section names (`Fields`) and lyrics (`Words`) are separated by `/t`, lines by `/n`.
+pub fn parse_usr(text: &str) -> Result<(String, Song), String> {
+    let mut in_song_section = false;
+    let mut values = HashMap::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(header) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            // only the song section is needed, its header has the CCLI song number
+            in_song_section = header.starts_with("S ");
+            if let Some(number) = header.strip_prefix("S ") {
+                let number = number.trim().trim_start_matches('A');
+                values.insert(String::from("ccli"), String::from(number));
+            }
+            continue;
+        }
+        if !in_song_section {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_lowercase(), String::from(value.trim()));
+        }
+    }
+
+    let title = values
+        .get("title")
+        .filter(|title| !title.is_empty())
+        .cloned()
+        .ok_or_else(|| String::from("Song has no title"))?;
+
+    let mut song = Song {
+        authors: split_authors(values.get("author").map_or("", String::as_str)),
+        copyright: values
+            .get("copyright")
+            .and_then(|copyright| clean_copyright(copyright)),
+        ccli_number: values
+            .get("ccli")
+            .filter(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+            .cloned(),
+        key: values
+            .get("keys")
+            .and_then(|keys| keys.split("/t").next())
+            .map(|key| String::from(key.trim()))
+            .filter(|key| !key.is_empty()),
+        themes: values
+            .get("themes")
+            .map(|themes| {
+                themes
+                    .split("/t")
+                    .map(|theme| String::from(theme.trim()))
+                    .filter(|theme| !theme.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let fields = values.get("fields").map_or("", String::as_str).split("/t");
+    let words = values.get("words").map_or("", String::as_str).split("/t");
+    let mut sections = Sections::default();
+    for (label, words) in fields.zip(words) {
+        sections.add(label, &words.replace("/n", "\n"));
+    }
+    sections.finish(&mut song)?;
+
+    Ok((title, song))
+}
+
+/// Parses SongSelect plain text lyrics into a song title and song.
+///
+/// The title is on the first line, followed by sections that start with a label line
+/// (`Verse 1`, `Chorus`...) and a footer with the CCLI song number, authors and copyright.
+pub fn parse_text(text: &str) -> Result<(String, Song), String> {
+    let lines: Vec<_> = text.lines().map(str::trim_end).collect();
+
+    let title_index = lines
+        .iter()
+        .position(|line| !line.trim().is_empty())
+        .ok_or_else(|| String::from("Song has no title"))?;
+    let title = String::from(lines[title_index].trim());
+
+    let footer_index = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("CCLI Song"))
+        .unwrap_or(lines.len());
+
+    let mut song = Song::default();
+
+    // footer: CCLI song number, then authors, then copyright lines
+    let mut copyright = Vec::new();
+    if let Some(footer) = lines.get(footer_index) {
+        song.ccli_number = footer
+            .split(|c: char| !c.is_ascii_digit())
+            .find(|number| !number.is_empty())
+            .map(String::from);
+    }
+    for line in lines.iter().skip(footer_index + 1).map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with("For use solely") || line.starts_with("CCLI License")
+        {
+            continue;
+        }
+        if line.starts_with('©') || line.to_lowercase().starts_with("copyright") {
+            copyright.extend(clean_copyright(line));
+        } else if song.authors.is_empty() && copyright.is_empty() {
+            song.authors = split_authors(line);
+        }
+    }
+    if !copyright.is_empty() {
+        song.copyright = Some(copyright.join("; "));
+    }
+
+    // body: blank line separated blocks, each starting with a section label
+    let mut sections = Sections::default();
+    let body = &lines[title_index + 1..footer_index];
+    for block in body.split(|line| line.trim().is_empty()) {
+        let Some((first, rest)) = block.split_first() else {
+            continue;
+        };
+        if section_prefix(first.trim()).is_some() {
+            sections.add(first.trim(), &rest.join("\n"));
+        } else {
+            sections.add("", &block.join("\n"));
+        }
+    }
+    sections.finish(&mut song)?;
+
+    Ok((title, song))
+}
+
+/// Song sections read so far
+#[derive(Default)]
+struct Sections {
+    verses: Vec<(String, String)>,
+    order: Vec<String>,
+    counters: HashMap<&'static str, u32>,
+}
+
+impl Sections {
+    /// Adds a section with a label (e.g. `Verse 1`, `Chorus`).
+    /// Sections without lyrics (e.g. a label on a line of its own) are ignored.
+    fn add(&mut self, label: &str, text: &str) {
+        let text = clean_verse_text(text);
+        if text.is_empty() {
+            return;
+        }
+
+        let prefix = section_prefix(label).unwrap_or("");
+        let labelled_name = section_name(prefix, label.trim());
+
+        // a section that's repeated word for word is only added to the verse order again
+        // (matched by name, or by kind if the label has no number)
+        if let Some((name, _)) = self.verses.iter().find(|(name, existing)| {
+            let same_section = match &labelled_name {
+                Some(labelled_name) => name == labelled_name,
+                None => name.strip_prefix(prefix).is_some_and(|number| {
+                    !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+                }),
+            };
+            same_section && *existing == text
+        }) {
+            self.order.push(name.clone());
+            return;
+        }
+
+        let name = labelled_name
+            .filter(|name| !self.verses.iter().any(|(existing, _)| existing == name))
+            .unwrap_or_else(|| loop {
+                let counter = self.counters.entry(prefix).or_default();
+                *counter += 1;
+                let name = format!("{}{}", prefix, counter);
+                if !self.verses.iter().any(|(existing, _)| *existing == name) {
+                    break name;
+                }
+            });
+
+        self.order.push(name.clone());
+        self.verses.push((name, text));
+    }
+
+    /// Adds the sections to a song as verses
+    fn finish(self, song: &mut Song) -> Result<(), String> {
+        if self.verses.is_empty() {
+            return Err(String::from("Song has no lyrics"));
+        }
+        song.verses = self.verses.into_iter().collect();
+        set_verse_order(song, self.order);
+        Ok(())
+    }
+}
+
+/// Splits SongSelect authors (separated by `|`)
+fn split_authors(authors: &str) -> Vec<String> {
+    authors
+        .split('|')
+        .map(|author| String::from(author.trim()))
+        .filter(|author| !author.is_empty())
+        .collect()
+}
+
+/// Removes the copyright symbol or word from the start of a copyright notice
+fn clean_copyright(copyright: &str) -> Option<String> {
+    let copyright = copyright.trim();
+    let copyright = copyright
+        .strip_prefix('©')
+        .or_else(|| {
+            copyright
+                .get(..9)
+                .filter(|prefix| prefix.eq_ignore_ascii_case("copyright"))
+                .map(|_| &copyright[9..])
+        })
+        .unwrap_or(copyright)
+        .trim();
+    Some(String::from(copyright)).filter(|copyright| !copyright.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_usr_files() {
+        let text = "[File]
+Type=SongSelect Import File
+Version=3.0
+
+[S A1234567]
+Title=Amazing Grace
+Author=John Newton | Edwin Excell
+Copyright=© Public Domain
+Admin=
+Themes=Grace/tSalvation
+Keys=G
+Fields=Verse 1/tChorus/tVerse 2/tChorus
+Words=Amazing grace/nhow sweet the sound/tMy chains are gone/tTwas grace/tMy chains are gone
+";
+        assert!(is_usr(text));
+        let (title, song) = parse_usr(text).unwrap();
+
+        assert_eq!(title, "Amazing Grace");
+        assert_eq!(song.authors, ["John Newton", "Edwin Excell"]);
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+        assert_eq!(song.ccli_number.as_deref(), Some("1234567"));
+        assert_eq!(song.key.as_deref(), Some("G"));
+        assert_eq!(song.themes, ["Grace", "Salvation"]);
+        assert_eq!(song.verses["1"], "Amazing grace\nhow sweet the sound");
+        assert_eq!(song.verses["c1"], "My chains are gone");
+        assert_eq!(song.verses["2"], "Twas grace");
+        assert_eq!(song.verse_order, ["1", "c1", "2", "c1"]);
+    }
+
+    #[test]
+    fn parses_text_lyrics() {
+        let text = "Amazing Grace
+
+Verse 1
+Amazing grace
+how sweet the sound
+
+Chorus
+My chains are gone
+
+Verse 2
+Twas grace
+
+Chorus
+My chains are gone
+
+CCLI Song # 1234567
+John Newton | Edwin Excell
+© Public Domain
+For use solely with the SongSelect® Terms of Use.
+CCLI License # 11111
+";
+        assert!(!is_usr(text));
+        let (title, song) = parse_text(text).unwrap();
+
+        assert_eq!(title, "Amazing Grace");
+        assert_eq!(song.authors, ["John Newton", "Edwin Excell"]);
+        assert_eq!(song.copyright.as_deref(), Some("Public Domain"));
+        assert_eq!(song.ccli_number.as_deref(), Some("1234567"));
+        assert_eq!(song.verses.len(), 3);
+        assert_eq!(song.verses["1"], "Amazing grace\nhow sweet the sound");
+        assert_eq!(song.verse_order, ["1", "c1", "2", "c1"]);
+    }
+
+    #[test]
+    fn rejects_songs_without_title_or_lyrics() {
+        assert!(parse_text("").is_err());
+        assert!(parse_text("Title only\n").is_err());
+        assert!(parse_usr("[File]\n[S A1]\nWords=Text\n").is_err());
+    }
+
+    #[test]
+    fn cleans_copyright_notices() {
+        assert_eq!(
+            clean_copyright("© 2020 Someone").as_deref(),
+            Some("2020 Someone")
+        );
+        assert_eq!(
+            clean_copyright("Copyright 2020 Someone").as_deref(),
+            Some("2020 Someone")
+        );
+        assert_eq!(clean_copyright("©"), None);
+    }
+}
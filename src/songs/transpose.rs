@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::{errors::GenericError, sort::natcasecmp};
+
+use super::{
+    chords::{self, Chord, Notation},
+    models::Song,
+};
+
+/// Song rendered in a different key
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransposedSong {
+    /// Song with its chords changed (the song key is the key it sounds in)
+    #[serde(flatten)]
+    pub song: Song,
+    /// Fret the capo is on (0 for no capo)
+    pub capo: u8,
+    /// Key of the chord shapes that are played with the capo on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shape_key: Option<String>,
+}
+
+/// How to transpose a song
+pub struct TransposeOptions<'a> {
+    /// Key to play the song in
+    pub key: Option<&'a str>,
+    /// Semitones to move the song up (or down) by, used if no key is given
+    pub semitones: Option<i32>,
+    /// Fret the capo is on
+    pub capo: u8,
+    /// How to write the chords (defaults to the usual notation for the key)
+    pub notation: Option<Notation>,
+}
+
+/// Highest fret a capo can be put on
+const MAX_CAPO: u8 = 11;
+
+/// Renders a song in another key.
+///
+/// The original key comes from the song key or the first chord in the song.
+/// Both chord annotations and chords written inline in the verse text are transposed.
+/// With a capo, the chords are the shapes played so that the song sounds in the requested key.
+pub fn transpose(
+    mut song: Song,
+    options: TransposeOptions,
+) -> Result<TransposedSong, GenericError> {
+    if options.capo > MAX_CAPO {
+        return Err(GenericError::BAD_REQUEST);
+    }
+
+    let original_key = match &song.key {
+        Some(key) => Some(Chord::parse(key).ok_or(GenericError::BAD_REQUEST)?),
+        None => first_chord(&song),
+    };
+    let requested_key = match options.key {
+        Some(key) => Some(Chord::parse(key).ok_or(GenericError::BAD_REQUEST)?),
+        None => None,
+    };
+
+    // moving by whole octaves doesn't change the chords
+    let semitones = match (&original_key, &requested_key) {
+        (Some(original_key), Some(requested_key)) => {
+            requested_key.root as i32 - original_key.root as i32
+        }
+        _ => options.semitones.unwrap_or_default(),
+    }
+    .rem_euclid(12);
+
+    // key the song sounds in (keeps major/minor from the original key)
+    let sounding_key = original_key.map(|key| key.transpose(semitones));
+    // key of the shapes that are played with the capo on
+    let shape_key = sounding_key
+        .as_ref()
+        .map(|key| key.transpose(-(options.capo as i32)));
+
+    // Nashville numbers need a key to be relative to
+    if options.notation == Some(Notation::Nashville) && shape_key.is_none() {
+        return Err(GenericError::BAD_REQUEST);
+    }
+    let notation = options
+        .notation
+        .or_else(|| shape_key.as_ref().map(Notation::for_key))
+        .unwrap_or(Notation::Sharps);
+    let key_root = shape_key.as_ref().map_or(0, |key| key.root);
+
+    let change = |chord: &str| match Chord::parse(chord) {
+        Some(chord) => chord
+            .transpose(semitones - options.capo as i32)
+            .render(notation, key_root),
+        None => String::from(chord),
+    };
+
+    for text in song.verses.values_mut() {
+        *text = chords::map_inline(text, change);
+    }
+    for verses in song.translations.values_mut() {
+        for text in verses.values_mut() {
+            *text = chords::map_inline(text, change);
+        }
+    }
+    for annotation in song.chords.values_mut().flatten() {
+        annotation.chord = change(&annotation.chord);
+    }
+
+    // keys are written with note names, in the requested notation or the usual one for the key
+    let render_key = |key: &Chord| {
+        let key_notation = match options.notation {
+            Some(notation @ (Notation::Sharps | Notation::Flats)) => notation,
+            _ => Notation::for_key(key),
+        };
+        Chord {
+            root: key.root,
+            quality: String::from(key.minor_suffix()),
+            bass: None,
+        }
+        .render(key_notation, key.root)
+    };
+    song.key = sounding_key.as_ref().map(render_key).or(song.key);
+
+    Ok(TransposedSong {
+        song,
+        capo: options.capo,
+        shape_key: match options.capo {
+            0 => None,
+            _ => shape_key.as_ref().map(render_key),
+        },
+    })
+}
+
+/// Finds the first chord in a song (in the verse order, or the first verse if there isn't one)
+fn first_chord(song: &Song) -> Option<Chord> {
+    let mut names: Vec<_> = song.verses.keys().collect();
+    names.sort_by(|a, b| natcasecmp(a, b));
+    let names = song
+        .verse_order
+        .iter()
+        .filter(|name| song.verses.contains_key(*name))
+        .chain(names);
+
+    for name in names {
+        let mut annotations = chords::extract(&song.verses[name]).1;
+        annotations.extend(song.chords.get(name).into_iter().flatten().cloned());
+        annotations.sort_by_key(|annotation| (annotation.line, annotation.position));
+
+        if let Some(chord) = annotations
+            .iter()
+            .find_map(|annotation| Chord::parse(&annotation.chord))
+        {
+            return Some(chord);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Song in G with inline chords
+    fn song() -> Song {
+        serde_json::from_value(serde_json::json!({
+            "verses": {
+                "1": "[G]Amazing [D/F#]grace how [Em]sweet the [C]sound",
+            },
+            "key": "G",
+        }))
+        .unwrap()
+    }
+
+    fn options(key: Option<&str>, semitones: Option<i32>, capo: u8) -> TransposeOptions<'_> {
+        TransposeOptions {
+            key,
+            semitones,
+            capo,
+            notation: None,
+        }
+    }
+
+    #[test]
+    fn transposes_to_a_key() {
+        let transposed = transpose(song(), options(Some("Bb"), None, 0)).unwrap();
+
+        assert_eq!(transposed.song.key.as_deref(), Some("Bb"));
+        assert_eq!(
+            transposed.song.verses["1"],
+            "[Bb]Amazing [F/A]grace how [Gm]sweet the [Eb]sound"
+        );
+        assert_eq!(transposed.shape_key, None);
+    }
+
+    #[test]
+    fn transposes_by_semitones() {
+        let up = transpose(song(), options(None, Some(2), 0)).unwrap();
+        assert_eq!(up.song.key.as_deref(), Some("A"));
+        assert_eq!(
+            up.song.verses["1"],
+            "[A]Amazing [E/G#]grace how [F#m]sweet the [D]sound"
+        );
+
+        let down = transpose(song(), options(None, Some(-2), 0)).unwrap();
+        assert_eq!(down.song.key.as_deref(), Some("F"));
+    }
+
+    #[test]
+    fn large_semitone_values_wrap_around() {
+        for semitones in [i32::MAX, i32::MIN, 1200, -1199] {
+            let transposed = transpose(song(), options(None, Some(semitones), 11)).unwrap();
+            let expected = transpose(song(), options(None, Some(semitones % 12), 11)).unwrap();
+            assert_eq!(transposed.song.verses, expected.song.verses);
+        }
+    }
+
+    #[test]
+    fn capo_gives_chord_shapes() {
+        let transposed = transpose(song(), options(Some("A"), None, 2)).unwrap();
+
+        assert_eq!(transposed.song.key.as_deref(), Some("A"));
+        assert_eq!(transposed.shape_key.as_deref(), Some("G"));
+        assert_eq!(transposed.song.verses, song().verses);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        assert!(transpose(song(), options(None, None, MAX_CAPO + 1)).is_err());
+        assert!(transpose(song(), options(Some("H"), None, 0)).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use serde::{Deserialize, Serialize};
+
+use crate::content::encoding;
+
+use super::{
+    chords::Chord,
+    models::{Song, SongLibrary},
+};
+
+/// Slowest tempo accepted (beats per minute)
+const MIN_TEMPO: u32 = 20;
+/// Fastest tempo accepted (beats per minute)
+const MAX_TEMPO: u32 = 400;
+
+/// Problem found when validating a song
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ValidationProblem {
+    /// Song title
+    pub title: String,
+    /// Song field with the problem
+    pub field: String,
+    pub message: String,
+}
+
+/// Validates the contents of a song library file before it is written, returning the song library.
+/// An empty file is an empty song library.
+pub fn validate_library_file(bytes: &[u8]) -> Result<SongLibrary, Vec<ValidationProblem>> {
+    let text = encoding::decode(bytes).text;
+    if text.trim().is_empty() {
+        return Ok(SongLibrary::new());
+    }
+
+    let library: SongLibrary = serde_json::from_str(&text).map_err(|err| {
+        vec![ValidationProblem {
+            title: String::new(),
+            field: String::new(),
+            message: format!("Invalid song library: {}", err),
+        }]
+    })?;
+
+    let problems = validate_library(&library);
+    if problems.is_empty() {
+        Ok(library)
+    } else {
+        Err(problems)
+    }
+}
+
+/// Validates every song in a song library
+pub fn validate_library(library: &SongLibrary) -> Vec<ValidationProblem> {
+    library
+        .iter()
+        .flat_map(|(title, song)| validate_song(title, song))
+        .collect()
+}
+
+/// Validates a song before it is written to the song library
+pub fn validate_song(title: &str, song: &Song) -> Vec<ValidationProblem> {
+    let mut problems = Vec::new();
+    let mut problem = |field: &str, message: String| {
+        problems.push(ValidationProblem {
+            title: String::from(title),
+            field: String::from(field),
+            message,
+        })
+    };
+
+    if title.trim().is_empty() {
+        problem("title", String::from("Title can't be empty"));
+    }
+
+    for name in song.verses.keys() {
+        if name.trim().is_empty() {
+            problem("verses", String::from("Verse names can't be empty"));
+        }
+    }
+
+    for name in &song.verse_order {
+        if !song.verses.contains_key(name) {
+            problem("verseOrder", format!("Verse \"{}\" doesn't exist", name));
+        }
+    }
+
+    for (name, verses) in &song.arrangements {
+        for arrangement_problem in validate_arrangement(title, song, name, verses) {
+            problem("arrangements", arrangement_problem.message);
+        }
+    }
+
+    for (field, values) in [
+        ("alternateTitles", &song.alternate_titles),
+        ("authors", &song.authors),
+        ("themes", &song.themes),
+    ] {
+        if values.iter().any(|value| value.trim().is_empty()) {
+            problem(field, String::from("Values can't be empty"));
+        }
+    }
+
+    if let Some(ccli_number) = &song.ccli_number {
+        if ccli_number.is_empty() || !ccli_number.chars().all(|c| c.is_ascii_digit()) {
+            problem(
+                "ccliNumber",
+                format!("\"{}\" isn't a CCLI song number", ccli_number),
+            );
+        }
+    }
+
+    if let Some(key) = &song.key {
+        if Chord::parse(key).is_none_or(|key| key.bass.is_some()) {
+            problem("key", format!("\"{}\" isn't a key", key));
+        }
+    }
+
+    if let Some(tempo) = song.tempo {
+        if !(MIN_TEMPO..=MAX_TEMPO).contains(&tempo) {
+            problem(
+                "tempo",
+                format!("Tempo must be between {} and {} BPM", MIN_TEMPO, MAX_TEMPO),
+            );
+        }
+    }
+
+    for (name, annotations) in &song.chords {
+        let Some(text) = song.verses.get(name) else {
+            problem("chords", format!("Verse \"{}\" doesn't exist", name));
+            continue;
+        };
+        let line_count = text.lines().count().max(1);
+        for annotation in annotations {
+            if annotation.line >= line_count {
+                problem(
+                    "chords",
+                    format!(
+                        "Chord \"{}\" is on line {} of verse \"{}\", which only has {} lines",
+                        annotation.chord,
+                        annotation.line + 1,
+                        name,
+                        line_count
+                    ),
+                );
+            }
+            if annotation.chord.trim().is_empty() {
+                problem("chords", format!("Empty chord in verse \"{}\"", name));
+            }
+        }
+    }
+
+    if song
+        .songbooks
+        .iter()
+        .any(|songbook| songbook.name.trim().is_empty())
+    {
+        problem("songbooks", String::from("Song book names can't be empty"));
+    }
+
+    for (language, verses) in &song.translations {
+        if language.trim().is_empty() {
+            problem("translations", String::from("Language can't be empty"));
+        }
+        if verses.keys().any(|name| name.trim().is_empty()) {
+            problem(
+                "translations",
+                format!("Verse names in \"{}\" can't be empty", language),
+            );
+        }
+    }
+
+    problems
+}
+
+/// Validates a named arrangement of a song's verses
+pub fn validate_arrangement(
+    title: &str,
+    song: &Song,
+    name: &str,
+    verses: &[String],
+) -> Vec<ValidationProblem> {
+    let problem = |message: String| ValidationProblem {
+        title: String::from(title),
+        field: String::from("arrangements"),
+        message,
+    };
+
+    let mut problems = Vec::new();
+    if name.trim().is_empty() {
+        problems.push(problem(String::from("Arrangement names can't be empty")));
+    }
+    if verses.is_empty() {
+        problems.push(problem(format!("Arrangement \"{}\" has no verses", name)));
+    }
+    for verse in verses {
+        if !song.verses.contains_key(verse) {
+            problems.push(problem(format!(
+                "Verse \"{}\" in arrangement \"{}\" doesn't exist",
+                verse, name
+            )));
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Fields and messages of the problems with a song
+    fn problems(title: &str, song: Value) -> Vec<(String, String)> {
+        let song: Song = serde_json::from_value(song).unwrap();
+        validate_song(title, &song)
+            .into_iter()
+            .map(|problem| {
+                assert_eq!(problem.title, title);
+                (problem.field, problem.message)
+            })
+            .collect()
+    }
+
+    fn problem(field: &str, message: &str) -> (String, String) {
+        (String::from(field), String::from(message))
+    }
+
+    #[test]
+    fn accepts_valid_songs() {
+        let song = json!({
+            "verses": { "v1": "Amazing grace\nHow sweet the sound", "c": "Chorus" },
+            "attribution": "John Newton",
+            "alternateTitles": ["Amazing Grace (My Chains Are Gone)"],
+            "authors": ["John Newton"],
+            "ccliNumber": "22025",
+            "verseOrder": ["v1", "c"],
+            "arrangements": { "Short": ["v1"] },
+            "translations": { "es": { "v1": "Sublime gracia" } },
+            "chords": { "v1": [{ "line": 1, "position": 0, "chord": "D/F#" }] },
+            "key": "G",
+            "tempo": 72,
+            "songbooks": [{ "name": "Hymns", "entry": "12" }],
+            "themes": ["Grace"],
+        });
+        assert!(problems("Amazing Grace", song).is_empty());
+        assert!(problems("Empty", json!({})).is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_titles_and_names() {
+        let song = json!({
+            "verses": { " ": "Text" },
+            "alternateTitles": [""],
+            "authors": ["John Newton", " "],
+            "themes": [""],
+            "songbooks": [{ "name": " " }],
+            "translations": { "": { "v1": "Text" }, "es": { " ": "Texto" } },
+        });
+        assert_eq!(
+            problems(" ", song),
+            [
+                problem("title", "Title can't be empty"),
+                problem("verses", "Verse names can't be empty"),
+                problem("alternateTitles", "Values can't be empty"),
+                problem("authors", "Values can't be empty"),
+                problem("themes", "Values can't be empty"),
+                problem("songbooks", "Song book names can't be empty"),
+                problem("translations", "Language can't be empty"),
+                problem("translations", "Verse names in \"es\" can't be empty"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_missing_verses() {
+        let song = json!({
+            "verses": { "v1": "Text" },
+            "verseOrder": ["v1", "v2"],
+            "arrangements": { "Full": ["v1", "c"], " ": ["v1"], "Empty": [] },
+            "chords": { "v3": [{ "line": 0, "position": 0, "chord": "G" }] },
+        });
+        assert_eq!(
+            problems("Song", song),
+            [
+                problem("verseOrder", "Verse \"v2\" doesn't exist"),
+                problem("arrangements", "Arrangement names can't be empty"),
+                problem("arrangements", "Arrangement \"Empty\" has no verses"),
+                problem(
+                    "arrangements",
+                    "Verse \"c\" in arrangement \"Full\" doesn't exist"
+                ),
+                problem("chords", "Verse \"v3\" doesn't exist"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_metadata() {
+        let song = json!({ "ccliNumber": "CCLI 22025", "key": "H", "tempo": 401 });
+        assert_eq!(
+            problems("Song", song),
+            [
+                problem("ccliNumber", "\"CCLI 22025\" isn't a CCLI song number"),
+                problem("key", "\"H\" isn't a key"),
+                problem("tempo", "Tempo must be between 20 and 400 BPM"),
+            ]
+        );
+
+        let song = json!({ "ccliNumber": "", "key": "D/F#", "tempo": 19 });
+        assert_eq!(
+            problems("Song", song),
+            [
+                problem("ccliNumber", "\"\" isn't a CCLI song number"),
+                problem("key", "\"D/F#\" isn't a key"),
+                problem("tempo", "Tempo must be between 20 and 400 BPM"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_chords_outside_the_verse() {
+        let song = json!({
+            "verses": { "v1": "Line one\nLine two" },
+            "chords": { "v1": [
+                { "line": 1, "position": 0, "chord": "G" },
+                { "line": 2, "position": 0, "chord": "C" },
+                { "line": 0, "position": 4, "chord": " " },
+            ] },
+        });
+        assert_eq!(
+            problems("Song", song),
+            [
+                problem(
+                    "chords",
+                    "Chord \"C\" is on line 3 of verse \"v1\", which only has 2 lines"
+                ),
+                problem("chords", "Empty chord in verse \"v1\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_library_files() {
+        assert!(validate_library_file(b"").ok().unwrap().is_empty());
+        assert!(validate_library_file(b" \n").ok().unwrap().is_empty());
+
+        let library = validate_library_file(br#"{"Amazing Grace": {"verses": {"v1": "Text"}}}"#)
+            .ok()
+            .unwrap();
+        assert!(library.contains_key("Amazing Grace"));
+
+        let problems = validate_library_file(b"[]").err().unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.starts_with("Invalid song library: "));
+
+        let problems = validate_library_file(br#"{"Song": {"verseOrder": ["v1"]}}"#)
+            .err()
+            .unwrap();
+        assert_eq!(problems[0].title, "Song");
+        assert_eq!(problems[0].field, "verseOrder");
+    }
+}
@@ -112,7 +112,9 @@ pub async fn websocket_handler(socket: WebSocket, state: Arc<AppServices>) {
                         }
 
                         // request to set new state
-                        StateRequest::Set { state } => {
+                        StateRequest::Set { mut state } => {
+                            state.strip_for_displays();
+
                             // set state (will trigger response)
                             if watch_send.send(state).is_err() {
                                 return;
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::songs::{chords, slides};
+
 /// State object
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CurrentState {
@@ -17,6 +19,23 @@ impl CurrentState {
             content: Value::Null,
         }
     }
+
+    /// Removes inline chords and slide split markers from the song text (in every language shown)
+    /// so that they aren't shown on audience displays
+    pub fn strip_for_displays(&mut self) {
+        let strip = |text: &str| slides::remove_split_markers(&chords::strip(text));
+
+        if let Some(Value::String(song)) = self.content.get_mut("song") {
+            *song = strip(song);
+        }
+        if let Some(Value::Array(languages)) = self.content.get_mut("songLanguages") {
+            for language in languages {
+                if let Some(Value::String(text)) = language.get_mut("text") {
+                    *text = strip(text);
+                }
+            }
+        }
+    }
 }
 
 impl Default for CurrentState {